@@ -18,14 +18,89 @@ import spire.implicits.cforRange
 
 import filodb.core.DatasetRef
 import filodb.core.binaryrecord2.RecordSchema
-import filodb.core.memstore.PartKeyIndexRaw.{bytesRefToUnsafeOffset, ignoreIndexNames, FACET_FIELD_PREFIX,
-  PART_ID_FIELD}
+import filodb.core.memstore.PartKeyIndexRaw.bytesRefToUnsafeOffset
 import filodb.core.metadata.{PartitionSchema, Schemas}
 import filodb.core.metadata.Column.ColumnType.{MapColumn, StringColumn}
-import filodb.core.query.{ColumnFilter, Filter}
+import filodb.core.query.ColumnFilter
 import filodb.memory.format.{UnsafeUtils, ZeroCopyUTF8String}
 
-object PartKeyTantivyIndex {
+object PartKeyTantivyIndex extends StrictLogging {
+  // Sort modes for labelValuesV2, must match reader.rs
+  val LabelValuesSortByCount = 0
+  val LabelValuesSortLexicographic = 1
+
+  // Value match types for labelValuesV2, must match reader.rs
+  val LabelValuesMatchNone = 0
+  val LabelValuesMatchPrefix = 1
+  val LabelValuesMatchSubstring = 2
+  val LabelValuesMatchRegex = 3
+
+  // Result orders for partIdsFromFilters and partKeyRecordsFromFilters, must match reader.rs.
+  // Any order other than none is applied before the limit, so the same subset is always returned.
+  val ResultOrderNone = 0
+  val ResultOrderPartId = 1
+  val ResultOrderStartTime = 2
+  val ResultOrderEndTimeDesc = 3
+
+  // Distinct value count of a label is exact up to this many values, estimated beyond
+  val LabelCardinalityExactThreshold = 10000
+
+  // Default number of records loaded per cursor batch
+  val CursorBatchSize = 1000
+
+  // Pin that reads the live index rather than a pinned searcher
+  val LiveSearcher = 0L
+
+  // Default lifetime of a pinned searcher.  The native side caps lifetimes at 10 minutes.
+  val SearcherPinLifetimeMs = 60000L
+
+  /**
+   * Cardinality of one label among matching series.  distinctValues is estimated
+   * when above the exact threshold.
+   */
+  case class LabelCardinality(label: String, distinctValues: Long, seriesCount: Long)
+
+  /**
+   * Number of series sharing one combination of label values.  Values are in the order the labels
+   * were requested, and are empty for series missing a label.
+   */
+  case class LabelGroupCount(values: Seq[String], count: Long)
+
+  /**
+   * What the native side did to answer a query.  limitHit means results were cut at the limit, and is not
+   * set when there were exactly as many results as the limit.  segmentsSkipped counts segments that could
+   * not match, segmentsAfterLimit those left unsearched once the limit was reached.  Fan out queries sum the
+   * stats of every index.
+   */
+  case class QueryStats(limitHit: Boolean, docsExamined: Long, segmentsRead: Int, segmentsSkipped: Int,
+                        segmentsCached: Int, segmentsAfterLimit: Int) {
+    def cacheServed: Boolean = segmentsRead > 0 && segmentsCached == segmentsRead
+  }
+
+  /**
+   * Label names of matching series with the number of series carrying each, most common first.
+   * truncated is set if there were more names than the limit.
+   */
+  case class LabelNameCounts(names: Seq[(String, Long)], truncated: Boolean)
+
+  /**
+   * Series started and ended per time bucket.  Bucket i covers startTime + i * bucketWidth for
+   * bucketWidth millis.
+   */
+  case class ChurnHistogram(startTime: Long, bucketWidth: Long, started: Array[Long], ended: Array[Long])
+
+  /**
+   * Requested label values of one series.  Labels the series does not have are left out.
+   */
+  case class SeriesLabels(partId: Int, labels: Map[String, String])
+
+  /**
+   * One level of a cardinality rollup.  totalCount and activeCount include all series below this node.
+   * The root has an empty value.
+   */
+  case class CardinalityRollupNode(value: String, totalCount: Long, activeCount: Long,
+                                   children: Seq[CardinalityRollupNode])
+
   def startMemoryProfiling(): Unit = {
     TantivyNativeMethods.startMemoryProfiling()
   }
@@ -33,6 +108,169 @@ object PartKeyTantivyIndex {
   def stopMemoryProfiling(): Unit = {
     TantivyNativeMethods.stopMemoryProfiling()
   }
+
+  // Process wide query cache shared by all indexes that opt in.  Created by the first index to use it
+  // and freed when the last one closes.
+  private var sharedQueryCache: Long = 0
+  private var sharedQueryCacheMaxSize: Long = 0
+  private var sharedQueryCacheItemSize: Long = 0
+  private var sharedQueryCacheUsers: Int = 0
+
+  /**
+   * Get the shared query cache, creating it if this is the first user.  Every call must be paired with
+   * releaseSharedQueryCache.  The sizes of the first user win, so later users asking for other sizes
+   * are logged.
+   */
+  def acquireSharedQueryCache(maxSize: Long, estimatedItemSize: Long): Long = synchronized {
+    if (sharedQueryCache == 0) {
+      sharedQueryCache = TantivyNativeMethods.newSharedQueryCache(maxSize, estimatedItemSize)
+      sharedQueryCacheMaxSize = maxSize
+      sharedQueryCacheItemSize = estimatedItemSize
+    } else if (maxSize != sharedQueryCacheMaxSize || estimatedItemSize != sharedQueryCacheItemSize) {
+      logger.warn(s"Shared query cache already created with maxSize=$sharedQueryCacheMaxSize " +
+        s"estimatedItemSize=$sharedQueryCacheItemSize, ignoring maxSize=$maxSize " +
+        s"estimatedItemSize=$estimatedItemSize")
+    }
+
+    sharedQueryCacheUsers += 1
+    sharedQueryCache
+  }
+
+  /**
+   * Release a shared query cache from acquireSharedQueryCache, freeing it after the last user.  Index
+   * handles keep their own reference to the cache, so this is safe even if one is still open.
+   */
+  def releaseSharedQueryCache(): Unit = synchronized {
+    if (sharedQueryCacheUsers > 0) {
+      sharedQueryCacheUsers -= 1
+
+      if (sharedQueryCacheUsers == 0) {
+        TantivyNativeMethods.freeSharedQueryCache(sharedQueryCache)
+        sharedQueryCache = 0
+      }
+    }
+  }
+
+  // Process wide pool for fan out queries over several shards
+  private[memstore] val fanOutPool = new FanOutPoolRefs(
+    () => TantivyNativeMethods.newFanOutPool(Runtime.getRuntime.availableProcessors),
+    TantivyNativeMethods.freeFanOutPool)
+
+  /**
+   * Label values with series counts summed over several shards.  The query runs on every shard in parallel
+   * natively and at most limit values are returned.  Each shard only contributes its own top limit values,
+   * which is exact in lexicographic order.  Sorted by count, values near the limit can be undercounted or
+   * missed when they fall outside the top limit of some shard.
+   *
+   * @param sort LabelValuesSortByCount or LabelValuesSortLexicographic
+   */
+  def labelValuesFanOut(indexes: Seq[PartKeyTantivyIndex], colFilters: Seq[ColumnFilter], startTime: Long,
+                        endTime: Long, colName: String, limit: Int,
+                        sort: Int = LabelValuesSortByCount): Seq[TermInfo] = {
+    if (indexes.isEmpty) {
+      Seq.empty
+    } else {
+      val queryBuilder = new TantivyQueryBuilder()
+      val query = queryBuilder.buildQuery(colFilters)
+
+      val results = fanOutPool.withPool { pool =>
+        TantivyNativeMethods.labelValuesFanOut(pool, indexes.map(_.indexHandle).toArray, query, colName, limit,
+          sort, startTime, endTime)
+      }
+
+      indexes.head.decodeTermInfoArray(results)
+    }
+  }
+
+  /**
+   * Part key records matching the filters over several shards, each with the index it came from.  Records
+   * are taken in the order of indexes until limit records are found in total.
+   */
+  def partKeyRecordsFanOut(indexes: Seq[PartKeyTantivyIndex], colFilters: Seq[ColumnFilter], startTime: Long,
+                           endTime: Long, limit: Int): Seq[(PartKeyTantivyIndex, PartKeyLuceneIndexRecord)] = {
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val shards = indexes.toIndexedSeq
+    val results = fanOutPool.withPool { pool =>
+      TantivyNativeMethods.partKeyRecordsFanOut(pool, shards.map(_.indexHandle).toArray, query, limit,
+        startTime, endTime)
+    }
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    val parsedResults = new ArrayBuffer[(PartKeyTantivyIndex, PartKeyLuceneIndexRecord)]()
+
+    while (buffer.hasRemaining) {
+      val index = shards(buffer.getInt)
+      val start = buffer.getLong
+      val end = buffer.getLong
+      val pkLen = buffer.getInt
+      val pk = new Array[Byte](pkLen)
+      buffer.get(pk)
+
+      parsedResults += index -> PartKeyLuceneIndexRecord(pk, start, end)
+    }
+
+    parsedResults
+  }
+}
+
+/**
+ * Reference counts for the fan out pool.  The pool is created on first use and freed once every open index
+ * has closed and no fan out call is still running on it, since fan out queries need open indexes.
+ */
+private[memstore] class FanOutPoolRefs(newPool: () => Long, freePool: Long => Unit) {
+  private var pool: Long = 0
+  private var runningCalls: Int = 0
+  private var openIndexes: Int = 0
+
+  /**
+   * Run func with the pool, which is not freed until func returns.
+   */
+  def withPool[T](func: Long => T): T = {
+    val handle = synchronized {
+      if (pool == 0) {
+        pool = newPool()
+      }
+      runningCalls += 1
+
+      pool
+    }
+
+    try {
+      func(handle)
+    } finally {
+      synchronized {
+        runningCalls -= 1
+        freeIfUnused()
+      }
+    }
+  }
+
+  def indexOpened(): Unit = synchronized {
+    openIndexes += 1
+  }
+
+  def indexClosed(): Unit = synchronized {
+    if (openIndexes > 0) {
+      openIndexes -= 1
+
+      freeIfUnused()
+    }
+  }
+
+  def allocated: Boolean = synchronized {
+    pool != 0
+  }
+
+  private def freeIfUnused(): Unit = synchronized {
+    if (openIndexes == 0 && runningCalls == 0 && pool != 0) {
+      freePool(pool)
+      pool = 0
+    }
+  }
 }
 
 class PartKeyTantivyIndex(ref: DatasetRef,
@@ -45,7 +283,11 @@ class PartKeyTantivyIndex(ref: DatasetRef,
                           queryCacheMaxSize: Long = 50 * 1000 * 1000,
                           queryCacheEstimatedItemSize: Long = 31250,
                           deletedDocMergeThreshold: Float = 0.1f,
-                          addMetricTypeField: Boolean = true
+                          addMetricTypeField: Boolean = true,
+                          sharedQueryCacheMaxSize: Long = 0,
+                          queryParallelism: Int = 0,
+                          partKeyFastField: Boolean = false,
+                          timeBucketSizeMs: Long = 0
                          ) extends PartKeyIndexRaw(ref, shardNum, schema, diskLocation, lifecycleManager,
                               addMetricTypeField = addMetricTypeField) {
 
@@ -58,6 +300,22 @@ class PartKeyTantivyIndex(ref: DatasetRef,
     .withTag("dataset", ref.dataset)
     .withTag("shard", shardNum)
 
+  // Latency of native queries beyond plain label names and values, tagged by query so slow
+  // aggregate queries don't skew the label values latency
+  private def queryLatency(query: String) = Kamon.histogram("index-tantivy-query-latency",
+      MeasurementUnit.time.nanoseconds)
+    .withTag("dataset", ref.dataset)
+    .withTag("shard", shardNum)
+    .withTag("query", query)
+
+  private val labelNamesWithCountsLatency = queryLatency("labelNamesWithCounts")
+  private val labelValuesMultiLatency = queryLatency("labelValuesMulti")
+  private val labelCardinalityLatency = queryLatency("labelCardinality")
+  private val cardinalityRollupLatency = queryLatency("cardinalityRollup")
+  private val countByLabelsLatency = queryLatency("countByLabels")
+  private val churnHistogramLatency = queryLatency("churnHistogram")
+  private val querySeriesLabelsLatency = queryLatency("querySeriesLabels")
+
   // Compute field names for native schema code
   private val schemaFields = schema.columns.filter { c =>
     c.columnType == StringColumn
@@ -73,10 +331,20 @@ class PartKeyTantivyIndex(ref: DatasetRef,
 
   private val schemaMultiColumnFacets = schema.options.multiColumnFacets.keys.toArray
 
+  // Shared query cache handle, or 0 to use a cache dedicated to this index
+  private var sharedQueryCache = if (sharedQueryCacheMaxSize > 0) {
+    PartKeyTantivyIndex.acquireSharedQueryCache(sharedQueryCacheMaxSize, queryCacheEstimatedItemSize)
+  } else {
+    0L
+  }
+
   // Native handle for cross JNI operations
   private var indexHandle: Long = loadIndexData(() => TantivyNativeMethods.newIndexHandle(indexDiskLocation.toString,
     schemaFields, schemaMapFields, schemaMultiColumnFacets, columnCacheCount, queryCacheMaxSize,
-    queryCacheEstimatedItemSize, deletedDocMergeThreshold))
+    queryCacheEstimatedItemSize, deletedDocMergeThreshold, sharedQueryCache, queryParallelism, partKeyFastField,
+    timeBucketSizeMs))
+
+  PartKeyTantivyIndex.fanOutPool.indexOpened()
 
   logger.info(s"Created tantivy index for dataset=$ref shard=$shardNum at $indexDiskLocation")
 
@@ -153,18 +421,62 @@ class PartKeyTantivyIndex(ref: DatasetRef,
     commit()
     TantivyNativeMethods.freeIndexHandle(indexHandle)
     indexHandle = 0
+
+    if (sharedQueryCache != 0) {
+      PartKeyTantivyIndex.releaseSharedQueryCache()
+      sharedQueryCache = 0
+    }
+
+    PartKeyTantivyIndex.fanOutPool.indexClosed()
   }
 
+  // Internal fields are left out natively, before the limit is applied
   override def indexNames(limit: Int): Seq[String] = {
-    decodeStringArray(TantivyNativeMethods.indexNames(indexHandle)).filterNot {
-      n => ignoreIndexNames.contains(n) || n.startsWith(FACET_FIELD_PREFIX)
-    }
+    indexNames(limit, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * Indexed label names as above, read from a searcher pinned with pinSearcher.
+   */
+  def indexNames(limit: Int, pin: Long): Seq[String] = {
+    decodeStringArray(TantivyNativeMethods.indexNames(indexHandle, pin, limit))
   }
 
   override def indexValues(fieldName: String, topK: Int): Seq[TermInfo] = {
-    val results = TantivyNativeMethods.indexValues(indexHandle, fieldName, topK)
+    indexValues(fieldName, topK, exactCounts = false)
+  }
 
-    val buffer = ByteBuffer.wrap(results)
+  /**
+   * Top values of a field over the whole index, ties in count broken by value.  By default counts include deleted
+   * series that have not been merged away yet; exactCounts only counts live series, at the cost of walking postings.
+   */
+  def indexValues(fieldName: String, topK: Int, exactCounts: Boolean): Seq[TermInfo] = {
+    indexValues(fieldName, topK, exactCounts, PartKeyTantivyIndex.LabelValuesMatchNone, "")
+  }
+
+  /**
+   * Top values of a field over the whole index, filtered before the term limit so matches past the first
+   * terms of the field are still found.
+   *
+   * @param matchType one of the LabelValuesMatch constants
+   * @param matchValue prefix, substring or regex to filter values with
+   */
+  def indexValues(fieldName: String, topK: Int, exactCounts: Boolean, matchType: Int,
+                  matchValue: String): Seq[TermInfo] = {
+    indexValues(fieldName, topK, exactCounts, matchType, matchValue, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * Top values of a field over the whole index as above, read from a searcher pinned with pinSearcher.
+   */
+  def indexValues(fieldName: String, topK: Int, exactCounts: Boolean, matchType: Int,
+                  matchValue: String, pin: Long): Seq[TermInfo] = {
+    decodeTermInfoArray(TantivyNativeMethods.indexValues(indexHandle, pin, fieldName, topK, exactCounts,
+      matchType, matchValue))
+  }
+
+  private def decodeTermInfoArray(arr: Array[Byte]): Seq[TermInfo] = {
+    val buffer = ByteBuffer.wrap(arr)
     buffer.order(ByteOrder.LITTLE_ENDIAN)
 
     val parsedResults = new ArrayBuffer[TermInfo]()
@@ -199,11 +511,19 @@ class PartKeyTantivyIndex(ref: DatasetRef,
   }
 
   override def labelNamesEfficient(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long): Seq[String] = {
+    labelNamesEfficient(colFilters, startTime, endTime, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * Label names of series matching the filters, read from a searcher pinned with pinSearcher.
+   */
+  def labelNamesEfficient(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                          pin: Long): Seq[String] = {
     val start = System.nanoTime()
     val queryBuilder = new TantivyQueryBuilder()
     val query = queryBuilder.buildQuery(colFilters)
 
-    val results = TantivyNativeMethods.labelNames(indexHandle, query, LABEL_NAMES_AND_VALUES_DEFAULT_LIMIT,
+    val results = TantivyNativeMethods.labelNames(indexHandle, pin, query, LABEL_NAMES_AND_VALUES_DEFAULT_LIMIT,
       startTime, endTime)
 
     labelValuesQueryLatency.record(System.nanoTime() - start)
@@ -211,19 +531,316 @@ class PartKeyTantivyIndex(ref: DatasetRef,
     decodeStringArray(results)
   }
 
+  /**
+   * Label names of series matching the filters, each with the number of matching series that carry it.
+   * Counts are exact; at most limit names are returned.
+   */
+  def labelNamesWithCounts(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                           limit: Int,
+                           pin: Long = PartKeyTantivyIndex.LiveSearcher): PartKeyTantivyIndex.LabelNameCounts = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.labelNamesWithCounts(indexHandle, pin, query, limit, startTime, endTime)
+
+    labelNamesWithCountsLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    val truncated = buffer.get() != 0
+    val parsedResults = new ArrayBuffer[(String, Long)]()
+
+    while (buffer.hasRemaining) {
+      val count = buffer.getLong
+      val strLen = buffer.getInt
+      val strBytes = new Array[Byte](strLen)
+      buffer.get(strBytes)
+
+      parsedResults += new String(strBytes, StandardCharsets.UTF_8) -> count
+    }
+
+    PartKeyTantivyIndex.LabelNameCounts(parsedResults, truncated)
+  }
+
   override def labelValuesEfficient(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
                                     colName: String, limit: Int): Seq[String] = {
+    labelValuesEfficient(colFilters, startTime, endTime, colName, limit, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * Values of a label for series matching the filters, read from a searcher pinned with pinSearcher.
+   */
+  def labelValuesEfficient(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                           colName: String, limit: Int, pin: Long): Seq[String] = {
     val start = System.nanoTime()
     val queryBuilder = new TantivyQueryBuilder()
     val query = queryBuilder.buildQuery(colFilters)
 
-    val results = TantivyNativeMethods.labelValues(indexHandle, query, colName, limit, startTime, endTime)
+    val results = TantivyNativeMethods.labelValues(indexHandle, pin, query, colName, limit, startTime, endTime)
 
     labelValuesQueryLatency.record(System.nanoTime() - start)
 
     decodeStringArray(results)
   }
 
+  /**
+   * Top values with series counts for each of several labels, running the query once.  Returns the
+   * values for each label in the order requested, most common first.
+   */
+  def labelValuesMulti(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                       labels: Seq[String], topK: Int,
+                       pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[(String, Seq[TermInfo])] = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.labelValuesMulti(indexHandle, pin, query, labels.toArray, topK,
+      startTime, endTime)
+
+    labelValuesMultiLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    labels.map { label =>
+      val valueCount = buffer.getInt
+      val values = (0 until valueCount).map { _ =>
+        val count = buffer.getLong
+        val strLen = buffer.getInt
+        val strBytes = new Array[Byte](strLen)
+        buffer.get(strBytes)
+
+        TermInfo(ZeroCopyUTF8String.apply(strBytes), count.toInt)
+      }
+
+      label -> values
+    }
+  }
+
+  /**
+   * Label values with the number of matching series for each value.
+   *
+   * @param sort LabelValuesSortByCount or LabelValuesSortLexicographic
+   * @param after only return values after this one, for paging.  Requires lexicographic sort.
+   * @param matchType one of the LabelValuesMatch constants, to filter values before the limit
+   * @param matchValue prefix, substring or regex to filter values with
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def labelValuesV2(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                    colName: String, limit: Int, sort: Int = PartKeyTantivyIndex.LabelValuesSortByCount,
+                    after: Option[String] = None, matchType: Int = PartKeyTantivyIndex.LabelValuesMatchNone,
+                    matchValue: String = "",
+                    pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[TermInfo] = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.labelValuesV2(indexHandle, pin, query, colName, limit, sort,
+      after.orNull, matchType, matchValue, startTime, endTime)
+
+    labelValuesQueryLatency.record(System.nanoTime() - start)
+
+    decodeTermInfoArray(results)
+  }
+
+  /**
+   * Distinct value and series counts for every label of series matching the filters, highest
+   * distinct count first.  Useful for finding cardinality explosions.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def labelCardinality(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                       exactThreshold: Int = PartKeyTantivyIndex.LabelCardinalityExactThreshold,
+                       pin: Long = PartKeyTantivyIndex.LiveSearcher)
+    : Seq[PartKeyTantivyIndex.LabelCardinality] = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.labelCardinality(indexHandle, pin, query, startTime, endTime,
+      exactThreshold)
+
+    labelCardinalityLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    val parsedResults = new ArrayBuffer[PartKeyTantivyIndex.LabelCardinality]()
+
+    while (buffer.hasRemaining) {
+      val strLen = buffer.getInt
+      val strBytes = new Array[Byte](strLen)
+      buffer.get(strBytes)
+      val distinctValues = buffer.getLong
+      val seriesCount = buffer.getLong
+
+      parsedResults += PartKeyTantivyIndex.LabelCardinality(new String(strBytes, StandardCharsets.UTF_8),
+        distinctValues, seriesCount)
+    }
+
+    parsedResults
+  }
+
+  /**
+   * Series counts for series matching the filters, grouped by the values of each label in order.
+   * For example labels of Seq("_ws_", "_ns_", "__name__") give a tree of workspace, namespace and
+   * metric counts.  Active counts only include series that are still ingesting.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def cardinalityRollup(colFilters: Seq[ColumnFilter], labels: Seq[String], startTime: Long, endTime: Long,
+                        pin: Long = PartKeyTantivyIndex.LiveSearcher): PartKeyTantivyIndex.CardinalityRollupNode = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.cardinalityRollup(indexHandle, pin, query, labels.toArray, startTime,
+      endTime)
+
+    cardinalityRollupLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    decodeCardinalityRollupNode(buffer)
+  }
+
+  private def decodeCardinalityRollupNode(buffer: ByteBuffer): PartKeyTantivyIndex.CardinalityRollupNode = {
+    val strLen = buffer.getInt
+    val strBytes = new Array[Byte](strLen)
+    buffer.get(strBytes)
+    val totalCount = buffer.getLong
+    val activeCount = buffer.getLong
+    val childCount = buffer.getInt
+
+    val children = (0 until childCount).map(_ => decodeCardinalityRollupNode(buffer))
+
+    PartKeyTantivyIndex.CardinalityRollupNode(new String(strBytes, StandardCharsets.UTF_8), totalCount,
+      activeCount, children)
+  }
+
+  /**
+   * Number of series matching the filters for each combination of values of the given labels,
+   * largest groups first.  At most limit groups are returned.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def countByLabels(colFilters: Seq[ColumnFilter], labels: Seq[String], startTime: Long, endTime: Long,
+                    limit: Int,
+                    pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[PartKeyTantivyIndex.LabelGroupCount] = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.countByLabels(indexHandle, pin, query, labels.toArray, startTime,
+      endTime, limit)
+
+    countByLabelsLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    val parsedResults = new ArrayBuffer[PartKeyTantivyIndex.LabelGroupCount]()
+
+    while (buffer.hasRemaining) {
+      val count = buffer.getLong
+      val values = labels.map { _ =>
+        val strLen = buffer.getInt
+        val strBytes = new Array[Byte](strLen)
+        buffer.get(strBytes)
+
+        new String(strBytes, StandardCharsets.UTF_8)
+      }
+
+      parsedResults += PartKeyTantivyIndex.LabelGroupCount(values, count)
+    }
+
+    parsedResults
+  }
+
+  /**
+   * Number of series matching the filters that started and ended in each bucketWidth wide bucket of
+   * [startTime, endTime).  Series still ingesting have not ended.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def churnHistogram(colFilters: Seq[ColumnFilter], startTime: Long, endTime: Long, bucketWidth: Long,
+                     pin: Long = PartKeyTantivyIndex.LiveSearcher): PartKeyTantivyIndex.ChurnHistogram = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.churnHistogram(indexHandle, pin, query, startTime, endTime, bucketWidth)
+
+    churnHistogramLatency.record(System.nanoTime() - start)
+
+    val (started, ended) = results.splitAt(results.length / 2)
+    PartKeyTantivyIndex.ChurnHistogram(startTime, bucketWidth, started, ended)
+  }
+
+  /**
+   * Part ID and the values of the given labels for each series matching the filters, read without
+   * loading part keys.  At most limit series are returned.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def querySeriesLabels(colFilters: Seq[ColumnFilter], labels: Seq[String], startTime: Long, endTime: Long,
+                        limit: Int,
+                        pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[PartKeyTantivyIndex.SeriesLabels] = {
+    val start = System.nanoTime()
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(colFilters)
+
+    val results = TantivyNativeMethods.querySeriesLabels(indexHandle, pin, query, labels.toArray, startTime,
+      endTime, limit)
+
+    querySeriesLabelsLatency.record(System.nanoTime() - start)
+
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    val parsedResults = new ArrayBuffer[PartKeyTantivyIndex.SeriesLabels]()
+
+    while (buffer.hasRemaining) {
+      val partId = buffer.getInt
+      val values = labels.map { label =>
+        val strLen = buffer.getInt
+        val strBytes = new Array[Byte](strLen)
+        buffer.get(strBytes)
+
+        label -> new String(strBytes, StandardCharsets.UTF_8)
+      }
+
+      parsedResults += PartKeyTantivyIndex.SeriesLabels(partId, values.filter(_._2.nonEmpty).toMap)
+    }
+
+    parsedResults
+  }
+
+  /**
+   * Stats of the last query this thread ran against any Tantivy index, for example to tell whether
+   * the results of partIdsFromFilters were truncated.  Must be called on the thread that ran the query.
+   * Queries that do not search the index, such as indexNames, report empty stats.  Prefer withQueryStats,
+   * which cannot be separated from its query by another query or a thread hop.
+   */
+  def lastQueryStats(): PartKeyTantivyIndex.QueryStats = {
+    val stats = TantivyNativeMethods.lastQueryStats()
+
+    PartKeyTantivyIndex.QueryStats(stats(0) != 0, stats(1), stats(2).toInt, stats(3).toInt, stats(4).toInt,
+      stats(5).toInt)
+  }
+
+  /**
+   * Runs f and returns its result with the stats of the last query it ran.  f must run its queries on the
+   * calling thread, as the stats are kept per thread.
+   */
+  def withQueryStats[T](f: => T): (T, PartKeyTantivyIndex.QueryStats) = {
+    val result = f
+    (result, lastQueryStats())
+  }
+
   override def addPartKey(partKeyOnHeapBytes: Array[Byte], partId: Int, startTime: Long, endTime: Long,
                           partKeyBytesRefOffset: Int)(partKeyNumBytes: Int, documentId: String): Unit = {
     logger.debug(s"Adding document ${partKeyString(documentId, partKeyOnHeapBytes, partKeyBytesRefOffset)} " +
@@ -241,14 +858,29 @@ class PartKeyTantivyIndex(ref: DatasetRef,
   }
 
   override def partKeyFromPartId(partId: Int): Option[BytesRef] = {
-    val results = searchFromFilters(Seq(ColumnFilter(PART_ID_FIELD, Filter.Equals(partId.toString))),
-      0, Long.MaxValue, 1, TantivyNativeMethods.queryPartKey)
+    val results = partKeysFromPartIds(Iterator.single(partId))
 
-    if (results == null) {
-      None
-    } else {
-      Some(new BytesRef(results, 0, results.length))
-    }
+    results.get(partId)
+  }
+
+  /**
+   * Look up part keys for many part IDs in one call.  IDs that are not in the index are left out
+   * of the result.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def partKeysFromPartIds(partIds: Iterator[Int],
+                          pin: Long = PartKeyTantivyIndex.LiveSearcher): debox.Map[Int, BytesRef] = {
+    val startExecute = System.nanoTime()
+    val partIdsArray = partIds.toArray
+
+    val result = debox.Map.empty[Int, BytesRef]
+    val rawResult = TantivyNativeMethods.partKeysFromPartIds(indexHandle, pin, partIdsArray)
+    decodePartIdsAndKeys(rawResult, (partId, partKey) => result.update(partId, partKey))
+
+    queryIndexLookupLatency.record(System.nanoTime - startExecute)
+
+    result
   }
 
   private val NOT_FOUND = -1
@@ -288,6 +920,49 @@ class PartKeyTantivyIndex(ref: DatasetRef,
     result
   }
 
+  /**
+   * Look up end times for many part IDs in one call.  IDs that are not in the index are left out
+   * of the result.
+   */
+  def endTimeFromPartIds(partIds: Iterator[Int]): debox.Map[Int, Long] = {
+    val startExecute = System.nanoTime()
+    val partIdsArray = partIds.toArray
+
+    val result = debox.Map.empty[Int, Long]
+    val rawResult = TantivyNativeMethods.endTimeFromPartIds(indexHandle, partIdsArray)
+    var idx = 0
+    while (idx < rawResult.length) {
+      result.update(rawResult(idx).toInt, rawResult(idx + 1))
+      idx += 2
+    }
+
+    queryIndexLookupLatency.record(System.nanoTime - startExecute)
+
+    result
+  }
+
+  /**
+   * Look up part IDs for many part keys in one call.  The result lines up with the input, with -1
+   * for part keys that are not in the index.
+   */
+  def partIdsFromPartKeys(partKeys: Seq[Array[Byte]]): Array[Int] = {
+    val startExecute = System.nanoTime()
+
+    // Pack as length then bytes for each part key
+    val packed = ByteBuffer.allocate(partKeys.map(_.length + 4).sum)
+    packed.order(ByteOrder.LITTLE_ENDIAN)
+    partKeys.foreach { pk =>
+      packed.putInt(pk.length)
+      packed.put(pk)
+    }
+
+    val result = TantivyNativeMethods.partIdsFromPartKeys(indexHandle, packed.array())
+
+    partIdFromPartKeyLookupLatency.record(System.nanoTime - startExecute)
+
+    result
+  }
+
   override def commit(): Unit = {
     TantivyNativeMethods.commit(indexHandle)
   }
@@ -307,18 +982,51 @@ class PartKeyTantivyIndex(ref: DatasetRef,
       partId, documentId, startTime, endTime, upsert = true)
   }
 
+  /**
+   * Pin the current view of the index so several calls passing the pin, such as label names and values,
+   * part IDs, part key records, label cardinality and counts by label, see the same series even if a
+   * commit or merge lands between them.  The pin is dropped after lifetimeMs
+   * if it is not released first; calls using an expired pin throw.
+   *
+   * Calls without a pin parameter always read the live index: partIdFromPartKeySlow, partIdsFromPartKeys,
+   * the start and end time lookups by part ID, partIdsEndedBefore and indexNumEntries.
+   */
+  def pinSearcher(lifetimeMs: Long = PartKeyTantivyIndex.SearcherPinLifetimeMs): Long = {
+    TantivyNativeMethods.pinSearcher(indexHandle, lifetimeMs)
+  }
+
+  /**
+   * Release a pin from pinSearcher.  Returns false if it was already released or had expired.
+   */
+  def releaseSearcher(pin: Long): Boolean = {
+    TantivyNativeMethods.releaseSearcher(indexHandle, pin)
+  }
+
+  /**
+   * Run func with a pinned searcher, releasing it afterwards.
+   */
+  def withPinnedSearcher[T](func: Long => T): T = {
+    val pin = pinSearcher()
+    try {
+      func(pin)
+    } finally {
+      releaseSearcher(pin)
+    }
+  }
+
   override def refreshReadersBlocking(): Unit = {
     TantivyNativeMethods.refreshReaders(indexHandle)
   }
 
   private def searchFromFilters[T](columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
-                                   limit: Int,
-                                   searchFunc: (Long, Array[Byte], Long, Long, Long) => Array[T]): Array[T] = {
+                                   limit: Int, order: Int, pin: Long,
+                                   searchFunc: (Long, Long, Array[Byte], Long, Long, Long, Int) => Array[T])
+    : Array[T] = {
     val startExecute = System.nanoTime()
     val span = Kamon.currentSpan()
     val queryBuilder = new TantivyQueryBuilder()
     val query = queryBuilder.buildQuery(columnFilters)
-    val results = searchFunc(indexHandle, query, limit, startTime, endTime)
+    val results = searchFunc(indexHandle, pin, query, limit, startTime, endTime, order)
     val latency = System.nanoTime - startExecute
     span.mark(s"index-partition-lookup-latency=${latency}ns")
     queryIndexLookupLatency.record(latency)
@@ -328,7 +1036,18 @@ class PartKeyTantivyIndex(ref: DatasetRef,
 
   override def partIdsFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
                                   limit: Int): Buffer[Int] = {
-    val results = searchFromFilters(columnFilters, startTime, endTime, limit, TantivyNativeMethods.queryPartIds)
+    partIdsFromFilters(columnFilters, startTime, endTime, limit, PartKeyTantivyIndex.ResultOrderNone)
+  }
+
+  /**
+   * Part IDs matching the filters, in one of the PartKeyTantivyIndex.ResultOrder orders
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def partIdsFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                         limit: Int, order: Int, pin: Long = PartKeyTantivyIndex.LiveSearcher): Buffer[Int] = {
+    val results = searchFromFilters(columnFilters, startTime, endTime, limit, order, pin,
+      TantivyNativeMethods.queryPartIds)
 
     // "unsafe" means you must not modify the array you're passing in after creating the buffer
     // We don't, so this is more performant
@@ -337,8 +1056,109 @@ class PartKeyTantivyIndex(ref: DatasetRef,
 
   override def partKeyRecordsFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
                                          limit: Int): Seq[PartKeyLuceneIndexRecord] = {
-    val results = searchFromFilters(columnFilters, startTime, endTime, limit, TantivyNativeMethods.queryPartKeyRecords)
+    partKeyRecordsFromFilters(columnFilters, startTime, endTime, limit, PartKeyTantivyIndex.ResultOrderNone)
+  }
+
+  /**
+   * Part key records matching the filters, in one of the PartKeyTantivyIndex.ResultOrder orders
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def partKeyRecordsFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                                limit: Int, order: Int,
+                                pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[PartKeyLuceneIndexRecord] = {
+    val results = searchFromFilters(columnFilters, startTime, endTime, limit, order, pin,
+      TantivyNativeMethods.queryPartKeyRecords)
+
+    decodePartKeyRecords(results)
+  }
 
+  /**
+   * A uniform random sample of up to sampleSize part key records matching the filters.  The same seed
+   * returns the same sample as long as the index is unchanged.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher
+   */
+  def samplePartKeyRecords(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                           sampleSize: Int, seed: Long,
+                           pin: Long = PartKeyTantivyIndex.LiveSearcher): Seq[PartKeyLuceneIndexRecord] = {
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(columnFilters)
+
+    val results = TantivyNativeMethods.samplePartKeyRecords(indexHandle, pin, query, sampleSize, seed,
+      startTime, endTime)
+
+    decodePartKeyRecords(results)
+  }
+
+  /**
+   * Run func on every part key record matching the filters, in index order.  Records are loaded
+   * batchSize at a time from a native cursor, so large result sets are never held in memory at once.
+   * All batches see the same view of the index.  Returns the number of records visited.
+   *
+   * The native cursor is closed automatically if no batch is read for 10 minutes, failing the next batch.
+   *
+   * @param pin searcher pinned with pinSearcher to read from, or LiveSearcher to read the index as it is now
+   */
+  def foreachPartKeyRecordMatchingFilter(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                                         batchSize: Int = PartKeyTantivyIndex.CursorBatchSize,
+                                         pin: Long = PartKeyTantivyIndex.LiveSearcher)
+                                        (func: PartKeyLuceneIndexRecord => Unit): Int = {
+    val queryBuilder = new TantivyQueryBuilder()
+    val query = queryBuilder.buildQuery(columnFilters)
+
+    val cursor = TantivyNativeMethods.openCursor(indexHandle, pin, query, startTime, endTime)
+    try {
+      var count = 0
+      var batch = decodePartKeyRecords(TantivyNativeMethods.nextBatch(indexHandle, cursor, batchSize))
+      while (batch.nonEmpty) {
+        batch.foreach(func)
+        count += batch.length
+        batch = decodePartKeyRecords(TantivyNativeMethods.nextBatch(indexHandle, cursor, batchSize))
+      }
+
+      count
+    } finally {
+      TantivyNativeMethods.closeCursor(indexHandle, cursor)
+    }
+  }
+
+  /**
+   * Run func on the part ID and part key of every series that is still ingesting, i.e. has an end time
+   * of Long.MaxValue.  Returns the number of series visited.
+   */
+  def foreachPartKeyStillIngesting(func: (Int, BytesRef) => Unit): Int = {
+    foreachPartKeyStillIngesting(func, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * Series still ingesting as above, read from a searcher pinned with pinSearcher.
+   */
+  def foreachPartKeyStillIngesting(func: (Int, BytesRef) => Unit, pin: Long): Int = {
+    val results = TantivyNativeMethods.partKeysStillIngesting(indexHandle, pin)
+
+    decodePartIdsAndKeys(results, func)
+  }
+
+  private def decodePartIdsAndKeys(results: Array[Byte], func: (Int, BytesRef) => Unit): Int = {
+    val buffer = ByteBuffer.wrap(results)
+    buffer.order(ByteOrder.LITTLE_ENDIAN)
+
+    var count = 0
+    while (buffer.hasRemaining) {
+      val partId = buffer.getInt
+      val pkLen = buffer.getInt
+      val pk = new Array[Byte](pkLen)
+      buffer.get(pk)
+
+      func(partId, new BytesRef(pk))
+      count += 1
+    }
+
+    count
+  }
+
+  private def decodePartKeyRecords(results: Array[Byte]): Seq[PartKeyLuceneIndexRecord] = {
     val buffer = ByteBuffer.wrap(results)
     buffer.order(ByteOrder.LITTLE_ENDIAN)
 
@@ -374,7 +1194,17 @@ class PartKeyTantivyIndex(ref: DatasetRef,
 
   override def singlePartKeyFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long,
                                         endTime: Long): Option[Array[Byte]] = {
-    val results = searchFromFilters(columnFilters, 0, Long.MaxValue, 1, TantivyNativeMethods.queryPartKey)
+    singlePartKeyFromFilters(columnFilters, startTime, endTime, PartKeyTantivyIndex.LiveSearcher)
+  }
+
+  /**
+   * A part key matching the filters as above, read from a searcher pinned with pinSearcher.
+   */
+  def singlePartKeyFromFilters(columnFilters: Seq[ColumnFilter], startTime: Long, endTime: Long,
+                               pin: Long): Option[Array[Byte]] = {
+    val results = searchFromFilters(columnFilters, 0, Long.MaxValue, 1, PartKeyTantivyIndex.ResultOrderNone, pin,
+      (handle, searcherPin, query, limit, start, end, _) =>
+        TantivyNativeMethods.queryPartKey(handle, searcherPin, query, limit, start, end))
 
     Option(results)
   }
@@ -448,6 +1278,10 @@ class PartKeyTantivyIndex(ref: DatasetRef,
   def dumpCacheStats(): String = {
     TantivyNativeMethods.dumpCacheStats(indexHandle)
   }
+
+  def dumpQueryCache(topN: Int): String = {
+    TantivyNativeMethods.dumpQueryCache(indexHandle, topN)
+  }
 }
 
 object ByteBufferEncodingUtils {
@@ -628,7 +1462,34 @@ protected object TantivyNativeMethods {
   def newIndexHandle(diskLocation: String, schemaFields: Array[String],
                      schemaMapFields: Array[String], schemaMultiColumnFacets: Array[String],
                      columnCacheSize: Long, queryCacheMaxSize: Long, queryCacheItemSize: Long,
-                     deletedDocMergeThreshold: Float): Long
+                     deletedDocMergeThreshold: Float, sharedQueryCache: Long,
+                     queryParallelism: Int, partKeyFastField: Boolean, timeBucketSizeMs: Long): Long
+
+  // Create a query cache that can be shared by multiple index handles
+  @native
+  def newSharedQueryCache(queryCacheMaxSize: Long, queryCacheItemSize: Long): Long
+
+  // Free a shared query cache handle.  Index handles using it keep their own reference.
+  @native
+  def freeSharedQueryCache(handle: Long): Unit
+
+  // Create a thread pool for queries over several index handles
+  @native
+  def newFanOutPool(threads: Int): Long
+
+  // Free a fan out thread pool
+  @native
+  def freeFanOutPool(pool: Long): Unit
+
+  // Get values with counts for a field summed over several indexes, in the same format as indexValues
+  @native
+  def labelValuesFanOut(pool: Long, handles: Array[Long], query: Array[Byte], colName: String, limit: Int,
+                        sort: Int, start: Long, end: Long): Array[Byte]
+
+  // Get part key records over several indexes, each prefixed with the position of its index in handles
+  @native
+  def partKeyRecordsFanOut(pool: Long, handles: Array[Long], query: Array[Byte], limit: Int, start: Long,
+                           end: Long): Array[Byte]
 
   // Free memory used by an index handle
   @native
@@ -638,6 +1499,14 @@ protected object TantivyNativeMethods {
   @native
   def refreshReaders(handle: Long): Unit
 
+  // Pin the current searcher for up to lifetimeMs, returning a pin for query methods that accept one
+  @native
+  def pinSearcher(handle: Long, lifetimeMs: Long): Long
+
+  // Release a pinned searcher, false if it was already released or expired
+  @native
+  def releaseSearcher(handle: Long, pin: Long): Boolean
+
   // Reset index data (delete all docs)
   @native
   def reset(handle: Long): Unit
@@ -675,34 +1544,109 @@ protected object TantivyNativeMethods {
   @native
   def removePartKeys(handle: Long, keys: Array[Int]): Unit
 
-  // Get the list of unique indexed field names
+  // Get stats of the last query run on the calling thread
   @native
-  def indexNames(handle: Long): Array[Byte]
+  def lastQueryStats(): Array[Long]
+
+  // Get up to limit indexed label names, leaving out internal fields
+  @native
+  def indexNames(handle: Long, pin: Long, limit: Int): Array[Byte]
 
   // Get the list of unique values for a field
   @native
-  def indexValues(handle: Long, fieldName: String, topK: Int): Array[Byte]
+  def indexValues(handle: Long, pin: Long, fieldName: String, topK: Int, exactCounts: Boolean, matchType: Int,
+                  matchValue: String): Array[Byte]
 
   // Get the list of unique indexed field names
   @native
-  def labelNames(handle: Long, query: Array[Byte], limit: Int, start: Long, end: Long): Array[Byte]
+  def labelNames(handle: Long, pin: Long, query: Array[Byte], limit: Int, start: Long, end: Long): Array[Byte]
+
+  // Get label names with the number of matching series carrying each, prefixed by a truncated flag
+  @native
+  def labelNamesWithCounts(handle: Long, pin: Long, query: Array[Byte], limit: Int, start: Long,
+                           end: Long): Array[Byte]
 
   // Get the list of unique values for a field
   @native
-  def labelValues(handle: Long, query: Array[Byte], colName: String, limit: Int, start: Long, end: Long): Array[Byte]
+  def labelValues(handle: Long, pin: Long, query: Array[Byte], colName: String, limit: Int, start: Long,
+                  end: Long): Array[Byte]
+
+  // Get the top values with counts for several fields in one query
+  @native
+  def labelValuesMulti(handle: Long, pin: Long, query: Array[Byte], labels: Array[String], topK: Int,
+                       start: Long, end: Long): Array[Byte]
+
+  // Get values with counts for a field, sorted by count or value, optionally after a cursor value
+  // and filtered by a value matcher
+  @native
+  def labelValuesV2(handle: Long, pin: Long, query: Array[Byte], colName: String, limit: Int, sort: Int,
+                    after: String, matchType: Int, matchValue: String, start: Long, end: Long): Array[Byte]
+
+  // Get distinct value and series counts per label for a query
+  @native
+  def labelCardinality(handle: Long, pin: Long, query: Array[Byte], start: Long, end: Long,
+                       exactThreshold: Int): Array[Byte]
+
+  // Get series counts rolled up by an ordered list of labels, encoded depth first
+  @native
+  def cardinalityRollup(handle: Long, pin: Long, query: Array[Byte], labels: Array[String], start: Long,
+                        end: Long): Array[Byte]
+
+  // Get series counts grouped by the values of a list of labels, largest groups first
+  @native
+  def countByLabels(handle: Long, pin: Long, query: Array[Byte], labels: Array[String], start: Long,
+                    end: Long, limit: Int): Array[Byte]
+
+  // Get series started counts then ended counts per time bucket
+  @native
+  def churnHistogram(handle: Long, pin: Long, query: Array[Byte], start: Long, end: Long,
+                     bucketWidth: Long): Array[Long]
+
+  // Get the part ID and the values of a list of labels for each matching series
+  @native
+  def querySeriesLabels(handle: Long, pin: Long, query: Array[Byte], labels: Array[String], start: Long,
+                        end: Long, limit: Int): Array[Byte]
 
   // Get the list of part IDs given a query
   @native
-  def queryPartIds(handle: Long, query: Array[Byte], limit: Long, start: Long, end: Long): Array[Int]
+  def queryPartIds(handle: Long, pin: Long, query: Array[Byte], limit: Long, start: Long, end: Long,
+                   order: Int): Array[Int]
 
   // Get the list of part IDs given a query
   @native
-  def queryPartKeyRecords(handle: Long, query: Array[Byte], limit: Long, start: Long,
-                          end: Long): Array[Byte]
+  def queryPartKeyRecords(handle: Long, pin: Long, query: Array[Byte], limit: Long, start: Long,
+                          end: Long, order: Int): Array[Byte]
+
+  // Get a seeded random sample of part key records for a query, in queryPartKeyRecords format
+  @native
+  def samplePartKeyRecords(handle: Long, pin: Long, query: Array[Byte], sampleSize: Int, seed: Long,
+                           start: Long, end: Long): Array[Byte]
+
+  // Open a cursor over part key records for a query, returning a cursor ID.  The query runs a batch at a
+  // time as batches are read.  Cursors left idle for 10 minutes are closed automatically, and all cursors
+  // are closed when the index handle is freed
+  @native
+  def openCursor(handle: Long, pin: Long, query: Array[Byte], start: Long, end: Long): Long
+
+  // Get up to maxRecords more records from a cursor, in queryPartKeyRecords format.  Empty when done
+  @native
+  def nextBatch(handle: Long, cursor: Long, maxRecords: Int): Array[Byte]
+
+  // Close a cursor, returning false if it was already closed or expired
+  @native
+  def closeCursor(handle: Long, cursor: Long): Boolean
+
+  // Get part IDs and part keys for a list of part IDs
+  @native
+  def partKeysFromPartIds(handle: Long, pin: Long, partIds: Array[Int]): Array[Byte]
+
+  // Get part IDs and part keys of all series with an open end time
+  @native
+  def partKeysStillIngesting(handle: Long, pin: Long): Array[Byte]
 
   // Get a part key by query
   @native
-  def queryPartKey(handle: Long, query: Array[Byte], limit: Long, start: Long, end: Long): Array[Byte]
+  def queryPartKey(handle: Long, pin: Long, query: Array[Byte], limit: Long, start: Long, end: Long): Array[Byte]
 
   /// Get a part ID from a part key
   @native
@@ -716,6 +1660,14 @@ protected object TantivyNativeMethods {
   @native
   def endTimeFromPartId(handle: Long, partId: Int): Long
 
+  // Get end times for a list of part IDs, as interleaved part ID, end time pairs
+  @native
+  def endTimeFromPartIds(handle: Long, partIds: Array[Int]): Array[Long]
+
+  // Get part IDs for packed part keys, -1 where not found
+  @native
+  def partIdsFromPartKeys(handle: Long, packedPartKeys: Array[Byte]): Array[Int]
+
   // Remove partition IDs and return approximate deleted count
   @native
   def removePartitionsEndedBefore(handle: Long, endedBefore: Long, returnApproxDeletedCount: Boolean): Int
@@ -729,6 +1681,10 @@ protected object TantivyNativeMethods {
   @native
   def dumpCacheStats(handle: Long): String
 
+  // Dump the top query cache entries by weight and by hits - meant for cache tuning
+  @native
+  def dumpQueryCache(handle: Long, topN: Int): String
+
   // Start memory profiling if enabled for this build, or no-op
   @native
   def startMemoryProfiling(): Unit
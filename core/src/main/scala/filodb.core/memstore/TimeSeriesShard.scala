@@ -292,6 +292,11 @@ class TimeSeriesShard(val ref: DatasetRef,
   private val tantivyQueryCacheEstimatedItemSize =
     filodbConfig.getMemorySize("memstore.tantivy.query-cache-estimated-item-size")
   private val tantivyDeletedDocMergeThreshold = filodbConfig.getDouble("memstore.tantivy.deleted-doc-merge-threshold")
+  private val tantivySharedQueryCacheSize =
+    filodbConfig.getMemorySize("memstore.tantivy.shared-query-cache-max-bytes")
+  private val tantivyQueryParallelism = filodbConfig.getInt("memstore.tantivy.query-parallelism")
+  private val tantivyPartKeyFastField = filodbConfig.getBoolean("memstore.tantivy.part-key-fast-field")
+  private val tantivyTimeBucketSize = filodbConfig.getDuration("memstore.tantivy.time-bucket-size")
 
   /////// END CONFIGURATION FIELDS ///////////////////
 
@@ -327,7 +332,11 @@ class TimeSeriesShard(val ref: DatasetRef,
       queryCacheMaxSize = tantivyQueryCacheSize.toBytes,
       queryCacheEstimatedItemSize = tantivyQueryCacheEstimatedItemSize.toBytes,
       deletedDocMergeThreshold = tantivyDeletedDocMergeThreshold.toFloat,
-      addMetricTypeField = typeFieldIndexingEnabled)
+      addMetricTypeField = typeFieldIndexingEnabled,
+      sharedQueryCacheMaxSize = tantivySharedQueryCacheSize.toBytes,
+      queryParallelism = tantivyQueryParallelism,
+      partKeyFastField = tantivyPartKeyFastField,
+      timeBucketSizeMs = tantivyTimeBucketSize.toMillis)
     case x => sys.error(s"Unsupported part key index type: '$x'")
   }
 
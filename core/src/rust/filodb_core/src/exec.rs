@@ -2,7 +2,7 @@
 
 use jni::{sys::jobject, JNIEnv};
 
-use crate::errors::JavaResult;
+use crate::{errors::JavaResult, state::reset_last_search_stats};
 
 /// Execs a function in a JNI context, supplying an environment
 /// and translating any errors to exceptions
@@ -28,6 +28,20 @@ where
     }
 }
 
+/// Execs a query function in a JNI context, like `jni_exec`
+///
+/// The last search stats of the calling thread are cleared first, so a query
+/// that fails or does not search never reports the stats of an earlier one.
+pub fn jni_query_exec<F, T>(env: &mut JNIEnv, func: F) -> T
+where
+    F: FnOnce(&mut JNIEnv) -> JavaResult<T>,
+    T: EarlyReturn,
+{
+    reset_last_search_stats();
+
+    jni_exec(env, func)
+}
+
 /// Trait for early return values when an exception is being thrown
 pub trait EarlyReturn {
     fn abort_value() -> Self;
@@ -39,6 +53,12 @@ impl EarlyReturn for jobject {
     }
 }
 
+impl EarlyReturn for u8 {
+    fn abort_value() -> Self {
+        0
+    }
+}
+
 impl EarlyReturn for i32 {
     fn abort_value() -> Self {
         0
@@ -0,0 +1,486 @@
+//! Methods that run one query over several indexes, such as the shards of a
+//! dataset on one node, and merge the results natively
+
+use hashbrown::HashMap;
+use jni::{
+    objects::{JByteArray, JClass, JLongArray, JString},
+    sys::{jbyteArray, jint, jlong},
+    JNIEnv,
+};
+use tantivy::collector::Collector;
+use tantivy_utils::{
+    collectors::{
+        limited_collector::LimitedCollector,
+        part_key_record_collector::{
+            resolve_part_key_records, PartKeyRecord, PartKeyRecordCollector,
+        },
+        string_field_collector::{StringFieldCollector, ValueOrder},
+    },
+    query::search_stats::SearchStats,
+};
+
+use crate::{
+    errors::{JavaException, JavaResult},
+    exec::{jni_exec, jni_query_exec},
+    jnienv::JNIEnvExt,
+    query_parser::filodb_query::FiloDBQuery,
+    reader::{
+        encode_bytes, encode_values_with_counts, query_label_values, value_order, LabelValueOptions,
+    },
+    state::{
+        last_search_stats, reset_last_search_stats, set_last_search_stats, FanOutPoolHandle,
+        IndexHandle,
+    },
+};
+
+/// Create a thread pool for fan out queries
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newFanOutPool(
+    mut env: JNIEnv,
+    _class: JClass,
+    threads: jint,
+) -> jlong {
+    jni_exec(&mut env, |_| {
+        Ok(FanOutPoolHandle::new_handle(threads.max(0) as usize)?)
+    })
+}
+
+/// Free a fan out pool.  No fan out call may still be running on it, which
+/// the caller must ensure.  A 0 handle is ignored.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_freeFanOutPool(
+    mut env: JNIEnv,
+    _class: JClass,
+    pool: jlong,
+) {
+    jni_exec(&mut env, |_| {
+        if pool == 0 {
+            return Ok(());
+        }
+
+        unsafe {
+            drop(Box::from_raw(pool as *mut FanOutPoolHandle));
+        }
+
+        Ok(())
+    });
+}
+
+// Decode an array of index handles
+fn get_index_handles<'a>(
+    env: &mut JNIEnv,
+    handles: &JLongArray,
+) -> JavaResult<Vec<&'a IndexHandle>> {
+    let len = env.get_array_length(handles)?;
+
+    let mut handle_values = vec![0i64; len as usize];
+    env.get_long_array_region(handles, 0, &mut handle_values[..])?;
+
+    if handle_values.contains(&0) {
+        return Err(JavaException::new_runtime_exception(
+            "Fan out query given a null index handle",
+        ));
+    }
+
+    Ok(handle_values
+        .into_iter()
+        .map(IndexHandle::get_ref_from_handle)
+        .collect())
+}
+
+// Run a function on every item in the pool, returning results in item order
+// along with the search stats summed over all items
+fn fan_out<I, T, F>(
+    pool: &FanOutPoolHandle,
+    items: Vec<I>,
+    func: F,
+) -> JavaResult<(Vec<T>, SearchStats)>
+where
+    I: Send,
+    T: Send,
+    F: Fn(I) -> JavaResult<T> + Sync,
+{
+    // Stats are per thread, so they are read on the thread that ran each item
+    let shard_results = pool.executor.map(
+        |item| {
+            reset_last_search_stats();
+
+            Ok(func(item).map(|results| (results, last_search_stats())))
+        },
+        items.into_iter(),
+    )?;
+
+    let mut stats = SearchStats::default();
+    let mut results = Vec::with_capacity(shard_results.len());
+    for shard_result in shard_results {
+        let (values, shard_stats) = shard_result?;
+
+        stats.add(&shard_stats);
+        results.push(values);
+    }
+
+    Ok((results, stats))
+}
+
+// Values of a field with series counts summed over all indexes, in `order`.
+// Each index contributes at most `limit` values to the merge.
+#[allow(clippy::too_many_arguments)]
+fn label_values_fan_out(
+    pool: &FanOutPoolHandle,
+    handles: Vec<&IndexHandle>,
+    query: &FiloDBQuery,
+    field: &str,
+    limit: usize,
+    order: ValueOrder,
+    start: i64,
+    end: i64,
+) -> JavaResult<(Vec<(String, u64)>, SearchStats)> {
+    let Some(first) = handles.first() else {
+        return Ok((vec![], SearchStats::default()));
+    };
+    let merge_collector =
+        StringFieldCollector::new(field, limit, usize::MAX, first.column_cache.clone())
+            .with_order(order);
+
+    // A limited collector by count stops at the first values found, so each
+    // index counts every value to rank them and keeps its top `limit`.  In
+    // lexicographic order the collector itself only keeps the smallest values.
+    let shard_limit = match order {
+        ValueOrder::CountDesc => usize::MAX,
+        ValueOrder::Lexicographic => limit,
+    };
+
+    let (shard_results, mut stats) = fan_out(pool, handles, |handle| {
+        let mut values = query_label_values(
+            query.clone(),
+            handle,
+            &handle.searcher(),
+            field.to_string(),
+            shard_limit,
+            usize::MAX,
+            start,
+            end,
+            LabelValueOptions {
+                order,
+                ..Default::default()
+            },
+        )?;
+
+        let truncated = values.len() > limit;
+        values.truncate(limit);
+
+        Ok((values.into_iter().collect::<HashMap<_, _>>(), truncated))
+    })?;
+
+    stats.limit_hit |= shard_results.iter().any(|(_, truncated)| *truncated);
+
+    // Merge as if each index were a segment of one index
+    let shard_results = shard_results
+        .into_iter()
+        .map(|(values, _)| values)
+        .collect();
+    let results = merge_collector.merge_fruits(shard_results)?;
+
+    stats.limit_hit |= merge_collector.truncated();
+
+    Ok((results, stats))
+}
+
+// Part key records over all indexes in index order, up to `limit` in total,
+// each with the position of its index in `handles`
+fn part_key_records_fan_out(
+    pool: &FanOutPoolHandle,
+    handles: Vec<&IndexHandle>,
+    query: &FiloDBQuery,
+    limit: usize,
+    start: i64,
+    end: i64,
+) -> JavaResult<(Vec<(i32, PartKeyRecord)>, SearchStats)> {
+    // No index can contribute more than the global limit
+    let (shard_results, mut stats) = fan_out(pool, handles, |handle| {
+        let searcher = handle.searcher();
+
+        let collector = PartKeyRecordCollector::new(limit, handle.column_cache.clone());
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+        let records = handle.execute_cachable_query_with_searcher(
+            query.clone(),
+            filter_collector,
+            &searcher,
+        )?;
+
+        Ok((handle, searcher, records))
+    })?;
+
+    // Apply the global limit before loading any part keys
+    let mut remaining = limit;
+    let mut kept = Vec::with_capacity(shard_results.len());
+    for (shard, (handle, searcher, mut records)) in shard_results.into_iter().enumerate() {
+        stats.limit_hit |= records.len() > remaining;
+        records.truncate(remaining);
+        remaining -= records.len();
+
+        if !records.is_empty() {
+            kept.push((shard as i32, handle, searcher, records));
+        }
+    }
+
+    let (resolved, _) = fan_out(pool, kept, |(shard, handle, searcher, records)| {
+        let records = resolve_part_key_records(&searcher, &handle.column_cache, &records)?;
+
+        Ok(records.into_iter().map(|r| (shard, r)).collect::<Vec<_>>())
+    })?;
+
+    Ok((resolved.into_iter().flatten().collect(), stats))
+}
+
+/// Values of a field with series counts summed over all indexes, in the same
+/// format as indexValues and in the order given by `sort` as in labelValuesV2.
+///
+/// Each index only passes its own top `limit` values to the merge, so the
+/// merge never holds every distinct value of every index.  In lexicographic
+/// order the merged result is exact, and each index only loads its smallest
+/// values.  By count, a value outside the top `limit` of
+/// some index is missing that index's count, so values near the cut can be
+/// undercounted or left out.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labelValuesFanOut(
+    mut env: JNIEnv,
+    _class: JClass,
+    pool: jlong,
+    handles: JLongArray,
+    query: JByteArray,
+    field: JString,
+    limit: jint,
+    sort: jint,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let pool = FanOutPoolHandle::get_ref_from_handle(pool);
+        let handles = get_index_handles(env, &handles)?;
+
+        let field = env.get_rust_string(&field)?;
+        let order = value_order(sort)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let (results, stats) = label_values_fan_out(
+            pool,
+            handles,
+            &query,
+            &field,
+            limit.max(0) as usize,
+            order,
+            start,
+            end,
+        )?;
+
+        set_last_search_stats(stats);
+
+        encode_values_with_counts(env, results.into_iter())
+    })
+}
+
+/// Part key records matching a query over all indexes.  Records are taken in
+/// index order up to `limit` in total, each prefixed with the position of its
+/// index in `handles` (i32) and otherwise in the format of queryPartKeyRecords.
+/// Part keys are only loaded for records within the limit.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_partKeyRecordsFanOut(
+    mut env: JNIEnv,
+    _class: JClass,
+    pool: jlong,
+    handles: JLongArray,
+    query: JByteArray,
+    limit: jint,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let pool = FanOutPoolHandle::get_ref_from_handle(pool);
+        let handles = get_index_handles(env, &handles)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let (results, stats) =
+            part_key_records_fan_out(pool, handles, &query, limit.max(0) as usize, start, end)?;
+
+        set_last_search_stats(stats);
+
+        let results_len: usize = results
+            .iter()
+            .map(|(_, r)| std::mem::size_of::<i32>() + r.serialized_len())
+            .sum();
+        let mut serialzied_bytes: Vec<u8> = Vec::with_capacity(results_len);
+
+        for (shard, record) in results {
+            serialzied_bytes.extend(shard.to_le_bytes());
+            record.serialize(&mut serialzied_bytes);
+        }
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::Executor;
+    use tantivy_utils::collectors::{
+        column_cache::ColumnCache, part_id_collector::PartIdCollector,
+    };
+
+    use crate::test_utils::TestIndexHandle;
+
+    use super::*;
+
+    fn test_pool() -> FanOutPoolHandle {
+        FanOutPoolHandle {
+            executor: Executor::multi_thread(2, "test-fanout-").expect("Should succeed"),
+        }
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let pool = test_pool();
+        let first = TestIndexHandle::new(1, 5);
+        let second = TestIndexHandle::new(2, 3);
+
+        let (results, stats) = fan_out(&pool, vec![&*first, &*second], |handle| {
+            let collector = PartIdCollector::new(usize::MAX, ColumnCache::default());
+
+            let mut part_ids = handle.execute_cachable_query(FiloDBQuery::All, collector)?;
+            // Segment order within an index is not fixed
+            part_ids.sort();
+
+            Ok(part_ids)
+        })
+        .expect("Should succeed");
+
+        // Results are in index order and stats are summed
+        assert_eq!(results, vec![vec![0, 1, 2, 3, 4], vec![0, 1, 2, 3, 4, 5]]);
+        assert_eq!(stats.docs_examined, 11);
+        assert_eq!(stats.segments_read, 3);
+        assert!(!stats.limit_hit);
+    }
+
+    #[test]
+    fn test_label_values_fan_out() {
+        let pool = test_pool();
+        let first = TestIndexHandle::new(1, 5);
+        let second = TestIndexHandle::new(2, 3);
+
+        let values = |limit, order| {
+            label_values_fan_out(
+                &pool,
+                vec![&*first, &*second],
+                &FiloDBQuery::All,
+                "col1",
+                limit,
+                order,
+                0,
+                i64::MAX,
+            )
+            .expect("Should succeed")
+        };
+
+        // Counts are summed over both indexes, ties broken by value
+        let (results, stats) = values(3, ValueOrder::CountDesc);
+        assert_eq!(
+            results,
+            vec![
+                ("V0".to_string(), 4),
+                ("V1".to_string(), 4),
+                ("V2".to_string(), 3)
+            ]
+        );
+        assert!(!stats.limit_hit);
+
+        let (results, stats) = values(2, ValueOrder::CountDesc);
+        assert_eq!(results, vec![("V0".to_string(), 4), ("V1".to_string(), 4)]);
+        assert!(stats.limit_hit);
+
+        // Each index keeps its smallest values, which merge to the exact result
+        let (results, stats) = values(1, ValueOrder::Lexicographic);
+        assert_eq!(results, vec![("V0".to_string(), 4)]);
+        assert!(stats.limit_hit);
+
+        let (results, stats) = values(3, ValueOrder::Lexicographic);
+        assert_eq!(
+            results,
+            vec![
+                ("V0".to_string(), 4),
+                ("V1".to_string(), 4),
+                ("V2".to_string(), 3)
+            ]
+        );
+        assert!(!stats.limit_hit);
+
+        let (results, _) = label_values_fan_out(
+            &pool,
+            vec![],
+            &FiloDBQuery::All,
+            "col1",
+            2,
+            ValueOrder::CountDesc,
+            0,
+            i64::MAX,
+        )
+        .expect("Should succeed");
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_part_key_records_fan_out() {
+        let pool = test_pool();
+        // Single segments, so records are in doc order within each index
+        let first = TestIndexHandle::new(1, 5);
+        let second = TestIndexHandle::new(1, 6);
+
+        let records = |limit| {
+            let (results, stats) = part_key_records_fan_out(
+                &pool,
+                vec![&*first, &*second],
+                &FiloDBQuery::All,
+                limit,
+                0,
+                i64::MAX,
+            )
+            .expect("Should succeed");
+
+            let results: Vec<_> = results
+                .into_iter()
+                .map(|(shard, record)| (shard, record.start_time))
+                .collect();
+
+            (results, stats.limit_hit)
+        };
+
+        // Records are taken in index order up to the limit
+        assert_eq!(
+            records(7),
+            (
+                vec![
+                    (0, 0),
+                    (0, 100),
+                    (0, 200),
+                    (0, 300),
+                    (0, 400),
+                    (1, 0),
+                    (1, 100)
+                ],
+                true
+            )
+        );
+
+        let (results, limit_hit) = records(11);
+        assert_eq!(results.len(), 11);
+        assert!(!limit_hit);
+
+        // The limit can be filled entirely by earlier indexes
+        assert_eq!(records(2), (vec![(0, 0), (0, 100)], true));
+    }
+}
@@ -2,17 +2,17 @@
 
 use jni::{
     objects::{JClass, JObjectArray, JString},
-    sys::{jfloat, jlong},
+    sys::{jboolean, jfloat, jint, jlong},
     JNIEnv,
 };
 use tantivy::{
     directory::MmapDirectory,
     indexer::LogMergePolicy,
     schema::{
-        BytesOptions, FacetOptions, Field, JsonObjectOptions, NumericOptions, Schema,
+        BytesOptions, FacetOptions, Field, FieldType, JsonObjectOptions, NumericOptions, Schema,
         SchemaBuilder, TextFieldIndexing, TextOptions,
     },
-    IndexBuilder, IndexSettings, ReloadPolicy, TantivyDocument,
+    Index, IndexBuilder, IndexSettings, ReloadPolicy, TantivyDocument,
 };
 use tantivy_utils::field_constants::{self, facet_field_name, LABEL_LIST};
 
@@ -20,7 +20,7 @@ use crate::{
     errors::{JavaException, JavaResult},
     exec::jni_exec,
     jnienv::JNIEnvExt,
-    state::IndexHandle,
+    state::{IndexHandle, SharedQueryCacheHandle},
 };
 
 pub const WRITER_MEM_BUDGET: usize = 50 * 1024 * 1024;
@@ -38,6 +38,10 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newI
     query_cache_max_size: jlong,
     query_cache_estimated_item_size: jlong,
     deleted_doc_merge_threshold: jfloat,
+    shared_query_cache: jlong,
+    query_parallelism: jint,
+    part_key_fast_field: jboolean,
+    time_bucket_size_ms: jlong,
 ) -> jlong {
     jni_exec(&mut env, |env| {
         let disk_location: String = env.get_string(&disk_location)?.into();
@@ -46,18 +50,22 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newI
         let directory = MmapDirectory::open(disk_location)?;
 
         // Build the schema for documents
-        let (schema, default_field) =
-            build_schema(env, &schema_fields, &map_fields, &multi_column_facet_fields)?;
+        let (schema, default_field) = build_schema(
+            env,
+            &schema_fields,
+            &map_fields,
+            &multi_column_facet_fields,
+            part_key_fast_field != 0,
+        )?;
 
         // Open index
         let settings = IndexSettings {
             ..Default::default()
         };
 
-        let index = IndexBuilder::new()
-            .schema(schema.clone())
-            .settings(settings)
-            .open_or_create(directory.clone())?;
+        let index = open_or_create_index(directory.clone(), schema, settings)?;
+        // An existing index may have been created with the other part key fast field setting
+        let schema = index.schema();
 
         let writer = index.writer::<TantivyDocument>(WRITER_MEM_BUDGET)?;
 
@@ -78,6 +86,17 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newI
             .reload_policy(ReloadPolicy::OnCommitWithDelay)
             .try_into()?;
 
+        // 0 means no shared pool - use a dedicated query cache
+        let shared_query_cache = if shared_query_cache != 0 {
+            Some(
+                SharedQueryCacheHandle::get_ref_from_handle(shared_query_cache)
+                    .pool
+                    .clone(),
+            )
+        } else {
+            None
+        };
+
         Ok(IndexHandle::new_handle(
             schema,
             default_field,
@@ -87,6 +106,9 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newI
             column_cache_size as u64,
             query_cache_max_size as u64,
             query_cache_estimated_item_size as u64,
+            shared_query_cache,
+            query_parallelism.max(0) as usize,
+            time_bucket_size_ms.max(0),
         )?)
     })
 }
@@ -106,11 +128,101 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_free
     });
 }
 
+/// Create a query cache pool that can be shared across index handles
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_newSharedQueryCache(
+    mut env: JNIEnv,
+    _class: JClass,
+    query_cache_max_size: jlong,
+    query_cache_estimated_item_size: jlong,
+) -> jlong {
+    jni_exec(&mut env, |_| {
+        if query_cache_max_size <= 0 || query_cache_estimated_item_size <= 0 {
+            return Err(JavaException::new_runtime_exception(
+                "Shared query cache sizes must be positive",
+            ));
+        }
+
+        Ok(SharedQueryCacheHandle::new_handle(
+            query_cache_max_size as u64,
+            query_cache_estimated_item_size as u64,
+        ))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_freeSharedQueryCache(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+) {
+    jni_exec(&mut env, |_| {
+        unsafe {
+            drop(Box::from_raw(handle as *mut SharedQueryCacheHandle));
+        }
+
+        Ok(())
+    });
+}
+
+// Options for the part key field
+fn part_key_options(fast: bool) -> BytesOptions {
+    // Bytes values are faster to read via the doc store vs fast fields and we don't need any of the fast
+    // field only features like iterating by sorted values.  A fast field can optionally be added for
+    // random access to part keys without decompressing doc store blocks.
+    let options = BytesOptions::default().set_indexed().set_stored();
+    if fast {
+        options.set_fast()
+    } else {
+        options
+    }
+}
+
+/// Open the index in a directory, or create it with `schema` if there is none
+///
+/// Part keys can be read with or without the part key fast field, so an index
+/// created with the other fast field setting is opened with the schema it was
+/// created with.  The setting takes effect when the index is rebuilt.  Any
+/// other schema difference fails as usual.
+fn open_or_create_index(
+    directory: MmapDirectory,
+    schema: Schema,
+    settings: IndexSettings,
+) -> tantivy::Result<Index> {
+    if Index::exists(&directory)? {
+        let index = Index::open(directory.clone())?;
+        if same_except_part_key_fast(&index.schema(), &schema) {
+            return Ok(index);
+        }
+    }
+
+    IndexBuilder::new()
+        .schema(schema)
+        .settings(settings)
+        .open_or_create(directory)
+}
+
+// True if two schemas differ at most by the part key fast field setting
+fn same_except_part_key_fast(a: &Schema, b: &Schema) -> bool {
+    a.num_fields() == b.num_fields()
+        && a.fields().zip(b.fields()).all(|((_, a), (_, b))| {
+            match (a.field_type(), b.field_type()) {
+                (FieldType::Bytes(a_options), FieldType::Bytes(b_options))
+                    if a.name() == field_constants::PART_KEY && b.name() == a.name() =>
+                {
+                    a_options.clone().set_fast() == b_options.clone().set_fast()
+                }
+                _ => a == b,
+            }
+        })
+}
+
 fn build_schema(
     env: &mut JNIEnv,
     schema_fields: &JObjectArray,
     map_fields: &JObjectArray,
     multi_column_facet_fields: &JObjectArray,
+    part_key_fast_field: bool,
 ) -> JavaResult<(Schema, Option<Field>)> {
     let mut builder = SchemaBuilder::new();
 
@@ -124,13 +236,12 @@ fn build_schema(
 
     let numeric_options = NumericOptions::default().set_indexed().set_fast();
 
-    // Bytes values are faster to read via the doc store vs fast fields and we don't need any of the fast
-    // field only features like iterating by sorted values
-    let byte_options = BytesOptions::default().set_indexed().set_stored();
-
     builder.add_text_field(field_constants::DOCUMENT_ID, text_options.clone());
     builder.add_i64_field(field_constants::PART_ID, numeric_options.clone());
-    builder.add_bytes_field(field_constants::PART_KEY, byte_options);
+    builder.add_bytes_field(
+        field_constants::PART_KEY,
+        part_key_options(part_key_fast_field),
+    );
     builder.add_i64_field(field_constants::START_TIME, numeric_options.clone());
     builder.add_i64_field(field_constants::END_TIME, numeric_options.clone());
     builder.add_text_field(field_constants::TYPE, text_options.clone());
@@ -181,3 +292,69 @@ fn build_schema(
 
     Ok((builder.build(), default_field))
 }
+
+#[cfg(test)]
+mod tests {
+    use tantivy::schema::{STORED, STRING};
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn test_schema(part_key_fast_field: bool, extra_field: bool) -> Schema {
+        let mut builder = SchemaBuilder::new();
+        builder.add_text_field(field_constants::DOCUMENT_ID, STRING | STORED);
+        builder.add_bytes_field(
+            field_constants::PART_KEY,
+            part_key_options(part_key_fast_field),
+        );
+        if extra_field {
+            builder.add_text_field("extra", STRING);
+        }
+
+        builder.build()
+    }
+
+    fn part_key_is_fast(index: &Index) -> bool {
+        let schema = index.schema();
+        let field = schema
+            .get_field(field_constants::PART_KEY)
+            .expect("Should succeed");
+
+        schema.get_field_entry(field).is_fast()
+    }
+
+    #[test]
+    fn test_open_with_other_part_key_fast_field_setting() {
+        for created_fast in [false, true] {
+            let dir = TempDir::new().expect("Should succeed");
+            let directory = MmapDirectory::open(dir.path()).expect("Should succeed");
+
+            let index = open_or_create_index(
+                directory.clone(),
+                test_schema(created_fast, false),
+                IndexSettings::default(),
+            )
+            .expect("Should succeed");
+            assert_eq!(part_key_is_fast(&index), created_fast);
+            drop(index);
+
+            // Reopening with the other setting keeps the schema the index was created with
+            let index = open_or_create_index(
+                directory.clone(),
+                test_schema(!created_fast, false),
+                IndexSettings::default(),
+            )
+            .expect("Should succeed");
+            assert_eq!(part_key_is_fast(&index), created_fast);
+            drop(index);
+
+            // Other schema changes still fail
+            assert!(open_or_create_index(
+                directory,
+                test_schema(created_fast, true),
+                IndexSettings::default(),
+            )
+            .is_err());
+        }
+    }
+}
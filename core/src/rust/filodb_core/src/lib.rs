@@ -20,6 +20,7 @@
 
 mod errors;
 mod exec;
+mod fanout;
 mod index;
 mod ingestion;
 mod jnienv;
@@ -28,3 +29,5 @@ mod profile;
 mod query_parser;
 mod reader;
 mod state;
+#[cfg(test)]
+mod test_utils;
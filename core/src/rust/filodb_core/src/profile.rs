@@ -3,13 +3,16 @@
 #[cfg(feature = "dhat-heap")]
 use std::sync::Mutex;
 
+use std::{cmp::Reverse, fmt::Write};
+
 use jni::{
     objects::JClass,
-    sys::{jdoubleArray, jlong, jstring},
+    sys::{jdoubleArray, jint, jlong, jstring},
     JNIEnv,
 };
+use tantivy_utils::query::cache::QueryCacheEntryInfo;
 
-use crate::{exec::jni_exec, state::IndexHandle};
+use crate::{exec::jni_exec, query_parser::filodb_query::FiloDBQuery, state::IndexHandle};
 
 /// Get cache stats info
 #[no_mangle]
@@ -24,7 +27,7 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_dump
         let (column_hits, column_misses) = index.column_cache.stats();
         let (query_hits, query_misses) = index.query_cache_stats();
 
-        let output = format!(
+        let mut output = format!(
             "Column cache: {} hits {} misses {}% hit\nQuery cache: {} hits {} misses {}% hit",
             column_hits,
             column_misses,
@@ -34,6 +37,16 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_dump
             cache_hit_rate(query_hits, query_misses),
         );
 
+        if let Some((pool_size, pool_capacity)) = index.shared_query_cache_size() {
+            let _ = write!(
+                output,
+                "\nShared query cache: {} of {} bytes, {} bytes from this index",
+                pool_size,
+                pool_capacity,
+                index.query_cache_size()
+            );
+        }
+
         let java_str = env.new_string(output)?;
 
         Ok(java_str.into_raw())
@@ -77,6 +90,57 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_getC
     })
 }
 
+/// Dump the top query cache entries by weight and by hits
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_dumpQueryCache(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    top_n: jint,
+) -> jstring {
+    jni_exec(&mut env, |env| {
+        let index = IndexHandle::get_ref_from_handle(handle);
+
+        let mut entries = index.query_cache_entries();
+        let top_n = top_n.max(0) as usize;
+
+        let mut output = format!(
+            "Query cache: {} entries {} bytes\n",
+            entries.len(),
+            index.query_cache_size()
+        );
+
+        entries.sort_by_key(|e| Reverse(e.weight));
+        let _ = writeln!(output, "Top {} entries by weight:", top_n);
+        format_cache_entries(&mut output, entries.iter().take(top_n));
+
+        entries.sort_by_key(|e| Reverse(e.hits));
+        let _ = writeln!(output, "Top {} entries by hits:", top_n);
+        format_cache_entries(&mut output, entries.iter().take(top_n));
+
+        let java_str = env.new_string(output)?;
+
+        Ok(java_str.into_raw())
+    })
+}
+
+fn format_cache_entries<'a>(
+    output: &mut String,
+    entries: impl Iterator<Item = &'a QueryCacheEntryInfo<FiloDBQuery>>,
+) {
+    for entry in entries {
+        let _ = writeln!(
+            output,
+            "  weight={} hits={} docs={} segment={} query={}",
+            entry.weight,
+            entry.hits,
+            entry.cardinality,
+            entry.segment_id.short_uuid_string(),
+            entry.query,
+        );
+    }
+}
+
 /// Start memory profiling
 #[no_mangle]
 #[allow(unused_mut, unused_variables)]
@@ -1,4 +1,34 @@
 //! Query parsers and builders
+//!
+//! Query format
+//!
+//! Queries are complex trees of predicates that must be supported. This prevents us from easily
+//! encoding them in primitive types across the JNI boundary.
+//!
+//! To avoid making the Rust code a series of complex reflection operations and to make this
+//! as efficient as possible a new binary format is defined for the JVM code to pass to the Rust
+//! code inside a byte array.
+//!
+//! Each query entry is encoded starting with a single byte type ID.  See the `QueryTypeId` enum
+//! for possible values.  For each child query in a boolean query it is encoded via an 8 bit occur value,
+//! a 8 bit type id, and a 16 bit length followed by a UTF-8 string with the specified length.
+//!
+//! As a simple example, consider a boolean query like:
+//!
+//! f1:ABC AND f2:DEF
+//!
+//! The encoded sequence would roughly look like:
+//!
+//! 01 - start of boolean query
+//!     01 - query must match
+//!         02 - equals query
+//!         03 00 - string of length 3
+//!         41 42 43 - UTF8 encoding of 'ABC'
+//!     01 - query must match
+//!         02 - equals query
+//!         03 00 - string of length 3
+//!         44 45 46 - UTF8 encoding of 'DEF'
+//!     00 - end of boolean query
 
 use std::ops::Bound;
 
@@ -22,36 +52,6 @@ use crate::parser::{parse_string, parse_type_id, AsNomError, ParserError, TypePa
 
 pub mod filodb_query;
 
-/// Query format
-///
-/// Queries are complex trees of predicates that must be supported. This prevents us from easily
-/// encoding them in primitive types across the JNI boundary.
-///
-/// To avoid making the Rust code a series of complex reflection operations and to make this
-/// as efficient as possible a new binary format is defined for the JVM code to pass to the Rust
-/// code inside a byte array.
-///
-/// Each query entry is encoded starting with a single byte type ID.  See the `QueryTypeId` enum
-/// for possible values.  For each child query in a boolean query it is encoded via an 8 bit occur value,
-/// a 8 bit type id, and a 16 bit length followed by a UTF-8 string with the specified length.
-///
-/// As a simple example, consider a boolean query like:
-///
-/// f1:ABC AND f2:DEF
-///
-/// The encoded sequence would roughly look like:
-///
-/// 01 - start of boolean query
-///     01 - query must match
-///         02 - equals query
-///         03 00 - string of length 3
-///         41 42 43 - UTF8 encoding of 'ABC'
-///     01 - query must match
-///         02 - equals query
-///         03 00 - string of length 3
-///         44 45 46 - UTF8 encoding of 'DEF'
-///     00 - end of boolean query
-
 /// Query type encoding
 #[derive(FromPrimitive)]
 #[repr(u8)]
@@ -307,6 +307,89 @@ fn parse_long_range_query<'a>(
     Ok((input, Box::new(query)))
 }
 
+/// Format a binary query as a human readable string
+///
+/// The output loosely follows Lucene query syntax and is meant for
+/// debugging and introspection only.  Unlike `parse_query` no schema
+/// is needed as field names are printed as given.
+pub fn format_query(input: &[u8]) -> IResult<&[u8], String, ParserError> {
+    let (input, type_id) = parse_type_id(input)?;
+
+    match type_id {
+        TypeParseResult::Failure(type_id) => Err(Err::Failure(ParserError::UnknownType(type_id))),
+        TypeParseResult::Success(QueryTypeId::Boolean) => format_boolean_query(input),
+        TypeParseResult::Success(QueryTypeId::Equals) => {
+            let (input, column) = parse_string(input)?;
+            let (input, text) = parse_string(input)?;
+
+            Ok((input, format!("{}:{:?}", column, text)))
+        }
+        TypeParseResult::Success(QueryTypeId::Regex) => {
+            let (input, column) = parse_string(input)?;
+            let (input, text) = parse_string(input)?;
+
+            Ok((input, format!("{}:/{}/", column, text)))
+        }
+        TypeParseResult::Success(QueryTypeId::TermIn) => {
+            let (input, column) = parse_string(input)?;
+            let (input, term_count) = le_u16(input)?;
+
+            let mut terms = Vec::with_capacity(term_count as usize);
+            let mut next_input = input;
+            for _ in 0..term_count {
+                let (input, text) = parse_string(next_input)?;
+                terms.push(format!("{:?}", text));
+                next_input = input;
+            }
+
+            Ok((next_input, format!("{}:({})", column, terms.join(" OR "))))
+        }
+        TypeParseResult::Success(QueryTypeId::Prefix) => {
+            let (input, column) = parse_string(input)?;
+            let (input, text) = parse_string(input)?;
+
+            Ok((input, format!("{}:{:?}*", column, text)))
+        }
+        TypeParseResult::Success(QueryTypeId::MatchAll) => Ok((input, "*:*".to_string())),
+        TypeParseResult::Success(QueryTypeId::LongRange) => {
+            let (input, column) = parse_string(input)?;
+            let (input, start) = le_i64(input)?;
+            let (input, end) = le_i64(input)?;
+
+            Ok((input, format!("{}:[{} TO {}]", column, start, end)))
+        }
+    }
+}
+
+fn format_boolean_query(input: &[u8]) -> IResult<&[u8], String, ParserError> {
+    let mut clauses = vec![];
+    let mut next_input = input;
+    loop {
+        let (input, occur) = parse_type_id(next_input)?;
+
+        let occur = match occur {
+            TypeParseResult::Success(Occurs::Must) => "+",
+            TypeParseResult::Success(Occurs::MustNot) => "-",
+            TypeParseResult::Failure(0) => {
+                // End of boolean marker
+                next_input = input;
+                break;
+            }
+            TypeParseResult::Failure(occur) => {
+                return Err(Err::Failure(ParserError::UnknownOccur(occur)))
+            }
+        };
+
+        let (input, query) = format_query(input)?;
+
+        next_input = input;
+
+        clauses.push(format!("{}{}", occur, query));
+    }
+
+    Ok((next_input, format!("({})", clauses.join(" "))))
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::BufMut;
@@ -994,4 +1077,79 @@ mod tests {
 
         assert_eq!(format!("{err}"), "Parsing Error: Nom(Eof)");
     }
+
+    #[test]
+    fn test_format_query() {
+        let mut buf = vec![];
+
+        let filter1 = "ABC";
+        let filter2 = "a.*";
+
+        buf.put_u8(QueryTypeId::Boolean as u8);
+
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(QueryTypeId::Equals as u8);
+        buf.put_u16_le(COL1_NAME.len() as u16);
+        buf.put_slice(COL1_NAME.as_bytes());
+        buf.put_u16_le(filter1.len() as u16);
+        buf.put_slice(filter1.as_bytes());
+
+        buf.put_u8(Occurs::MustNot as u8);
+        buf.put_u8(QueryTypeId::Regex as u8);
+        buf.put_u16_le(COL2_NAME.len() as u16);
+        buf.put_slice(COL2_NAME.as_bytes());
+        buf.put_u16_le(filter2.len() as u16);
+        buf.put_slice(filter2.as_bytes());
+
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(QueryTypeId::TermIn as u8);
+        buf.put_u16_le(COL1_NAME.len() as u16);
+        buf.put_slice(COL1_NAME.as_bytes());
+        buf.put_u16_le(2);
+        buf.put_u16_le(filter1.len() as u16);
+        buf.put_slice(filter1.as_bytes());
+        buf.put_u16_le(filter2.len() as u16);
+        buf.put_slice(filter2.as_bytes());
+
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(QueryTypeId::Prefix as u8);
+        buf.put_u16_le(COL2_NAME.len() as u16);
+        buf.put_slice(COL2_NAME.as_bytes());
+        buf.put_u16_le(filter1.len() as u16);
+        buf.put_slice(filter1.as_bytes());
+
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(QueryTypeId::LongRange as u8);
+        buf.put_u16_le(PART_ID.len() as u16);
+        buf.put_slice(PART_ID.as_bytes());
+        buf.put_i64_le(1);
+        buf.put_i64_le(2);
+
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(QueryTypeId::MatchAll as u8);
+
+        buf.put_u8(0); // End of boolean marker
+
+        let (rest, formatted) = format_query(&buf).expect("Should succeed");
+
+        assert!(rest.is_empty());
+        assert_eq!(
+            formatted,
+            "(+col1:\"ABC\" -col2:/a.*/ +col1:(\"ABC\" OR \"a.*\") +col2:\"ABC\"* \
+             +__partIdDv__:[1 TO 2] +*:*)"
+        );
+    }
+
+    #[test]
+    fn test_format_query_invalid_type() {
+        let mut buf = vec![];
+
+        buf.put_u8(QueryTypeId::Boolean as u8);
+        buf.put_u8(Occurs::Must as u8);
+        buf.put_u8(255);
+
+        let err = format_query(&buf).expect_err("Should fail");
+
+        assert_eq!(format!("{err}"), "Parsing Failure: UnknownType(255)");
+    }
 }
@@ -1,17 +1,17 @@
 //! Cachable query implementation
 
-use std::{ops::Bound, sync::Arc};
+use std::{fmt::Display, ops::Bound, sync::Arc};
 
 use quick_cache::Weighter;
 use tantivy::{
-    query::{AllQuery, Query, RangeQuery, TermQuery, TermSetQuery},
+    query::{AllQuery, BooleanQuery, Occur, Query, RangeQuery, TermQuery, TermSetQuery},
     schema::{Field, IndexRecordOption, Schema},
     SegmentId, TantivyError, Term,
 };
 use tantivy_common::BitSet;
 use tantivy_utils::field_constants;
 
-use super::parse_query;
+use super::{format_query, parse_query};
 
 /// A query that can potentially be cached
 ///
@@ -27,18 +27,89 @@ pub enum FiloDBQuery {
     ByPartIds(Arc<Box<[i32]>>),
     /// Search by end time
     ByEndTime(i64),
+    /// Search for docs active within a start and end time, used for time
+    /// bucketed filtering.  A start of 0 or less or an end of i64::MAX is unbounded.
+    ActiveInRange(i64, i64),
+    /// Search for docs that are still ingesting, with an end time of i64::MAX
+    StillIngesting,
     /// Search for single part ID (not cached)
     ByPartId(i32),
     /// All docs query (not cached)
     All,
 }
 
+// Max part IDs to print before truncating
+const MAX_DISPLAY_PART_IDS: usize = 10;
+
+impl Display for FiloDBQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FiloDBQuery::Complex(query_bytes) => match format_query(query_bytes) {
+                Ok((_, query)) => write!(f, "{}", query),
+                Err(e) => write!(f, "<invalid query ({} bytes): {}>", query_bytes.len(), e),
+            },
+            FiloDBQuery::ByPartKey(part_key) => {
+                write!(f, "{}:0x", field_constants::PART_KEY)?;
+                for b in part_key.iter() {
+                    write!(f, "{:02x}", b)?;
+                }
+
+                Ok(())
+            }
+            FiloDBQuery::ByPartIds(part_ids) => {
+                let ids: Vec<_> = part_ids
+                    .iter()
+                    .take(MAX_DISPLAY_PART_IDS)
+                    .map(|id| id.to_string())
+                    .collect();
+
+                write!(f, "{}:({}", field_constants::PART_ID, ids.join(" OR "))?;
+                if part_ids.len() > MAX_DISPLAY_PART_IDS {
+                    write!(f, " ... {} total", part_ids.len())?;
+                }
+                write!(f, ")")
+            }
+            FiloDBQuery::ByEndTime(ended_at) => {
+                write!(f, "{}:[0 TO {}]", field_constants::END_TIME, ended_at)
+            }
+            FiloDBQuery::ActiveInRange(start, end) => {
+                let start = if *start > 0 {
+                    start.to_string()
+                } else {
+                    "*".to_string()
+                };
+                let end = if *end < i64::MAX {
+                    end.to_string()
+                } else {
+                    "*".to_string()
+                };
+
+                write!(
+                    f,
+                    "(+{}:[* TO {}] +{}:[{} TO *])",
+                    field_constants::START_TIME,
+                    end,
+                    field_constants::END_TIME,
+                    start
+                )
+            }
+            FiloDBQuery::StillIngesting => {
+                write!(f, "{}:{}", field_constants::END_TIME, i64::MAX)
+            }
+            FiloDBQuery::ByPartId(part_id) => write!(f, "{}:{}", field_constants::PART_ID, part_id),
+            FiloDBQuery::All => write!(f, "*:*"),
+        }
+    }
+}
+
 impl tantivy_utils::query::cache::CachableQuery for FiloDBQuery {
     fn should_cache(&self) -> bool {
         match self {
             FiloDBQuery::Complex(_) => true,
             FiloDBQuery::ByPartIds(_) => true,
             FiloDBQuery::ByEndTime(_) => true,
+            FiloDBQuery::ActiveInRange(_, _) => true,
+            FiloDBQuery::StillIngesting => true,
             // No point caching all docs - the "query" is constant time anyway
             &FiloDBQuery::All => false,
             // A single term lookup is very efficient - no benefit in caching the doc ID
@@ -98,6 +169,50 @@ impl tantivy_utils::query::cache::CachableQuery for FiloDBQuery {
 
                 Ok(Box::new(query))
             }
+            FiloDBQuery::StillIngesting => {
+                let query = RangeQuery::new_i64_bounds(
+                    field_constants::END_TIME.to_string(),
+                    Bound::Included(i64::MAX),
+                    Bound::Included(i64::MAX),
+                );
+
+                Ok(Box::new(query))
+            }
+            FiloDBQuery::ActiveInRange(start, end) => {
+                // Exclude docs outside the range rather than requiring docs inside it,
+                // so docs missing a time are unbounded on that side like in TimeRangeFilter
+                let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+
+                if *end < i64::MAX {
+                    clauses.push((
+                        Occur::MustNot,
+                        Box::new(RangeQuery::new_i64_bounds(
+                            field_constants::START_TIME.to_string(),
+                            Bound::Excluded(*end),
+                            Bound::Unbounded,
+                        )),
+                    ));
+                }
+
+                if *start > 0 {
+                    clauses.push((
+                        Occur::MustNot,
+                        Box::new(RangeQuery::new_i64_bounds(
+                            field_constants::END_TIME.to_string(),
+                            Bound::Unbounded,
+                            Bound::Excluded(*start),
+                        )),
+                    ));
+                }
+
+                if clauses.is_empty() {
+                    Ok(Box::new(AllQuery))
+                } else {
+                    clauses.push((Occur::Must, Box::new(AllQuery)));
+
+                    Ok(Box::new(BooleanQuery::new(clauses)))
+                }
+            }
         }
     }
 }
@@ -116,7 +231,7 @@ pub struct CachableQueryWeighter;
 // make space for a new incoming item.
 impl Weighter<(SegmentId, FiloDBQuery), Arc<BitSet>> for CachableQueryWeighter {
     fn weight(&self, key: &(SegmentId, FiloDBQuery), val: &Arc<BitSet>) -> u64 {
-        let bitset_size = ((val.max_value() as usize + 63) / 64) * 8;
+        let bitset_size = (val.max_value() as usize).div_ceil(64) * 8;
         let key_size = std::mem::size_of::<(SegmentId, FiloDBQuery)>();
 
         let type_size = match &key.1 {
@@ -128,6 +243,8 @@ impl Weighter<(SegmentId, FiloDBQuery), Arc<BitSet>> for CachableQueryWeighter {
             FiloDBQuery::All => 0,
             FiloDBQuery::ByPartId(_) => 0,
             FiloDBQuery::ByEndTime(_) => 0,
+            FiloDBQuery::ActiveInRange(_, _) => 0,
+            FiloDBQuery::StillIngesting => 0,
         };
 
         (type_size + key_size + bitset_size) as u64
@@ -136,9 +253,16 @@ impl Weighter<(SegmentId, FiloDBQuery), Arc<BitSet>> for CachableQueryWeighter {
 
 #[cfg(test)]
 mod tests {
-    use tantivy::query::EmptyQuery;
+    use tantivy::{collector::Count, query::EmptyQuery};
 
-    use tantivy_utils::{query::cache::CachableQuery as _, test_utils::build_test_schema};
+    use tantivy_utils::{
+        collectors::{
+            column_cache::ColumnCache, limited_collector::UnlimitedCollector,
+            time_range_filter::TimeRangeFilter,
+        },
+        query::cache::CachableQuery as _,
+        test_utils::{build_test_index_missing_times, build_test_schema},
+    };
 
     use super::*;
 
@@ -147,6 +271,8 @@ mod tests {
         assert!(FiloDBQuery::Complex(Arc::new([0u8; 0].into())).should_cache());
         assert!(FiloDBQuery::ByPartIds(Arc::new([0i32; 0].into())).should_cache());
         assert!(FiloDBQuery::ByEndTime(0).should_cache());
+        assert!(FiloDBQuery::ActiveInRange(0, 0).should_cache());
+        assert!(FiloDBQuery::StillIngesting.should_cache());
         assert!(!FiloDBQuery::All.should_cache());
         assert!(!FiloDBQuery::ByPartId(0).should_cache());
         assert!(!FiloDBQuery::ByPartKey(Arc::new([0u8; 0].into())).should_cache());
@@ -168,7 +294,7 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            58
+            66
         );
     }
 
@@ -188,7 +314,7 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            58
+            66
         );
     }
 
@@ -208,7 +334,81 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            40
+            48
+        );
+    }
+
+    #[test]
+    fn test_active_in_range_query() {
+        let index = build_test_schema();
+        let weighter = CachableQueryWeighter;
+        let reader = index.searcher.segment_readers().first().unwrap();
+        let query = FiloDBQuery::ActiveInRange(2000, 5000);
+
+        let parsed = query.to_query(&index.schema, None).expect("Should succeed");
+
+        assert!(parsed.is::<BooleanQuery>());
+        assert_eq!(parsed.count(&index.searcher).expect("Should succeed"), 1);
+
+        let parsed = FiloDBQuery::ActiveInRange(0, i64::MAX)
+            .to_query(&index.schema, None)
+            .expect("Should succeed");
+
+        assert!(parsed.is::<AllQuery>());
+
+        assert_eq!(
+            weighter.weight(
+                &(reader.segment_id(), query),
+                &Arc::new(BitSet::with_max_value(1))
+            ),
+            48
+        );
+    }
+
+    #[test]
+    fn test_active_in_range_missing_times() {
+        let index = build_test_index_missing_times();
+
+        // Docs missing a time must match the same as with the exact filter
+        for (start, end) in [(0, 1200), (1200, 1400), (1600, 1700), (1800, i64::MAX)] {
+            let parsed = FiloDBQuery::ActiveInRange(start, end)
+                .to_query(&index.schema, None)
+                .expect("Should succeed");
+
+            let collector = UnlimitedCollector::new(Count);
+            let collector = TimeRangeFilter::new(&collector, start, end, ColumnCache::default());
+            let expected = index
+                .searcher
+                .search(&AllQuery, &collector)
+                .expect("Should succeed");
+
+            assert_eq!(expected, 2);
+            assert_eq!(
+                parsed.count(&index.searcher).expect("Should succeed"),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_still_ingesting_query() {
+        let index = build_test_schema();
+        let weighter = CachableQueryWeighter;
+        let reader = index.searcher.segment_readers().first().unwrap();
+        let query = FiloDBQuery::StillIngesting;
+
+        let parsed = query.to_query(&index.schema, None).expect("Should succeed");
+
+        assert!(parsed.is::<RangeQuery>());
+        // Neither test doc is still ingesting
+        assert_eq!(parsed.count(&index.searcher).expect("Should succeed"), 0);
+
+        assert_eq!(
+            weighter.weight(
+                &(reader.segment_id(), query),
+                &Arc::new(BitSet::with_max_value(1))
+            ),
+            48
         );
     }
 
@@ -228,7 +428,7 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            40
+            48
         );
     }
 
@@ -248,7 +448,7 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            40
+            48
         );
     }
 
@@ -268,7 +468,49 @@ mod tests {
                 &(reader.segment_id(), query),
                 &Arc::new(BitSet::with_max_value(1))
             ),
-            64
+            72
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            FiloDBQuery::Complex(Arc::new([6u8].into())).to_string(),
+            "*:*"
+        );
+        assert_eq!(
+            FiloDBQuery::Complex(Arc::new([255u8].into())).to_string(),
+            "<invalid query (1 bytes): Parsing Failure: UnknownType(255)>"
+        );
+        assert_eq!(
+            FiloDBQuery::ByPartKey(Arc::new([0x41u8, 0xff].into())).to_string(),
+            "__partKey__:0x41ff"
+        );
+        assert_eq!(
+            FiloDBQuery::ByPartIds(Arc::new([1, 2].into())).to_string(),
+            "__partIdDv__:(1 OR 2)"
+        );
+        assert_eq!(
+            FiloDBQuery::ByPartIds(Arc::new((0..20).collect::<Vec<_>>().into())).to_string(),
+            "__partIdDv__:(0 OR 1 OR 2 OR 3 OR 4 OR 5 OR 6 OR 7 OR 8 OR 9 ... 20 total)"
+        );
+        assert_eq!(
+            FiloDBQuery::ByEndTime(100).to_string(),
+            "__endTime__:[0 TO 100]"
+        );
+        assert_eq!(
+            FiloDBQuery::ActiveInRange(100, 200).to_string(),
+            "(+__startTime__:[* TO 200] +__endTime__:[100 TO *])"
+        );
+        assert_eq!(
+            FiloDBQuery::ActiveInRange(0, i64::MAX).to_string(),
+            "(+__startTime__:[* TO *] +__endTime__:[* TO *])"
+        );
+        assert_eq!(
+            FiloDBQuery::StillIngesting.to_string(),
+            "__endTime__:9223372036854775807"
         );
+        assert_eq!(FiloDBQuery::ByPartId(5).to_string(), "__partIdDv__:5");
+        assert_eq!(FiloDBQuery::All.to_string(), "*:*");
     }
 }
@@ -1,34 +1,75 @@
 //! Methods related to reading / querying the index
 
-use std::sync::atomic::Ordering;
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 
 use hashbrown::HashSet;
 use jni::{
-    objects::{JByteArray, JClass, JIntArray, JObject, JString},
-    sys::{jbyteArray, jint, jintArray, jlong, jlongArray},
+    objects::{JByteArray, JClass, JIntArray, JObject, JObjectArray, JString},
+    sys::{jboolean, jbyteArray, jint, jintArray, jlong, jlongArray},
     JNIEnv,
 };
-use tantivy::schema::FieldType;
-use tantivy_utils::collectors::part_id_collector::PartIdCollector;
-use tantivy_utils::collectors::string_field_collector::StringFieldCollector;
-use tantivy_utils::collectors::time_collector::TimeCollector;
-use tantivy_utils::collectors::time_range_filter::TimeRangeFilter;
-use tantivy_utils::collectors::{
-    index_collector::collect_from_index, part_key_record_collector::PartKeyRecordCollector,
+use tantivy::{schema::FieldType, Searcher};
+use tantivy_utils::collectors::cardinality_rollup_collector::{
+    CardinalityNode, CardinalityRollupCollector,
 };
+use tantivy_utils::collectors::churn_histogram_collector::ChurnHistogramCollector;
+use tantivy_utils::collectors::count_by_labels_collector::CountByLabelsCollector;
+use tantivy_utils::collectors::end_time_filter::EndTimeFilter;
+use tantivy_utils::collectors::label_cardinality_collector::LabelCardinalityCollector;
+use tantivy_utils::collectors::multi_label_values_collector::MultiLabelValuesCollector;
+use tantivy_utils::collectors::ordered_record_collector::{OrderedRecordCollector, RecordOrder};
+use tantivy_utils::collectors::part_id_collector::{part_ids_from_part_keys, PartIdCollector};
+use tantivy_utils::collectors::part_id_key_collector::PartIdKeyCollector;
+use tantivy_utils::collectors::part_key_collector::resolve_part_keys;
+use tantivy_utils::collectors::sample_collector::SampleCollector;
+use tantivy_utils::collectors::series_labels_collector::SeriesLabelsCollector;
+use tantivy_utils::collectors::string_field_collector::{StringFieldCollector, ValueOrder};
+use tantivy_utils::collectors::time_collector::TimeCollector;
+use tantivy_utils::collectors::value_matcher::ValueMatcher;
 use tantivy_utils::collectors::{
-    part_key_collector::PartKeyCollector, part_key_record_collector::PartKeyRecord,
+    part_key_collector::PartKeyCollector,
+    part_key_record_collector::{resolve_part_key_records, PartKeyRecord, PartKeyRecordCollector},
 };
 use tantivy_utils::field_constants::{self, facet_field_name};
 
 use crate::{
     errors::{JavaException, JavaResult},
-    exec::jni_exec,
+    exec::{jni_exec, jni_query_exec},
     jnienv::JNIEnvExt,
     query_parser::filodb_query::FiloDBQuery,
-    state::IndexHandle,
+    state::{last_search_stats, IndexHandle, QueryCursor, MAX_CURSOR_IDLE_TIME},
 };
 
+/// Stats of the last query run on the calling thread, as limit hit, docs
+/// examined, segments read, segments skipped, segments served from cache,
+/// segments not searched after the limit was reached
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_lastQueryStats(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jlongArray {
+    jni_exec(&mut env, |env| {
+        let stats = last_search_stats();
+
+        let values = [
+            stats.limit_hit as i64,
+            stats.docs_examined as i64,
+            stats.segments_read as i64,
+            stats.segments_skipped as i64,
+            stats.segments_cached as i64,
+            stats.segments_after_limit as i64,
+        ];
+
+        let java_ret = env.new_long_array(values.len() as i32)?;
+        env.set_long_array_region(&java_ret, 0, &values)?;
+
+        Ok(java_ret.into_raw())
+    })
+}
+
 #[no_mangle]
 pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_indexRamBytes(
     mut env: JNIEnv,
@@ -72,12 +113,48 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_refr
             }
 
             handle.reader.reload()?;
+            handle.searcher_pins.expire()?;
+            handle.cursors.expire()?;
         };
 
         Ok(())
     })
 }
 
+/// Pin the current searcher so later calls passing the returned pin read the
+/// same point in time.  Pins last at most `lifetime_ms`, capped at
+/// `MAX_SEARCHER_PIN_LIFETIME`, and 0 is never returned.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_pinSearcher(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    lifetime_ms: jlong,
+) -> jlong {
+    jni_exec(&mut env, |_| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let lifetime = Duration::from_millis(lifetime_ms.max(0) as u64);
+
+        handle.searcher_pins.insert(handle.searcher(), lifetime)
+    })
+}
+
+/// Release a pinned searcher, returning false if it was already released or expired
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_releaseSearcher(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+) -> jboolean {
+    jni_exec(&mut env, |_| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        Ok(handle.searcher_pins.release(pin)? as jboolean)
+    })
+}
+
 #[no_mangle]
 pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_indexNumEntries(
     mut env: JNIEnv,
@@ -99,13 +176,25 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_part
     handle: jlong,
     ended_before: jlong,
 ) -> jintArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
-        let query = FiloDBQuery::ByEndTime(ended_before);
+        // Round the cached query up to a time bucket so repeated calls reuse it,
+        // then filter to the exact end time
+        let bucket_size = handle.time_bucket_size_ms;
+        let bucket_end = if bucket_size > 0 {
+            ended_before
+                .saturating_sub(ended_before.rem_euclid(bucket_size))
+                .saturating_add(bucket_size - 1)
+        } else {
+            ended_before
+        };
+        let query = FiloDBQuery::ByEndTime(bucket_end);
         let collector = PartIdCollector::new(usize::MAX, handle.column_cache.clone());
+        let filter_collector =
+            EndTimeFilter::new(&collector, ended_before, handle.column_cache.clone());
 
-        let results = handle.execute_cachable_query(query, collector)?;
+        let results = handle.execute_cachable_query(query, filter_collector)?;
 
         let java_ret = env.new_int_array(results.len() as i32)?;
         env.set_int_array_region(&java_ret, 0, &results)?;
@@ -114,6 +203,78 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_part
     })
 }
 
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_partKeysStillIngesting(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let searcher = handle.searcher_for(pin)?;
+        let collector = PartIdKeyCollector::new(usize::MAX, handle.column_cache.clone());
+
+        let results = handle.execute_cachable_query_with_searcher(
+            FiloDBQuery::StillIngesting,
+            collector,
+            &searcher,
+        )?;
+        let results = resolve_part_keys(&searcher, &handle.column_cache, results)?;
+
+        encode_part_ids_and_keys(env, results)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_partKeysFromPartIds(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    part_ids: JIntArray,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let len = env.get_array_length(&part_ids)?;
+
+        let mut part_id_values = vec![0i32; len as usize];
+        env.get_int_array_region(&part_ids, 0, &mut part_id_values[..])?;
+
+        let searcher = handle.searcher_for(pin)?;
+        let query = FiloDBQuery::ByPartIds(part_id_values.into_boxed_slice().into());
+        let collector = PartIdKeyCollector::new(usize::MAX, handle.column_cache.clone());
+
+        // Lists of part IDs to resolve rarely repeat, so keep them out of the query cache
+        let results = handle.execute_uncached_query_with_searcher(query, collector, &searcher)?;
+        let results = resolve_part_keys(&searcher, &handle.column_cache, results)?;
+
+        encode_part_ids_and_keys(env, results)
+    })
+}
+
+// Encode part IDs and keys as part ID, part key len, part key repeated
+fn encode_part_ids_and_keys(
+    env: &mut JNIEnv,
+    results: Vec<(i32, Vec<u8>)>,
+) -> JavaResult<jbyteArray> {
+    let results_len: usize = results
+        .iter()
+        .map(|(_, part_key)| part_key.len() + (2 * std::mem::size_of::<i32>()))
+        .sum();
+    let mut results_vec: Vec<u8> = Vec::with_capacity(results_len);
+
+    for (part_id, part_key) in results {
+        results_vec.extend(part_id.to_le_bytes());
+        results_vec.extend((part_key.len() as i32).to_le_bytes());
+        results_vec.extend(part_key);
+    }
+
+    encode_bytes(env, &results_vec)
+}
+
 #[no_mangle]
 pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_partIdFromPartKey(
     mut env: JNIEnv,
@@ -121,7 +282,7 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_part
     handle: jlong,
     part_id: JByteArray,
 ) -> jint {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
         let bytes = env.get_byte_array(&part_id)?;
@@ -140,35 +301,74 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_part
     })
 }
 
+/// Look up part IDs for many part keys.  Part keys are packed as a length then
+/// bytes, repeated.  Returns a part ID per part key, or -1 if not found.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_partIdsFromPartKeys(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    packed_part_keys: JByteArray,
+) -> jintArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let bytes = env.get_byte_array(&packed_part_keys)?;
+        let part_keys = unpack_part_keys(&bytes)?;
+
+        let results =
+            part_ids_from_part_keys(&handle.searcher(), &handle.column_cache, &part_keys)?;
+
+        let java_ret = env.new_int_array(results.len() as i32)?;
+        env.set_int_array_region(&java_ret, 0, &results)?;
+
+        Ok(java_ret.into_raw())
+    })
+}
+
+// Split packed part keys, each a little endian i32 length followed by the key bytes
+fn unpack_part_keys(mut bytes: &[u8]) -> JavaResult<Vec<&[u8]>> {
+    let mut part_keys = vec![];
+
+    while !bytes.is_empty() {
+        let (len, rest) = bytes
+            .split_first_chunk::<4>()
+            .ok_or_else(|| JavaException::new_runtime_exception("Truncated part key length"))?;
+        let len = i32::from_le_bytes(*len).max(0) as usize;
+
+        if rest.len() < len {
+            return Err(JavaException::new_runtime_exception("Truncated part key"));
+        }
+
+        let (part_key, rest) = rest.split_at(len);
+        part_keys.push(part_key);
+        bytes = rest;
+    }
+
+    Ok(part_keys)
+}
+
+// Label names with the number of matching series carrying each, most common first
 fn fetch_label_names(
     query: FiloDBQuery,
     handle: &IndexHandle,
-    results: &mut HashSet<String>,
-    limit: i32,
+    searcher: &Searcher,
+    limit: usize,
     start: i64,
     end: i64,
-) -> JavaResult<()> {
+) -> JavaResult<Vec<(String, u64)>> {
     let field = facet_field_name(field_constants::LABEL_LIST);
-    let collector = StringFieldCollector::new(
-        &field,
-        limit as usize,
-        usize::MAX,
-        handle.column_cache.clone(),
-    );
+    let collector =
+        StringFieldCollector::new(&field, limit, usize::MAX, handle.column_cache.clone());
 
     let query_results = if matches!(query, FiloDBQuery::All) {
-        collect_from_index(&handle.searcher(), collector)?
+        handle.collect_from_index_with_searcher(collector, searcher)?
     } else {
-        let filter_collector =
-            TimeRangeFilter::new(&collector, start, end, handle.column_cache.clone());
-        handle.execute_cachable_query(query, filter_collector)?
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+        handle.execute_cachable_query_with_searcher(query, filter_collector, searcher)?
     };
 
-    for (facet, _count) in query_results {
-        results.insert(facet.to_string());
-    }
-
-    Ok(())
+    Ok(query_results)
 }
 
 #[no_mangle]
@@ -176,12 +376,13 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labe
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     query: JByteArray,
     limit: jint,
     start: jlong,
     end: jlong,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
         let mut results = HashSet::new();
@@ -189,12 +390,72 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labe
         let query_bytes = env.get_byte_array(&query)?;
 
         let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
-        fetch_label_names(query, handle, &mut results, limit, start, end)?;
+        let searcher = handle.searcher_for(pin)?;
+        let names = fetch_label_names(query, handle, &searcher, limit.max(0) as usize, start, end)?;
+        results.extend(names.into_iter().map(|(name, _count)| name));
 
         encode_string_array(env, results)
     })
 }
 
+// Up to `limit` label names with exact counts, and whether there were more
+fn label_names_with_counts(
+    query: FiloDBQuery,
+    handle: &IndexHandle,
+    searcher: &Searcher,
+    limit: usize,
+    start: i64,
+    end: i64,
+) -> JavaResult<(Vec<(String, u64)>, bool)> {
+    let mut names = fetch_label_names(query, handle, searcher, usize::MAX, start, end)?;
+
+    let truncated = names.len() > limit;
+    names.truncate(limit);
+
+    Ok((names, truncated))
+}
+
+/// Label names of matching series with the number of series carrying each,
+/// most common first.  Every match is counted so counts are exact, and the
+/// result starts with a flag byte set if there were more than `limit` names.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labelNamesWithCounts(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    limit: jint,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+        let searcher = handle.searcher_for(pin)?;
+        let (names, truncated) =
+            label_names_with_counts(query, handle, &searcher, limit.max(0) as usize, start, end)?;
+
+        // Truncated flag, then count, name len, name repeated
+        let mut serialzied_bytes = vec![truncated as u8];
+        for (name, count) in names {
+            serialzied_bytes.extend((count as i64).to_le_bytes());
+            serialzied_bytes.extend((name.len() as i32).to_le_bytes());
+            serialzied_bytes.extend(name.as_bytes());
+        }
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+/// Copy serialized results into a new Java byte array
+pub(crate) fn encode_bytes(env: &mut JNIEnv, bytes: &[u8]) -> JavaResult<jbyteArray> {
+    Ok(env.byte_array_from_slice(bytes)?.into_raw())
+}
+
 fn encode_string_array(env: &mut JNIEnv, arr: HashSet<String>) -> JavaResult<jbyteArray> {
     let len: usize = arr
         .iter()
@@ -207,50 +468,59 @@ fn encode_string_array(env: &mut JNIEnv, arr: HashSet<String>) -> JavaResult<jby
         serialzied_bytes.extend(s.as_bytes());
     }
 
-    let java_ret = env.new_byte_array(len as i32)?;
-    let bytes_ptr = serialzied_bytes.as_ptr() as *const i8;
-    let bytes_ptr = unsafe { std::slice::from_raw_parts(bytes_ptr, len) };
+    encode_bytes(env, &serialzied_bytes)
+}
 
-    env.set_byte_array_region(&java_ret, 0, bytes_ptr)?;
+// Up to `limit` label names over the whole index, leaving out internal fields
+// before the limit is applied
+fn index_names(
+    handle: &IndexHandle,
+    searcher: &Searcher,
+    limit: usize,
+) -> JavaResult<HashSet<String>> {
+    // For each indexed field, include it
+    // For map fields, include encoded sub fields
+    let field_names = handle
+        .schema
+        .fields()
+        .filter_map(|(_field, field_entry)| match field_entry.field_type() {
+            // Skip this, we're going to get subfields via the facet below
+            FieldType::JsonObject(..) => None,
+            _ => Some(field_entry.name().to_string()),
+        });
+
+    let query = FiloDBQuery::All;
+    let label_names = fetch_label_names(query, handle, searcher, usize::MAX, 0, i64::MAX)?
+        .into_iter()
+        .map(|(name, _count)| name);
+
+    let mut results = HashSet::new();
+    for name in field_names.chain(label_names) {
+        if results.len() >= limit {
+            break;
+        }
 
-    Ok(java_ret.into_raw())
-}
+        if !field_constants::is_internal_field(&name) {
+            results.insert(name);
+        }
+    }
 
-const LABEL_NAMES_AND_VALUES_DEFAULT_LIMIT: i32 = 100;
+    Ok(results)
+}
 
 #[no_mangle]
 pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_indexNames(
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
+    limit: jint,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
-        let mut results = HashSet::new();
-
-        // For each indexed field, include it
-        // For map fields, include encoded sub fields
-        for (_field, field_entry) in handle.schema.fields() {
-            match field_entry.field_type() {
-                FieldType::JsonObject(..) => {
-                    // Skip this, we're going to get subfields via the facet below
-                }
-                _ => {
-                    results.insert(field_entry.name().to_string());
-                }
-            };
-        }
-
-        let query = FiloDBQuery::All;
-        fetch_label_names(
-            query,
-            handle,
-            &mut results,
-            LABEL_NAMES_AND_VALUES_DEFAULT_LIMIT,
-            0,
-            i64::MAX,
-        )?;
+        let searcher = handle.searcher_for(pin)?;
+        let results = index_names(handle, &searcher, limit.max(0) as usize)?;
 
         encode_string_array(env, results)
     })
@@ -260,14 +530,27 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_inde
 // consistent between the two index types
 const MAX_TERMS_TO_ITERATE: usize = 10_000;
 
-fn query_label_values(
+/// Ordering, paging and filtering of label values
+#[derive(Default)]
+pub(crate) struct LabelValueOptions<'a> {
+    pub order: ValueOrder,
+    pub after: Option<&'a str>,
+    pub matcher: Option<&'a ValueMatcher>,
+    // Count only live docs when reading counts from the index
+    pub exact_counts: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn query_label_values(
     query: FiloDBQuery,
     handle: &IndexHandle,
+    searcher: &Searcher,
     mut field: String,
     limit: usize,
     term_limit: usize,
     start: i64,
     end: i64,
+    options: LabelValueOptions,
 ) -> JavaResult<Vec<(String, u64)>> {
     let field_and_prefix = handle
         .schema
@@ -280,14 +563,17 @@ fn query_label_values(
         }
 
         let collector =
-            StringFieldCollector::new(&field, limit, term_limit, handle.column_cache.clone());
+            StringFieldCollector::new(&field, limit, term_limit, handle.column_cache.clone())
+                .with_order(options.order)
+                .with_after(options.after)
+                .with_matcher(options.matcher)
+                .with_exact_counts(options.exact_counts);
 
         if matches!(query, FiloDBQuery::All) {
-            Ok(collect_from_index(&handle.searcher(), collector)?)
+            Ok(handle.collect_from_index_with_searcher(collector, searcher)?)
         } else {
-            let filter_collector =
-                TimeRangeFilter::new(&collector, start, end, handle.column_cache.clone());
-            Ok(handle.execute_cachable_query(query, filter_collector)?)
+            let filter_collector = handle.time_range_filter(&collector, start, end);
+            Ok(handle.execute_cachable_query_with_searcher(query, filter_collector, searcher)?)
         }
     } else {
         // Invalid field, no values
@@ -300,13 +586,14 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labe
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     query: JByteArray,
     field: JString,
     top_k: jint,
     start: jlong,
     end: jlong,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
         let top_k = top_k as usize;
@@ -317,7 +604,18 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labe
 
         let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
 
-        let results = query_label_values(query, handle, field, top_k, usize::MAX, start, end)?;
+        let searcher = handle.searcher_for(pin)?;
+        let results = query_label_values(
+            query,
+            handle,
+            &searcher,
+            field,
+            top_k,
+            usize::MAX,
+            start,
+            end,
+            LabelValueOptions::default(),
+        )?;
 
         let len: usize = results
             .iter()
@@ -330,167 +628,780 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labe
             serialzied_bytes.extend(s.as_bytes());
         }
 
-        let java_ret = env.new_byte_array(len as i32)?;
-        let bytes_ptr = serialzied_bytes.as_ptr() as *const i8;
-        let bytes_ptr = unsafe { std::slice::from_raw_parts(bytes_ptr, len) };
-
-        env.set_byte_array_region(&java_ret, 0, bytes_ptr)?;
-
-        Ok(java_ret.into_raw())
+        encode_bytes(env, &serialzied_bytes)
     })
 }
 
+/// Top values of a field over the whole index.  Counts come from the term
+/// dictionary and include deleted docs unless `exact_counts` is set.
+///
+/// Values can be filtered as in labelValuesV2.  Only matching values count
+/// towards the term limit, and prefix matches only walk terms with the prefix.
 #[no_mangle]
 pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_indexValues(
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     field: JString,
     top_k: jint,
+    exact_counts: jboolean,
+    match_type: jint,
+    match_value: JString,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
+        let searcher = handle.searcher_for(pin)?;
 
         let top_k = top_k as usize;
 
         let field = env.get_rust_string(&field)?;
+        let matcher = get_value_matcher(env, match_type, &match_value)?;
 
         let query = FiloDBQuery::All;
         let results = query_label_values(
             query,
             handle,
+            &searcher,
             field,
             MAX_TERMS_TO_ITERATE,
             MAX_TERMS_TO_ITERATE,
             0,
             i64::MAX,
+            LabelValueOptions {
+                exact_counts: exact_counts != 0,
+                matcher: matcher.as_ref(),
+                ..Default::default()
+            },
         )?;
 
-        // String length, plus count, plus string data
-        let results_len: usize = results
-            .iter()
-            .take(top_k)
-            .map(|(value, _)| value.len() + std::mem::size_of::<i32>() + std::mem::size_of::<i64>())
-            .sum();
-        let mut serialzied_bytes = Vec::with_capacity(results_len);
-        for (value, count) in results.into_iter().take(top_k) {
-            serialzied_bytes.extend(count.to_le_bytes());
-            serialzied_bytes.extend((value.len() as i32).to_le_bytes());
-            serialzied_bytes.extend(value.as_bytes());
-        }
-
-        let java_ret = env.new_byte_array(results_len as i32)?;
-        let bytes_ptr = serialzied_bytes.as_ptr() as *const i8;
-        let bytes_ptr = unsafe { std::slice::from_raw_parts(bytes_ptr, results_len) };
-
-        env.set_byte_array_region(&java_ret, 0, bytes_ptr)?;
-
-        Ok(java_ret.into_raw())
+        encode_values_with_counts(env, results.into_iter().take(top_k))
     })
 }
 
+/// Top values with series counts for several labels, running the query once.
+/// For each label in order, the number of values then each count, value len,
+/// value.
 #[no_mangle]
-pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartIds(
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labelValuesMulti(
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     query: JByteArray,
-    limit: jint,
+    labels: JObjectArray,
+    top_k: jint,
     start: jlong,
     end: jlong,
-) -> jintArray {
-    jni_exec(&mut env, |env| {
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
         let query_bytes = env.get_byte_array(&query)?;
 
         let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
 
-        let collector = PartIdCollector::new(limit as usize, handle.column_cache.clone());
-        let filter_collector =
-            TimeRangeFilter::new(&collector, start, end, handle.column_cache.clone());
+        let mut label_names = vec![];
+        env.foreach_string_in_array(&labels, |name| {
+            label_names.push(name);
 
-        let results = handle.execute_cachable_query(query, filter_collector)?;
+            Ok(())
+        })?;
 
-        let java_ret = env.new_int_array(results.len() as i32)?;
-        env.set_int_array_region(&java_ret, 0, &results)?;
+        let collector = MultiLabelValuesCollector::new(
+            label_names,
+            handle.default_field,
+            top_k.max(0) as usize,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
 
-        Ok(java_ret.into_raw())
+        let searcher = handle.searcher_for(pin)?;
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        let mut serialzied_bytes = Vec::new();
+        for values in results {
+            serialzied_bytes.extend((values.len() as i32).to_le_bytes());
+            for (value, count) in values {
+                serialzied_bytes.extend(count.to_le_bytes());
+                serialzied_bytes.extend((value.len() as i32).to_le_bytes());
+                serialzied_bytes.extend(value.as_bytes());
+            }
+        }
+
+        encode_bytes(env, &serialzied_bytes)
     })
 }
 
+pub(crate) fn encode_values_with_counts(
+    env: &mut JNIEnv,
+    values: impl Iterator<Item = (String, u64)>,
+) -> JavaResult<jbyteArray> {
+    // Count, plus string length, plus string data
+    let mut serialzied_bytes = Vec::new();
+    for (value, count) in values {
+        serialzied_bytes.extend(count.to_le_bytes());
+        serialzied_bytes.extend((value.len() as i32).to_le_bytes());
+        serialzied_bytes.extend(value.as_bytes());
+    }
+
+    encode_bytes(env, &serialzied_bytes)
+}
+
+// Sort modes for labelValuesV2 and labelValuesFanOut, matching PartKeyTantivyIndex.scala
+const LABEL_VALUES_SORT_BY_COUNT: jint = 0;
+const LABEL_VALUES_SORT_LEXICOGRAPHIC: jint = 1;
+
+// Map a label values sort mode constant
+pub(crate) fn value_order(sort: jint) -> JavaResult<ValueOrder> {
+    match sort {
+        LABEL_VALUES_SORT_BY_COUNT => Ok(ValueOrder::CountDesc),
+        LABEL_VALUES_SORT_LEXICOGRAPHIC => Ok(ValueOrder::Lexicographic),
+        _ => Err(JavaException::new_runtime_exception(format!(
+            "Unknown label values sort mode {}",
+            sort
+        ))),
+    }
+}
+
+// Value match types for labelValuesV2 and indexValues, matching PartKeyTantivyIndex.scala
+const LABEL_VALUES_MATCH_NONE: jint = 0;
+const LABEL_VALUES_MATCH_PREFIX: jint = 1;
+const LABEL_VALUES_MATCH_SUBSTRING: jint = 2;
+const LABEL_VALUES_MATCH_REGEX: jint = 3;
+
+// Decode a value match type and value into a matcher
+fn get_value_matcher(
+    env: &mut JNIEnv,
+    match_type: jint,
+    match_value: &JString,
+) -> JavaResult<Option<ValueMatcher>> {
+    let matcher = match match_type {
+        LABEL_VALUES_MATCH_NONE => None,
+        LABEL_VALUES_MATCH_PREFIX => Some(ValueMatcher::Prefix(env.get_rust_string(match_value)?)),
+        LABEL_VALUES_MATCH_SUBSTRING => {
+            Some(ValueMatcher::Substring(env.get_rust_string(match_value)?))
+        }
+        LABEL_VALUES_MATCH_REGEX => Some(ValueMatcher::regex(&env.get_rust_string(match_value)?)?),
+        _ => {
+            return Err(JavaException::new_runtime_exception(format!(
+                "Unknown label values match type {}",
+                match_type
+            )))
+        }
+    };
+
+    Ok(matcher)
+}
+
+/// Label values with series counts, in count or lexicographic order
+///
+/// `after` is a paging cursor - only values that sort after it are returned.  It
+/// is only supported in lexicographic order, as counts shift between pages.
+///
+/// Values can be filtered by prefix, substring or regex with `match_type` and
+/// `match_value`.  Filtering happens before the limit is applied.
 #[no_mangle]
-pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartKeyRecords(
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labelValuesV2(
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     query: JByteArray,
+    field: JString,
     limit: jint,
+    sort: jint,
+    after: JString,
+    match_type: jint,
+    match_value: JString,
     start: jlong,
     end: jlong,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
-        let query_bytes = env.get_byte_array(&query)?;
-
-        let searcher = handle.searcher();
-        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
-
-        let collector = PartKeyRecordCollector::new(limit as usize, handle.column_cache.clone());
-        let filter_collector =
-            TimeRangeFilter::new(&collector, start, end, handle.column_cache.clone());
-        let results =
-            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+        let field = env.get_rust_string(&field)?;
+        let after = if after.is_null() {
+            None
+        } else {
+            Some(env.get_rust_string(&after)?)
+        };
 
-        let mut results: Vec<PartKeyRecord> = results
-            .into_iter()
-            .map(|x| x.resolve(&searcher))
-            .collect::<Result<Vec<_>, _>>()?;
+        let order = value_order(sort)?;
 
-        let results_len: usize = results.iter().map(|x| x.serialized_len()).sum();
-        let mut results_vec: Vec<u8> = Vec::with_capacity(results_len);
+        let matcher = get_value_matcher(env, match_type, &match_value)?;
 
-        for r in results.drain(..) {
-            r.serialize(&mut results_vec);
+        if after.is_some() && order != ValueOrder::Lexicographic {
+            return Err(JavaException::new_runtime_exception(
+                "Paging label values is only supported in lexicographic order",
+            ));
         }
 
-        let java_ret = env.new_byte_array(results_len as i32)?;
-        let bytes_ptr = results_vec.as_ptr() as *const i8;
-        let bytes_ptr = unsafe { std::slice::from_raw_parts(bytes_ptr, results_len) };
+        let query_bytes = env.get_byte_array(&query)?;
 
-        env.set_byte_array_region(&java_ret, 0, bytes_ptr)?;
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
 
-        Ok(java_ret.into_raw())
+        let searcher = handle.searcher_for(pin)?;
+        let results = query_label_values(
+            query,
+            handle,
+            &searcher,
+            field,
+            limit.max(0) as usize,
+            usize::MAX,
+            start,
+            end,
+            LabelValueOptions {
+                order,
+                after: after.as_deref(),
+                matcher: matcher.as_ref(),
+                exact_counts: false,
+            },
+        )?;
+
+        encode_values_with_counts(env, results.into_iter())
     })
 }
 
+/// Distinct value and series counts for every label of series matching a query
+///
+/// Distinct counts are exact up to `exact_threshold` values and estimated beyond.
+/// Results are encoded as label length, label bytes, distinct count (i64) and
+/// series count (i64), highest distinct count first.
 #[no_mangle]
-pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartKey(
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_labelCardinality(
     mut env: JNIEnv,
     _class: JClass,
     handle: jlong,
+    pin: jlong,
     query: JByteArray,
-    limit: jint,
     start: jlong,
     end: jlong,
+    exact_threshold: jint,
 ) -> jbyteArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
-
-        if limit != 1 {
-            return Err(JavaException::new_runtime_exception(
-                "Only limit of 1 is supported for queryPartKey",
-            ));
-        }
+        let searcher = handle.searcher_for(pin)?;
 
         let query_bytes = env.get_byte_array(&query)?;
+
         let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
-        let searcher = handle.searcher();
 
-        let collector = PartKeyCollector::new();
-        let filter_collector =
-            TimeRangeFilter::new(&collector, start, end, handle.column_cache.clone());
+        let collector = LabelCardinalityCollector::new(
+            handle.default_field,
+            exact_threshold.max(0) as usize,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        let mut serialzied_bytes = Vec::new();
+        for result in results {
+            serialzied_bytes.extend((result.label.len() as i32).to_le_bytes());
+            serialzied_bytes.extend(result.label.as_bytes());
+            serialzied_bytes.extend((result.distinct_values as i64).to_le_bytes());
+            serialzied_bytes.extend((result.series as i64).to_le_bytes());
+        }
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_cardinalityRollup(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    labels: JObjectArray,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+        let searcher = handle.searcher_for(pin)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let mut label_names = vec![];
+        env.foreach_string_in_array(&labels, |name| {
+            label_names.push(name);
+
+            Ok(())
+        })?;
+
+        let collector = CardinalityRollupCollector::new(
+            label_names,
+            handle.default_field,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+
+        let root =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        let mut serialzied_bytes = Vec::new();
+        encode_cardinality_node(&root, &mut serialzied_bytes);
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_countByLabels(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    labels: JObjectArray,
+    start: jlong,
+    end: jlong,
+    limit: jint,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+        let searcher = handle.searcher_for(pin)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let mut label_names = vec![];
+        env.foreach_string_in_array(&labels, |name| {
+            label_names.push(name);
+
+            Ok(())
+        })?;
+
+        let collector = CountByLabelsCollector::new(
+            label_names,
+            handle.default_field,
+            limit.max(0) as usize,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        // Count, then each value in label order
+        let mut serialzied_bytes = Vec::new();
+        for result in results {
+            serialzied_bytes.extend((result.count as i64).to_le_bytes());
+            for value in result.values {
+                serialzied_bytes.extend((value.len() as i32).to_le_bytes());
+                serialzied_bytes.extend(value.as_bytes());
+            }
+        }
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_querySeriesLabels(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    labels: JObjectArray,
+    start: jlong,
+    end: jlong,
+    limit: jint,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+        let searcher = handle.searcher_for(pin)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let mut label_names = vec![];
+        env.foreach_string_in_array(&labels, |name| {
+            label_names.push(name);
+
+            Ok(())
+        })?;
+
+        let collector = SeriesLabelsCollector::new(
+            label_names,
+            handle.default_field,
+            limit.max(0) as usize,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        // Part ID, then each value in label order
+        let mut serialzied_bytes = Vec::new();
+        for result in results {
+            serialzied_bytes.extend(result.part_id.to_le_bytes());
+            for value in result.values {
+                serialzied_bytes.extend((value.len() as i32).to_le_bytes());
+                serialzied_bytes.extend(value.as_bytes());
+            }
+        }
+
+        encode_bytes(env, &serialzied_bytes)
+    })
+}
+
+// Upper bound on churn histogram buckets, to bound memory per query
+const MAX_CHURN_BUCKETS: usize = 100_000;
+
+/// Number of matching series started and ended in each `bucket_width` wide
+/// bucket of `[start, end)`.  Returns the started counts followed by the
+/// ended counts.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_churnHistogram(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    start: jlong,
+    end: jlong,
+    bucket_width: jlong,
+) -> jlongArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        if bucket_width <= 0 || end <= start {
+            return Err(JavaException::new_runtime_exception(
+                "Churn histogram needs a positive bucket width and non empty range",
+            ));
+        }
+
+        let collector =
+            ChurnHistogramCollector::new(start, end, bucket_width, handle.column_cache.clone());
+        if collector.bucket_count() > MAX_CHURN_BUCKETS {
+            return Err(JavaException::new_runtime_exception(format!(
+                "Churn histogram is limited to {} buckets",
+                MAX_CHURN_BUCKETS
+            )));
+        }
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+        let searcher = handle.searcher_for(pin)?;
+
+        // Series started or ended in the range were active in it
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        let local_array: Vec<i64> = results
+            .started
+            .into_iter()
+            .chain(results.ended)
+            .map(|count| count as i64)
+            .collect();
+
+        let java_ret = env.new_long_array(local_array.len() as i32)?;
+        env.set_long_array_region(&java_ret, 0, &local_array)?;
+
+        Ok(java_ret.into_raw())
+    })
+}
+
+// Encode a rollup tree depth first - value len, value, total, active, child count
+fn encode_cardinality_node(node: &CardinalityNode, buffer: &mut Vec<u8>) {
+    buffer.extend((node.value.len() as i32).to_le_bytes());
+    buffer.extend(node.value.as_bytes());
+    buffer.extend((node.total as i64).to_le_bytes());
+    buffer.extend((node.active as i64).to_le_bytes());
+    buffer.extend((node.children.len() as i32).to_le_bytes());
+
+    for child in node.children.iter() {
+        encode_cardinality_node(child, buffer);
+    }
+}
+
+// Result orders for queryPartIds and queryPartKeyRecords, matching PartKeyTantivyIndex.scala
+const RESULT_ORDER_NONE: jint = 0;
+const RESULT_ORDER_PART_ID: jint = 1;
+const RESULT_ORDER_START_TIME: jint = 2;
+const RESULT_ORDER_END_TIME_DESC: jint = 3;
+
+// Map a result order constant, None for index order
+fn record_order(order: jint) -> JavaResult<Option<RecordOrder>> {
+    match order {
+        RESULT_ORDER_NONE => Ok(None),
+        RESULT_ORDER_PART_ID => Ok(Some(RecordOrder::PartId)),
+        RESULT_ORDER_START_TIME => Ok(Some(RecordOrder::StartTime)),
+        RESULT_ORDER_END_TIME_DESC => Ok(Some(RecordOrder::EndTimeDesc)),
+        _ => Err(JavaException::new_runtime_exception(format!(
+            "Unknown result order {}",
+            order
+        ))),
+    }
+}
+
+/// Part IDs matching a query
+///
+/// Without an order results are in index order, which changes as segments
+/// merge.  With an order the limit keeps the first matches in that order, so
+/// the same subset is returned every time.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartIds(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    limit: jint,
+    start: jlong,
+    end: jlong,
+    order: jint,
+) -> jintArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+        let searcher = handle.searcher_for(pin)?;
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let results = if let Some(order) = record_order(order)? {
+            let collector = OrderedRecordCollector::new(
+                order,
+                limit.max(0) as usize,
+                handle.column_cache.clone(),
+            );
+            let filter_collector = handle.time_range_filter(&collector, start, end);
+
+            handle
+                .execute_cachable_query_with_searcher(query, filter_collector, &searcher)?
+                .into_iter()
+                .map(|r| r.part_id)
+                .collect()
+        } else {
+            let collector = PartIdCollector::new(limit as usize, handle.column_cache.clone());
+            let filter_collector = handle.time_range_filter(&collector, start, end);
+
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?
+        };
+
+        let java_ret = env.new_int_array(results.len() as i32)?;
+        env.set_int_array_region(&java_ret, 0, &results)?;
+
+        Ok(java_ret.into_raw())
+    })
+}
+
+/// Part key records matching a query, optionally ordered as for queryPartIds
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartKeyRecords(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    limit: jint,
+    start: jlong,
+    end: jlong,
+    order: jint,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let searcher = handle.searcher_for(pin)?;
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let results = if let Some(order) = record_order(order)? {
+            let collector = OrderedRecordCollector::new(
+                order,
+                limit.max(0) as usize,
+                handle.column_cache.clone(),
+            );
+            let filter_collector = handle.time_range_filter(&collector, start, end);
+
+            handle
+                .execute_cachable_query_with_searcher(query, filter_collector, &searcher)?
+                .into_iter()
+                .map(|r| r.record)
+                .collect()
+        } else {
+            let collector =
+                PartKeyRecordCollector::new(limit as usize, handle.column_cache.clone());
+            let filter_collector = handle.time_range_filter(&collector, start, end);
+
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?
+        };
+
+        let results = resolve_part_key_records(&searcher, &handle.column_cache, &results)?;
+
+        encode_part_key_records(env, results)
+    })
+}
+
+/// Uniform random sample of up to `sample_size` part key records matching a
+/// query.  The same seed returns the same sample for an unchanged index.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_samplePartKeyRecords(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    sample_size: jint,
+    seed: jlong,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let searcher = handle.searcher_for(pin)?;
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let collector = SampleCollector::new(
+            sample_size.max(0) as usize,
+            seed as u64,
+            handle.column_cache.clone(),
+        );
+        let filter_collector = handle.time_range_filter(&collector, start, end);
+        let results =
+            handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
+
+        let results = resolve_part_key_records(&searcher, &handle.column_cache, &results)?;
+
+        encode_part_key_records(env, results)
+    })
+}
+
+fn encode_part_key_records(
+    env: &mut JNIEnv,
+    mut results: Vec<PartKeyRecord>,
+) -> JavaResult<jbyteArray> {
+    let results_len: usize = results.iter().map(|x| x.serialized_len()).sum();
+    let mut results_vec: Vec<u8> = Vec::with_capacity(results_len);
+
+    for r in results.drain(..) {
+        r.serialize(&mut results_vec);
+    }
+
+    encode_bytes(env, &results_vec)
+}
+
+/// Open a cursor over part key records matching a query
+///
+/// Cursors are held by the index handle and closed automatically when left
+/// idle for `MAX_CURSOR_IDLE_TIME`, or when the index handle is freed.  The
+/// query is run as batches are read, so opening a cursor is cheap.  0 is
+/// never returned.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_openCursor(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    start: jlong,
+    end: jlong,
+) -> jlong {
+    jni_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let query_bytes = env.get_byte_array(&query)?;
+
+        let searcher = handle.searcher_for(pin)?;
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+
+        let cursor = QueryCursor::new(searcher, query, start, end);
+
+        handle
+            .cursors
+            .insert(Arc::new(cursor), MAX_CURSOR_IDLE_TIME)
+    })
+}
+
+/// Get the next batch of records from a cursor, in the same format as queryPartKeyRecords.
+/// An empty result means the cursor is exhausted.
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_nextBatch(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    cursor: jlong,
+    max_records: jint,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        let cursor = handle
+            .cursors
+            .renew(cursor, MAX_CURSOR_IDLE_TIME)?
+            .ok_or_else(|| {
+                JavaException::new_runtime_exception(format!(
+                    "Cursor {cursor} was closed or expired"
+                ))
+            })?;
+
+        let results = cursor.next_batch(handle, max_records.max(0) as usize)?;
+
+        encode_part_key_records(env, results)
+    })
+}
+
+/// Close a cursor and release the searcher it pins, returning false if it was
+/// already closed or expired
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_closeCursor(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    cursor: jlong,
+) -> jboolean {
+    jni_exec(&mut env, |_| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        Ok(handle.cursors.release(cursor)? as jboolean)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_queryPartKey(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    pin: jlong,
+    query: JByteArray,
+    limit: jint,
+    start: jlong,
+    end: jlong,
+) -> jbyteArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
+
+        if limit != 1 {
+            return Err(JavaException::new_runtime_exception(
+                "Only limit of 1 is supported for queryPartKey",
+            ));
+        }
+
+        let query_bytes = env.get_byte_array(&query)?;
+        let query = FiloDBQuery::Complex(query_bytes.into_boxed_slice().into());
+        let searcher = handle.searcher_for(pin)?;
+
+        let collector = PartKeyCollector::new();
+        let filter_collector = handle.time_range_filter(&collector, start, end);
 
         let results =
             handle.execute_cachable_query_with_searcher(query, filter_collector, &searcher)?;
@@ -499,13 +1410,7 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_quer
             Some(part_key) => {
                 let part_key = part_key.resolve(&searcher)?;
 
-                let bytes_obj = env.new_byte_array(part_key.len() as i32)?;
-                let bytes_ptr = part_key.as_ptr() as *const i8;
-                let bytes_ptr = unsafe { std::slice::from_raw_parts(bytes_ptr, part_key.len()) };
-
-                env.set_byte_array_region(&bytes_obj, 0, bytes_ptr)?;
-
-                bytes_obj.into_raw()
+                encode_bytes(env, &part_key)?
             }
             None => JObject::null().into_raw(),
         };
@@ -521,41 +1426,60 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_star
     handle: jlong,
     part_ids: JIntArray,
 ) -> jlongArray {
-    jni_exec(&mut env, |env| {
+    jni_query_exec(&mut env, |env| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
-        let len = env.get_array_length(&part_ids)?;
+        times_from_part_ids(env, handle, &part_ids, field_constants::START_TIME)
+    })
+}
 
-        let mut part_id_values = vec![0i32; len as usize];
-        env.get_int_array_region(&part_ids, 0, &mut part_id_values[..])?;
+#[no_mangle]
+pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_endTimeFromPartIds(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    part_ids: JIntArray,
+) -> jlongArray {
+    jni_query_exec(&mut env, |env| {
+        let handle = IndexHandle::get_ref_from_handle(handle);
 
-        let query = FiloDBQuery::ByPartIds(part_id_values.into_boxed_slice().into());
+        times_from_part_ids(env, handle, &part_ids, field_constants::END_TIME)
+    })
+}
 
-        let collector = TimeCollector::new(
-            field_constants::START_TIME,
-            usize::MAX,
-            handle.column_cache.clone(),
-        );
+fn times_from_part_ids(
+    env: &mut JNIEnv,
+    handle: &IndexHandle,
+    part_ids: &JIntArray,
+    time_field: &str,
+) -> JavaResult<jlongArray> {
+    let len = env.get_array_length(part_ids)?;
 
-        let results = handle.execute_cachable_query(query, collector)?;
+    let mut part_id_values = vec![0i32; len as usize];
+    env.get_int_array_region(part_ids, 0, &mut part_id_values[..])?;
 
-        // Return is encoded as a single long array of tuples of part id, start time repeated. For example
-        // the first part ID is at offset 0, then its start time is at offset 1, the next part id is at offset 2
-        // and its start time is at offset 3, etc.
-        //
-        // This lets us avoid non primitive types in the return, which greatly improves performance
-        let java_ret = env.new_long_array(results.len() as i32 * 2)?;
-        let mut local_array = Vec::with_capacity(results.len() * 2);
-
-        for (p, t) in results {
-            local_array.push(p as i64);
-            local_array.push(t);
-        }
+    let query = FiloDBQuery::ByPartIds(part_id_values.into_boxed_slice().into());
 
-        env.set_long_array_region(&java_ret, 0, &local_array)?;
+    let collector = TimeCollector::new(time_field, usize::MAX, handle.column_cache.clone());
 
-        Ok(java_ret.into_raw())
-    })
+    let results = handle.execute_cachable_query(query, collector)?;
+
+    // Return is encoded as a single long array of tuples of part id, time repeated. For example
+    // the first part ID is at offset 0, then its time is at offset 1, the next part id is at offset 2
+    // and its time is at offset 3, etc.
+    //
+    // This lets us avoid non primitive types in the return, which greatly improves performance
+    let java_ret = env.new_long_array(results.len() as i32 * 2)?;
+    let mut local_array = Vec::with_capacity(results.len() * 2);
+
+    for (p, t) in results {
+        local_array.push(p as i64);
+        local_array.push(t);
+    }
+
+    env.set_long_array_region(&java_ret, 0, &local_array)?;
+
+    Ok(java_ret.into_raw())
 }
 
 #[no_mangle]
@@ -565,7 +1489,7 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_endT
     handle: jlong,
     part_id: jint,
 ) -> jlong {
-    jni_exec(&mut env, |_| {
+    jni_query_exec(&mut env, |_| {
         let handle = IndexHandle::get_ref_from_handle(handle);
 
         let query = FiloDBQuery::ByPartId(part_id);
@@ -584,3 +1508,90 @@ pub extern "system" fn Java_filodb_core_memstore_TantivyNativeMethods_00024_endT
         Ok(result)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{state::reset_last_search_stats, test_utils::TestIndexHandle};
+
+    use super::*;
+
+    #[test]
+    fn test_label_names_with_counts() {
+        let handle = TestIndexHandle::new(2, 5);
+        let query = FiloDBQuery::ByPartIds(Arc::new([0, 1, 2].into()));
+
+        let (names, truncated) =
+            label_names_with_counts(query.clone(), &handle, &handle.searcher(), 3, 0, i64::MAX)
+                .expect("Should succeed");
+
+        assert_eq!(
+            names,
+            vec![
+                ("col1".to_string(), 3),
+                ("col2".to_string(), 3),
+                ("f1".to_string(), 3)
+            ]
+        );
+        assert!(!truncated);
+
+        let (names, truncated) =
+            label_names_with_counts(query, &handle, &handle.searcher(), 2, 0, i64::MAX)
+                .expect("Should succeed");
+
+        assert_eq!(
+            names,
+            vec![("col1".to_string(), 3), ("col2".to_string(), 3)]
+        );
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_index_names_limit() {
+        let handle = TestIndexHandle::new(2, 5);
+
+        // Internal fields don't use up the limit
+        let names = index_names(&handle, &handle.searcher(), 3).expect("Should succeed");
+        assert_eq!(
+            names,
+            ["col1", "col2", "f1"]
+                .into_iter()
+                .map(String::from)
+                .collect::<HashSet<_>>()
+        );
+
+        let names = index_names(&handle, &handle.searcher(), 1).expect("Should succeed");
+        assert_eq!(names.len(), 1);
+    }
+
+    #[test]
+    fn test_query_label_values_stats() {
+        let handle = TestIndexHandle::new(2, 5);
+
+        // Values V0, V1 and V2, read from the index without a query
+        for (limit, limit_hit) in [(2, true), (3, false)] {
+            reset_last_search_stats();
+
+            let values = query_label_values(
+                FiloDBQuery::All,
+                &handle,
+                &handle.searcher(),
+                "col1".to_string(),
+                limit,
+                usize::MAX,
+                0,
+                i64::MAX,
+                LabelValueOptions {
+                    order: ValueOrder::Lexicographic,
+                    ..Default::default()
+                },
+            )
+            .expect("Should succeed");
+
+            assert_eq!(values.len(), limit);
+
+            let stats = last_search_stats();
+            assert_eq!(stats.limit_hit, limit_hit);
+            assert_eq!(stats.segments_read, 2);
+        }
+    }
+}
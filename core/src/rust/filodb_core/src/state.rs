@@ -1,27 +1,294 @@
 //! State objects shared with Java
 
 use std::{
+    cell::Cell,
     collections::{BTreeMap, HashMap},
-    sync::{atomic::AtomicBool, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 use filesize::PathExt;
 use jni::sys::jlong;
 use tantivy::{
+    collector::Collector,
     directory::{MmapDirectory, WatchCallback, WatchHandle},
     schema::{Field, OwnedValue, Schema},
-    Directory, IndexReader, IndexWriter, Searcher, TantivyDocument, TantivyError,
+    Directory, DocAddress, Executor, IndexReader, IndexWriter, Searcher, SegmentReader,
+    TantivyDocument, TantivyError,
 };
+use tantivy_common::BitSet;
 use tantivy_utils::{
     collectors::{
+        after_doc_filter::AfterDocFilter,
         column_cache::ColumnCache,
+        index_collector::{collect_from_index_with_stats, IndexCollector},
         limited_collector::{LimitedCollector, LimitedSegmentCollector},
+        part_key_record_collector::{
+            resolve_part_key_records, PartKeyRecord, PartKeyRecordCollector,
+        },
+        time_range_filter::{ActiveDocsSource, TimeRangeFilter},
+    },
+    query::{
+        cache::{CachableQuery, QueryCache, QueryCacheEntryInfo, QueryCachePool},
+        search_stats::SearchStats,
     },
-    query::cache::QueryCache,
 };
 
+use crate::errors::{JavaException, JavaResult};
 use crate::query_parser::filodb_query::{CachableQueryWeighter, FiloDBQuery};
 
+thread_local! {
+    // Stats of the last cachable query run on this thread
+    static LAST_SEARCH_STATS: Cell<SearchStats> = Cell::new(SearchStats::default());
+}
+
+/// Stats of the last cachable query run on the calling thread
+///
+/// JNI calls run on the calling Java thread, so Java can read the stats of a
+/// query right after running it.  Methods that run several queries on one
+/// index report the last one, and fan out methods report the sum over all
+/// indexes.  Stats are cleared as each query method starts, so a method that
+/// does not search reports default stats.
+pub fn last_search_stats() -> SearchStats {
+    LAST_SEARCH_STATS.with(|stats| stats.get())
+}
+
+/// Set the stats of the last query run on the calling thread
+pub fn set_last_search_stats(stats: SearchStats) {
+    LAST_SEARCH_STATS.with(|last| last.set(stats));
+}
+
+/// Clear the stats of the last query run on the calling thread
+pub fn reset_last_search_stats() {
+    set_last_search_stats(SearchStats::default());
+}
+
+pub type FiloDBQueryCachePool = QueryCachePool<FiloDBQuery, CachableQueryWeighter>;
+
+/// Query cache pool that can be shared by many index handles
+///
+/// Index handles keep their own reference to the pool, so freeing this
+/// handle does not invalidate indexes that are using it.
+pub struct SharedQueryCacheHandle {
+    pub pool: Arc<FiloDBQueryCachePool>,
+}
+
+impl SharedQueryCacheHandle {
+    pub fn new_handle(query_cache_max_size: u64, query_cache_estimated_item_size: u64) -> jlong {
+        let estimated_item_count: u64 = query_cache_max_size / query_cache_estimated_item_size;
+
+        let obj = Box::new(Self {
+            pool: Arc::new(QueryCachePool::new(
+                estimated_item_count,
+                query_cache_max_size,
+            )),
+        });
+
+        Box::into_raw(obj) as jlong
+    }
+
+    /// Decode handle back into a reference
+    pub fn get_ref_from_handle<'a>(handle: jlong) -> &'a Self {
+        let ptr = handle as *const SharedQueryCacheHandle;
+
+        unsafe { &*ptr }
+    }
+}
+
+/// Thread pool for running one query over several index handles
+///
+/// Shared by every fan out call, so the number of threads searching shards
+/// stays bounded no matter how many requests are in flight.
+pub struct FanOutPoolHandle {
+    pub executor: Executor,
+}
+
+impl FanOutPoolHandle {
+    pub fn new_handle(threads: usize) -> tantivy::Result<jlong> {
+        // 0 or 1 threads means search shards on the calling thread
+        let executor = if threads > 1 {
+            Executor::multi_thread(threads, "filodb-tantivy-fanout-")?
+        } else {
+            Executor::single_thread()
+        };
+
+        let obj = Box::new(Self { executor });
+
+        Ok(Box::into_raw(obj) as jlong)
+    }
+
+    /// Decode handle back into a reference
+    pub fn get_ref_from_handle<'a>(handle: jlong) -> &'a Self {
+        let ptr = handle as *const FanOutPoolHandle;
+
+        unsafe { &*ptr }
+    }
+}
+
+/// Cursor over part key records matching a query
+///
+/// The cursor pins the searcher it was opened with, so batches stay consistent
+/// even if the index is refreshed.  Only the query and the address of the last
+/// record returned are held between batches.  Each batch reruns the query,
+/// which is usually served from the query cache, and collects up to the batch
+/// size after that address.
+pub struct QueryCursor {
+    searcher: Searcher,
+    query: FiloDBQuery,
+    start: i64,
+    end: i64,
+    // Address of the last record returned, none before the first batch
+    last: Mutex<Option<DocAddress>>,
+}
+
+impl QueryCursor {
+    pub fn new(searcher: Searcher, query: FiloDBQuery, start: i64, end: i64) -> Self {
+        Self {
+            searcher,
+            query,
+            start,
+            end,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Resolve up to `max_records` more records, returning none once the cursor is exhausted
+    pub fn next_batch(
+        &self,
+        handle: &IndexHandle,
+        max_records: usize,
+    ) -> JavaResult<Vec<PartKeyRecord>> {
+        let mut last = self.last.lock()?;
+
+        let collector = PartKeyRecordCollector::new(max_records, handle.column_cache.clone());
+        let records = match *last {
+            Some(after) => {
+                let after_collector = AfterDocFilter::new(&collector, &self.searcher, after);
+                let filter_collector =
+                    handle.time_range_filter(&after_collector, self.start, self.end);
+
+                handle.execute_cachable_query_with_searcher(
+                    self.query.clone(),
+                    filter_collector,
+                    &self.searcher,
+                )?
+            }
+            None => {
+                let filter_collector = handle.time_range_filter(&collector, self.start, self.end);
+
+                handle.execute_cachable_query_with_searcher(
+                    self.query.clone(),
+                    filter_collector,
+                    &self.searcher,
+                )?
+            }
+        };
+
+        if let Some(record) = records.last() {
+            *last = Some(record.doc_id);
+        }
+
+        Ok(resolve_part_key_records(
+            &self.searcher,
+            &handle.column_cache,
+            &records,
+        )?)
+    }
+}
+
+/// Longest a searcher can stay pinned before it is released automatically
+pub const MAX_SEARCHER_PIN_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Longest a cursor can go without a batch being read before it is closed automatically
+pub const MAX_CURSOR_IDLE_TIME: Duration = Duration::from_secs(10 * 60);
+
+/// Objects held for Java by ID until released or past their deadline
+///
+/// Objects are looked up by ID rather than handed out as pointers, so a released
+/// or expired ID fails cleanly instead of reading freed memory.  Expired objects
+/// are dropped on the next access, and anything still held is dropped with the
+/// index handle, so resources are released even if the caller never does.
+pub struct ExpiringHandles<T> {
+    entries: Mutex<HashMap<jlong, (T, Instant)>>,
+    // IDs start at 1, so 0 can be used by callers as no ID
+    next_id: AtomicI64,
+    // Cap on the lifetime given to any one object
+    max_lifetime: Duration,
+}
+
+/// Searchers pinned so several calls see the same point in time
+pub type SearcherPins = ExpiringHandles<Searcher>;
+
+/// Open cursors, expiring when left idle
+pub type QueryCursors = ExpiringHandles<Arc<QueryCursor>>;
+
+impl<T: Clone> ExpiringHandles<T> {
+    pub fn new(max_lifetime: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            next_id: AtomicI64::new(1),
+            max_lifetime,
+        }
+    }
+
+    /// Hold an object for up to `lifetime`, capped at the max lifetime
+    pub fn insert(&self, value: T, lifetime: Duration) -> JavaResult<jlong> {
+        let expires = Instant::now() + lifetime.min(self.max_lifetime);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut entries = self.entries.lock()?;
+        Self::remove_expired(&mut entries);
+        entries.insert(id, (value, expires));
+
+        Ok(id)
+    }
+
+    /// Object for an ID, if it is still held
+    pub fn get(&self, id: jlong) -> JavaResult<Option<T>> {
+        let mut entries = self.entries.lock()?;
+        Self::remove_expired(&mut entries);
+
+        Ok(entries.get(&id).map(|(value, _)| value.clone()))
+    }
+
+    /// Object for an ID, if it is still held, pushing its deadline out to
+    /// `lifetime` from now
+    pub fn renew(&self, id: jlong, lifetime: Duration) -> JavaResult<Option<T>> {
+        let mut entries = self.entries.lock()?;
+        Self::remove_expired(&mut entries);
+
+        Ok(entries.get_mut(&id).map(|(value, expires)| {
+            *expires = Instant::now() + lifetime.min(self.max_lifetime);
+
+            value.clone()
+        }))
+    }
+
+    /// Release an object, returning false if it was already released or expired
+    pub fn release(&self, id: jlong) -> JavaResult<bool> {
+        let mut entries = self.entries.lock()?;
+        Self::remove_expired(&mut entries);
+
+        Ok(entries.remove(&id).is_some())
+    }
+
+    /// Drop objects past their deadline
+    pub fn expire(&self) -> JavaResult<()> {
+        let mut entries = self.entries.lock()?;
+        Self::remove_expired(&mut entries);
+
+        Ok(())
+    }
+
+    fn remove_expired(entries: &mut HashMap<jlong, (T, Instant)>) {
+        let now = Instant::now();
+        entries.retain(|_, (_, expires)| *expires > now);
+    }
+}
+
 pub struct IndexHandle {
     // Fields that don't need explicit synchronization
     //
@@ -34,6 +301,18 @@ pub struct IndexHandle {
     pub reader: IndexReader,
     // Cache of query -> docs
     query_cache: QueryCache<FiloDBQuery, CachableQueryWeighter>,
+    // Shared pool backing the query cache, if one is in use
+    shared_query_cache: Option<Arc<FiloDBQueryCachePool>>,
+    // Thread pool for searching segments in parallel, if enabled
+    query_executor: Option<Executor>,
+    // Searchers pinned for consistent reads across calls
+    pub searcher_pins: SearcherPins,
+    // Open cursors
+    pub cursors: QueryCursors,
+    // Size of time buckets used to cache time range filters in ms, 0 to disable.
+    // Queries with sliding time windows reuse cached results until the window
+    // crosses into a new bucket.
+    pub time_bucket_size_ms: i64,
     // Are there changes pending to commit
     pub changes_pending: AtomicBool,
     // Column lookup cache
@@ -61,9 +340,26 @@ impl IndexHandle {
         column_cache_size: u64,
         query_cache_max_size: u64,
         query_cache_estimated_item_size: u64,
+        shared_query_cache: Option<Arc<FiloDBQueryCachePool>>,
+        query_parallelism: usize,
+        time_bucket_size_ms: i64,
     ) -> tantivy::Result<jlong> {
-        let estimated_item_count: u64 = query_cache_max_size / query_cache_estimated_item_size;
+        let query_cache = if let Some(pool) = &shared_query_cache {
+            QueryCache::with_pool(pool.clone())
+        } else {
+            let estimated_item_count: u64 = query_cache_max_size / query_cache_estimated_item_size;
+            QueryCache::new(estimated_item_count, query_cache_max_size)
+        };
         let column_cache = ColumnCache::new(column_cache_size as usize);
+        // 0 or 1 threads means search segments on the calling thread
+        let query_executor = if query_parallelism > 1 {
+            Some(Executor::multi_thread(
+                query_parallelism,
+                "filodb-tantivy-query-",
+            )?)
+        } else {
+            None
+        };
 
         let cache = column_cache.clone();
         // When the index segment list changes, clear the column cache to release those mmaped files
@@ -77,7 +373,12 @@ impl IndexHandle {
             writer: RwLock::new(writer),
             reader,
             changes_pending: AtomicBool::new(false),
-            query_cache: QueryCache::new(estimated_item_count, query_cache_max_size),
+            query_cache,
+            shared_query_cache,
+            query_executor,
+            searcher_pins: SearcherPins::new(MAX_SEARCHER_PIN_LIFETIME),
+            cursors: QueryCursors::new(MAX_CURSOR_IDLE_TIME),
+            time_bucket_size_ms,
             column_cache,
             mmap_directory,
             _watch_handle: watch_handle,
@@ -101,6 +402,17 @@ impl IndexHandle {
         self.query_cache.size()
     }
 
+    /// Size and capacity of the shared query cache pool, if one is in use
+    pub fn shared_query_cache_size(&self) -> Option<(u64, u64)> {
+        self.shared_query_cache
+            .as_ref()
+            .map(|pool| (pool.size(), pool.capacity()))
+    }
+
+    pub fn query_cache_entries(&self) -> Vec<QueryCacheEntryInfo<FiloDBQuery>> {
+        self.query_cache.entries()
+    }
+
     pub fn mmap_size(&self) -> u64 {
         self.mmap_directory
             .get_cache_info()
@@ -114,6 +426,34 @@ impl IndexHandle {
         self.reader.searcher()
     }
 
+    /// Searcher for a pin from `searcher_pins`, or the live searcher if `pin` is 0
+    pub fn searcher_for(&self, pin: jlong) -> JavaResult<Searcher> {
+        if pin == 0 {
+            return Ok(self.searcher());
+        }
+
+        self.searcher_pins.get(pin)?.ok_or_else(|| {
+            JavaException::new_runtime_exception(format!(
+                "Searcher pin {pin} was released or expired"
+            ))
+        })
+    }
+
+    /// Wrap a collector in a time range filter backed by cached time buckets
+    pub fn time_range_filter<'a, C>(
+        &'a self,
+        collector: &'a C,
+        start: i64,
+        end: i64,
+    ) -> TimeRangeFilter<'a, C>
+    where
+        C: LimitedCollector,
+        C::Child: LimitedSegmentCollector,
+    {
+        TimeRangeFilter::new(collector, start, end, self.column_cache.clone())
+            .with_active_docs(self, self.time_bucket_size_ms)
+    }
+
     pub fn execute_cachable_query<C>(
         &self,
         cachable_query: FiloDBQuery,
@@ -138,12 +478,85 @@ impl IndexHandle {
         C: LimitedCollector,
         C::Child: LimitedSegmentCollector,
     {
-        self.query_cache.search(
-            searcher,
+        let mut stats = SearchStats::default();
+
+        let results = match &self.query_executor {
+            Some(executor) if searcher.segment_readers().len() > 1 => {
+                self.query_cache.search_parallel_with_stats(
+                    executor,
+                    searcher,
+                    &self.schema,
+                    self.default_field,
+                    cachable_query,
+                    collector,
+                    &mut stats,
+                )
+            }
+            _ => self.query_cache.search_with_stats(
+                searcher,
+                &self.schema,
+                self.default_field,
+                cachable_query,
+                collector,
+                &mut stats,
+            ),
+        };
+
+        set_last_search_stats(stats);
+
+        results
+    }
+
+    /// Execute a query without reading or filling the query cache
+    ///
+    /// For one off lookups, such as by a long list of part IDs, whose cached
+    /// results would rarely be reused.  No search stats are recorded.
+    pub fn execute_uncached_query_with_searcher<C>(
+        &self,
+        query: FiloDBQuery,
+        collector: C,
+        searcher: &Searcher,
+    ) -> Result<C::Fruit, TantivyError>
+    where
+        C: Collector,
+    {
+        let query = query.to_query(&self.schema, self.default_field)?;
+
+        searcher.search(query.as_ref(), &collector)
+    }
+
+    /// Collect over every doc in the index without a query, recording the stats
+    pub fn collect_from_index_with_searcher<C>(
+        &self,
+        collector: C,
+        searcher: &Searcher,
+    ) -> Result<C::Fruit, TantivyError>
+    where
+        C: IndexCollector,
+        C::Child: LimitedSegmentCollector,
+    {
+        let mut stats = SearchStats::default();
+
+        let results = collect_from_index_with_stats(searcher, collector, &mut stats);
+
+        set_last_search_stats(stats);
+
+        results
+    }
+}
+
+impl ActiveDocsSource for IndexHandle {
+    fn active_docs(
+        &self,
+        segment: &SegmentReader,
+        start: i64,
+        end: i64,
+    ) -> tantivy::Result<Arc<BitSet>> {
+        self.query_cache.segment_docs(
             &self.schema,
             self.default_field,
-            cachable_query,
-            collector,
+            segment,
+            &FiloDBQuery::ActiveInRange(start, end),
         )
     }
 }
@@ -158,3 +571,189 @@ pub struct IngestingDocument {
     // Document state for ingestion
     pub doc: TantivyDocument,
 }
+
+#[cfg(test)]
+mod tests {
+    use tantivy_utils::{
+        collectors::part_id_collector::PartIdCollector, test_utils::build_multi_segment_test_index,
+    };
+
+    use crate::test_utils::TestIndexHandle;
+
+    use super::*;
+
+    #[test]
+    fn test_query_cursor_batches() {
+        let index = TestIndexHandle::new(3, 2);
+
+        let cursor = QueryCursor::new(index.searcher(), FiloDBQuery::All, 0, i64::MAX);
+
+        let mut batch_sizes = vec![];
+        let mut start_times = vec![];
+        loop {
+            let batch = cursor.next_batch(&index, 4).expect("Should succeed");
+            if batch.is_empty() {
+                break;
+            }
+
+            batch_sizes.push(batch.len());
+            start_times.extend(batch.iter().map(|r| r.start_time));
+        }
+
+        assert_eq!(batch_sizes, vec![4, 2]);
+
+        start_times.sort();
+        assert_eq!(start_times, vec![0, 100, 200, 300, 400, 500]);
+    }
+
+    #[test]
+    fn test_query_cursor_pins_searcher() {
+        let index = TestIndexHandle::new(3, 2);
+
+        let cursor = QueryCursor::new(index.searcher(), FiloDBQuery::All, 0, i64::MAX);
+        let first = cursor.next_batch(&index, 4).expect("Should succeed");
+
+        // Docs deleted after the cursor was opened are still returned
+        {
+            let mut writer = index.writer.write().expect("Should succeed");
+            writer.delete_all_documents().expect("Should succeed");
+            writer.commit().expect("Should succeed");
+        }
+        index.reader.reload().expect("Should succeed");
+        assert_eq!(index.searcher().num_docs(), 0);
+
+        let rest = cursor.next_batch(&index, 4).expect("Should succeed");
+        assert_eq!(first.len() + rest.len(), 6);
+        assert!(cursor
+            .next_batch(&index, 4)
+            .expect("Should succeed")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_execute_uncached_query() {
+        let index = TestIndexHandle::new(2, 3);
+        let query = FiloDBQuery::ByPartIds(Arc::new([1, 4].into()));
+
+        let collector = PartIdCollector::new(usize::MAX, index.column_cache.clone());
+        let mut results = index
+            .execute_uncached_query_with_searcher(query.clone(), collector, &index.searcher())
+            .expect("Should succeed");
+        results.sort();
+
+        assert_eq!(results, vec![1, 4]);
+        assert!(index.query_cache_entries().is_empty());
+
+        // The same query through the cache is cached
+        let collector = PartIdCollector::new(usize::MAX, index.column_cache.clone());
+        index
+            .execute_cachable_query(query, collector)
+            .expect("Should succeed");
+        assert!(!index.query_cache_entries().is_empty());
+    }
+
+    #[test]
+    fn test_last_search_stats() {
+        let index = TestIndexHandle::new(4, 5);
+
+        // Limit reached in the second segment read
+        let collector = PartIdCollector::new(7, index.column_cache.clone());
+        let results = index
+            .execute_cachable_query(FiloDBQuery::All, collector)
+            .expect("Should succeed");
+        assert_eq!(results.len(), 7);
+
+        let expected = SearchStats {
+            limit_hit: true,
+            docs_examined: 7,
+            segments_read: 2,
+            segments_skipped: 0,
+            segments_after_limit: 2,
+            segments_cached: 0,
+        };
+        assert_eq!(last_search_stats(), expected);
+
+        // Stats are per thread
+        std::thread::scope(|s| {
+            s.spawn(|| assert_eq!(last_search_stats(), SearchStats::default()));
+        });
+        assert_eq!(last_search_stats(), expected);
+
+        // The next query replaces them
+        let collector = PartIdCollector::new(usize::MAX, index.column_cache.clone());
+        index
+            .execute_cachable_query(FiloDBQuery::All, collector)
+            .expect("Should succeed");
+        assert!(!last_search_stats().limit_hit);
+        assert_eq!(last_search_stats().segments_read, 4);
+
+        reset_last_search_stats();
+        assert_eq!(last_search_stats(), SearchStats::default());
+    }
+
+    #[test]
+    fn test_searcher_pins() {
+        let index = build_multi_segment_test_index(3, 2);
+        let pins = SearcherPins::new(MAX_SEARCHER_PIN_LIFETIME);
+
+        let first = pins
+            .insert(index.searcher.clone(), Duration::from_secs(60))
+            .expect("Should succeed");
+        let second = pins
+            .insert(index.searcher.clone(), Duration::from_secs(60))
+            .expect("Should succeed");
+        assert_ne!(first, second);
+        assert_ne!(first, 0);
+
+        let searcher = pins.get(first).expect("Should succeed");
+        assert_eq!(searcher.map(|s| s.num_docs()), Some(6));
+
+        assert!(pins.release(first).expect("Should succeed"));
+        assert!(!pins.release(first).expect("Should succeed"));
+        assert!(pins.get(first).expect("Should succeed").is_none());
+        assert!(pins.get(second).expect("Should succeed").is_some());
+    }
+
+    #[test]
+    fn test_searcher_pins_expire() {
+        let index = build_multi_segment_test_index(1, 2);
+        let pins = SearcherPins::new(MAX_SEARCHER_PIN_LIFETIME);
+
+        let pin = pins
+            .insert(index.searcher.clone(), Duration::ZERO)
+            .expect("Should succeed");
+
+        assert!(pins.get(pin).expect("Should succeed").is_none());
+        assert!(!pins.release(pin).expect("Should succeed"));
+
+        // Lifetimes are capped
+        let pin = pins
+            .insert(index.searcher.clone(), Duration::MAX)
+            .expect("Should succeed");
+        assert!(pins.get(pin).expect("Should succeed").is_some());
+    }
+
+    #[test]
+    fn test_expiring_handles_renew() {
+        let handles = ExpiringHandles::new(Duration::from_secs(60));
+
+        let id = handles.insert(1, Duration::ZERO).expect("Should succeed");
+        assert_eq!(
+            handles
+                .renew(id, Duration::from_secs(60))
+                .expect("Should succeed"),
+            None
+        );
+
+        let id = handles
+            .insert(2, Duration::from_secs(60))
+            .expect("Should succeed");
+        assert_eq!(
+            handles.renew(id, Duration::ZERO).expect("Should succeed"),
+            Some(2)
+        );
+
+        // Renewing with no lifetime expires it
+        assert_eq!(handles.get(id).expect("Should succeed"), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Utilities for testing
+
+use std::ops::Deref;
+
+use jni::sys::jlong;
+use tantivy::{directory::MmapDirectory, ReloadPolicy, TantivyDocument};
+use tantivy_utils::test_utils::{create_multi_segment_test_index_in, JSON_COL_NAME};
+use tempfile::TempDir;
+
+use crate::state::IndexHandle;
+
+/// An index handle over an on disk copy of `build_multi_segment_test_index`,
+/// freed on drop
+pub struct TestIndexHandle {
+    handle: jlong,
+    _dir: TempDir,
+}
+
+impl TestIndexHandle {
+    // Allow unwraps since this is test code
+    #[allow(clippy::unwrap_used)]
+    pub fn new(segment_count: usize, docs_per_segment: usize) -> Self {
+        let dir = TempDir::new().unwrap();
+        let directory = MmapDirectory::open(dir.path()).unwrap();
+
+        let index =
+            create_multi_segment_test_index_in(directory.clone(), segment_count, docs_per_segment);
+        let schema = index.schema();
+        let default_field = schema.get_field(JSON_COL_NAME).ok();
+
+        let writer = index.writer::<TantivyDocument>(50_000_000).unwrap();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .unwrap();
+
+        let handle = IndexHandle::new_handle(
+            schema,
+            default_field,
+            writer,
+            reader,
+            directory,
+            100,
+            10_000_000,
+            31250,
+            None,
+            0,
+            0,
+        )
+        .unwrap();
+
+        Self { handle, _dir: dir }
+    }
+}
+
+impl Deref for TestIndexHandle {
+    type Target = IndexHandle;
+
+    fn deref(&self) -> &IndexHandle {
+        IndexHandle::get_ref_from_handle(self.handle)
+    }
+}
+
+impl Drop for TestIndexHandle {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(self.handle as *mut IndexHandle));
+        }
+    }
+}
@@ -1,11 +1,24 @@
 //! Common collectors
 
+pub mod after_doc_filter;
+pub mod bounded_record_collector;
+pub mod cardinality_rollup_collector;
+pub mod churn_histogram_collector;
 pub mod column_cache;
+pub mod count_by_labels_collector;
+pub mod end_time_filter;
 pub mod index_collector;
+pub mod label_cardinality_collector;
 pub mod limited_collector;
+pub mod multi_label_values_collector;
+pub mod ordered_record_collector;
 pub mod part_id_collector;
+pub mod part_id_key_collector;
 pub mod part_key_collector;
 pub mod part_key_record_collector;
+pub mod sample_collector;
+pub mod series_labels_collector;
 pub mod string_field_collector;
 pub mod time_collector;
 pub mod time_range_filter;
+pub mod value_matcher;
@@ -0,0 +1,204 @@
+//! Filter collector that keeps docs after a doc address
+
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    index::SegmentId,
+    DocAddress, DocId, Searcher, SegmentReader, TantivyError, TERMINATED,
+};
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// Filters results to docs after a given doc address in a searcher
+///
+/// Segments are searched in order and docs within a segment in doc order, so
+/// this lets callers page through the results of a query one limited search at
+/// a time.  Segments entirely before the address are skipped.
+pub struct AfterDocFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    /// Inner collector
+    collector: &'a T,
+    /// Docs up to and including this address are left out
+    after: DocAddress,
+    /// Segments before the one holding `after`
+    earlier_segments: Vec<SegmentId>,
+}
+
+impl<'a, T> AfterDocFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    pub fn new(collector: &'a T, searcher: &Searcher, after: DocAddress) -> Self {
+        let earlier_segments = searcher
+            .segment_readers()
+            .iter()
+            .take(after.segment_ord as usize)
+            .map(|r| r.segment_id())
+            .collect();
+
+        Self {
+            collector,
+            after,
+            earlier_segments,
+        }
+    }
+}
+
+impl<'a, T> LimitedCollector for AfterDocFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    fn limit(&self) -> usize {
+        self.collector.limit()
+    }
+
+    fn truncated(&self) -> bool {
+        self.collector.truncated()
+    }
+
+    fn skip_segment(&self, reader: &SegmentReader) -> Result<bool, TantivyError> {
+        if self.earlier_segments.contains(&reader.segment_id()) {
+            return Ok(true);
+        }
+
+        self.collector.skip_segment(reader)
+    }
+}
+
+impl<'a, T> Collector for AfterDocFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    type Fruit = T::Fruit;
+
+    type Child = AfterDocFilterSegmentCollector<T::Child>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let first_doc = match segment_local_id.cmp(&self.after.segment_ord) {
+            std::cmp::Ordering::Less => TERMINATED,
+            std::cmp::Ordering::Equal => self.after.doc_id.saturating_add(1),
+            std::cmp::Ordering::Greater => 0,
+        };
+
+        let collector = self.collector.for_segment(segment_local_id, segment)?;
+
+        Ok(AfterDocFilterSegmentCollector::<T::Child> {
+            first_doc,
+            collector,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<Self::Child as tantivy::collector::SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<Self::Fruit> {
+        self.collector.merge_fruits(segment_fruits)
+    }
+}
+
+pub struct AfterDocFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    collector: T,
+    // First doc in this segment that is kept
+    first_doc: DocId,
+}
+
+impl<T> LimitedSegmentCollector for AfterDocFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        score: tantivy::Score,
+        limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        if doc < self.first_doc {
+            return Ok(());
+        }
+
+        self.collector.collect_with_limiter(doc, score, limiter)
+    }
+}
+
+impl<T> SegmentCollector for AfterDocFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    type Fruit = T::Fruit;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.collector.harvest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::{
+        collectors::{
+            column_cache::ColumnCache, part_key_record_collector::PartKeyRecordCollector,
+        },
+        test_utils::build_multi_segment_test_index,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_after_doc_filter() {
+        let index = build_multi_segment_test_index(3, 2);
+        let collector = PartKeyRecordCollector::new(usize::MAX, ColumnCache::default());
+
+        let all = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+        assert_eq!(all.len(), 6);
+
+        // Every doc after each position, across segment boundaries
+        for (idx, record) in all.iter().enumerate() {
+            let filter = AfterDocFilter::new(&collector, &index.searcher, record.doc_id);
+            let after = index
+                .searcher
+                .search(&AllQuery, &filter)
+                .expect("Should succeed");
+
+            assert_eq!(after, all[idx + 1..]);
+        }
+    }
+
+    #[test]
+    fn test_after_doc_filter_skip_segment() {
+        let index = build_multi_segment_test_index(3, 2);
+        let collector = PartKeyRecordCollector::new(usize::MAX, ColumnCache::default());
+        let readers = index.searcher.segment_readers();
+
+        let filter = AfterDocFilter::new(&collector, &index.searcher, DocAddress::new(1, 0));
+
+        let skipped: Vec<_> = readers
+            .iter()
+            .map(|r| filter.skip_segment(r).expect("Should succeed"))
+            .collect();
+        assert_eq!(skipped, vec![true, false, false]);
+    }
+}
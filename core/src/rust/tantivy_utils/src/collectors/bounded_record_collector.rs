@@ -0,0 +1,236 @@
+//! Collector for the records with the lowest priority, using a bounded heap
+
+use std::collections::BinaryHeap;
+
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::Column,
+    DocAddress, TantivyError,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::collectors::part_key_record_collector::UnresolvedPartKeyRecord;
+use crate::field_constants::{END_TIME, PART_ID, START_TIME};
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// Priority of a record, records with the lowest keys are kept
+pub trait RecordPriority: Copy + Send + Sync + 'static {
+    type Key: Ord + Copy + Send + 'static;
+
+    fn key(&self, part_id: i32, start_time: i64, end_time: i64) -> Self::Key;
+}
+
+/// Record with the part ID it was prioritized by
+#[derive(Debug, PartialEq, Eq)]
+pub struct OrderedRecord {
+    pub part_id: i32,
+    pub record: UnresolvedPartKeyRecord,
+}
+
+/// Collects the `limit` matching records with the lowest priority keys
+///
+/// Unlike collectors that stop at the limit, every match is seen, so the
+/// results do not depend on segment layout and are the same after merges or
+/// on other replicas.  Each segment keeps only its best `limit` records in a
+/// heap.
+pub struct BoundedRecordCollector<P: RecordPriority> {
+    priority: P,
+    limit: usize,
+    column_cache: ColumnCache,
+}
+
+impl<P: RecordPriority> BoundedRecordCollector<P> {
+    pub fn new(priority: P, limit: usize, column_cache: ColumnCache) -> Self {
+        Self {
+            priority,
+            limit,
+            column_cache,
+        }
+    }
+}
+
+impl<P: RecordPriority> LimitedCollector for BoundedRecordCollector<P> {
+    fn limit(&self) -> usize {
+        // Any doc could have the lowest key, so collection can't stop early
+        usize::MAX
+    }
+}
+
+impl<P: RecordPriority> Collector for BoundedRecordCollector<P> {
+    type Fruit = Vec<OrderedRecord>;
+
+    type Child = BoundedRecordSegmentCollector<P>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<BoundedRecordSegmentCollector<P>> {
+        let column = |name: &str| -> tantivy::Result<Column<i64>> {
+            self.column_cache
+                .get_column(segment, name)?
+                .ok_or_else(|| TantivyError::FieldNotFound(name.to_string()))
+        };
+
+        Ok(BoundedRecordSegmentCollector {
+            segment_ord: segment_local_id,
+            priority: self.priority,
+            limit: self.limit,
+            part_id_column: column(PART_ID)?,
+            start_time_column: column(START_TIME)?,
+            end_time_column: column(END_TIME)?,
+            heap: BinaryHeap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<HeapEntry<P::Key>>>,
+    ) -> tantivy::Result<Vec<OrderedRecord>> {
+        let mut results: Vec<_> = segment_fruits.into_iter().flatten().collect();
+        results.sort_unstable();
+        results.truncate(self.limit);
+
+        Ok(results.into_iter().map(|entry| entry.record).collect())
+    }
+}
+
+/// Heap entry ordered by priority key only
+#[derive(Debug)]
+pub struct HeapEntry<K> {
+    key: K,
+    record: OrderedRecord,
+}
+
+impl<K: Ord> PartialEq for HeapEntry<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Ord> Eq for HeapEntry<K> {}
+
+impl<K: Ord> PartialOrd for HeapEntry<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for HeapEntry<K> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+pub struct BoundedRecordSegmentCollector<P: RecordPriority> {
+    segment_ord: u32,
+    priority: P,
+    limit: usize,
+    part_id_column: Column<i64>,
+    start_time_column: Column<i64>,
+    end_time_column: Column<i64>,
+    // Max heap, so the highest kept key is on top
+    heap: BinaryHeap<HeapEntry<P::Key>>,
+}
+
+impl<P: RecordPriority> LimitedSegmentCollector for BoundedRecordSegmentCollector<P> {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        _limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let (Some(part_id), Some(start_time), Some(end_time)) = (
+            self.part_id_column.first(doc),
+            self.start_time_column.first(doc),
+            self.end_time_column.first(doc),
+        ) else {
+            return Ok(());
+        };
+
+        let part_id = part_id as i32;
+        let key = self.priority.key(part_id, start_time, end_time);
+
+        if self.heap.len() >= self.limit {
+            match self.heap.peek() {
+                Some(highest) if key < highest.key => {
+                    self.heap.pop();
+                }
+                _ => return Ok(()),
+            }
+        }
+
+        self.heap.push(HeapEntry {
+            key,
+            record: OrderedRecord {
+                part_id,
+                record: UnresolvedPartKeyRecord {
+                    doc_id: DocAddress::new(self.segment_ord, doc),
+                    start_time,
+                    end_time,
+                },
+            },
+        });
+
+        Ok(())
+    }
+}
+
+impl<P: RecordPriority> SegmentCollector for BoundedRecordSegmentCollector<P> {
+    type Fruit = Vec<HeapEntry<P::Key>>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.heap.into_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::build_multi_segment_test_index;
+
+    use super::*;
+
+    // Keeps the highest part IDs
+    #[derive(Clone, Copy)]
+    struct HighestPartId;
+
+    impl RecordPriority for HighestPartId {
+        type Key = i32;
+
+        fn key(&self, part_id: i32, _start_time: i64, _end_time: i64) -> i32 {
+            -part_id
+        }
+    }
+
+    #[test]
+    fn test_bounded_record_collector() {
+        let index = build_multi_segment_test_index(3, 10);
+
+        let collector = BoundedRecordCollector::new(HighestPartId, 4, ColumnCache::default());
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        let part_ids: Vec<_> = results.iter().map(|r| r.part_id).collect();
+        assert_eq!(part_ids, vec![29, 28, 27, 26]);
+        assert_eq!(results[0].record.start_time, 2900);
+        assert_eq!(results[0].record.end_time, 2950);
+    }
+}
@@ -0,0 +1,318 @@
+//! Collector for series counts rolled up by an ordered list of labels
+
+use hashbrown::HashMap;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::{Column, StrColumn},
+    schema::Field,
+    SegmentReader,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::field_constants;
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// One level of a cardinality rollup
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CardinalityNode {
+    /// Label value, empty for the root
+    pub value: String,
+    /// Number of series under this node
+    pub total: u64,
+    /// Number of series under this node that are still ingesting
+    pub active: u64,
+    /// Child nodes for the next label, sorted by value
+    pub children: Vec<CardinalityNode>,
+}
+
+impl CardinalityNode {
+    fn new(value: String) -> Self {
+        Self {
+            value,
+            ..Default::default()
+        }
+    }
+
+    // Add counts for a full label path, creating nodes as needed.  Paths must be
+    // added in sorted order so a matching child is always the last one.
+    fn add(&mut self, path: &[String], total: u64, active: u64) {
+        self.total += total;
+        self.active += active;
+
+        if let Some((value, rest)) = path.split_first() {
+            if self.children.last().map(|c| &c.value) != Some(value) {
+                self.children.push(CardinalityNode::new(value.clone()));
+            }
+
+            if let Some(child) = self.children.last_mut() {
+                child.add(rest, total, active);
+            }
+        }
+    }
+}
+
+/// Counts matching series grouped by the values of an ordered list of labels
+///
+/// The result is a tree with one level per label, for example workspace then
+/// namespace then metric.  Each node holds the total number of series below it
+/// and the number still ingesting, i.e. with an open end time.  Series missing
+/// a label are grouped under an empty value.
+pub struct CardinalityRollupCollector {
+    labels: Vec<String>,
+    default_field: Option<Field>,
+    column_cache: ColumnCache,
+}
+
+impl CardinalityRollupCollector {
+    pub fn new(
+        labels: Vec<String>,
+        default_field: Option<Field>,
+        column_cache: ColumnCache,
+    ) -> Self {
+        Self {
+            labels,
+            default_field,
+            column_cache,
+        }
+    }
+}
+
+impl LimitedCollector for CardinalityRollupCollector {
+    fn limit(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl Collector for CardinalityRollupCollector {
+    type Fruit = CardinalityNode;
+
+    type Child = CardinalityRollupSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: tantivy::SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<CardinalityRollupSegmentCollector> {
+        let columns = self
+            .labels
+            .iter()
+            .map(|label| {
+                self.column_cache
+                    .get_label_column(segment, label, self.default_field)
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+
+        let end_column = self
+            .column_cache
+            .get_column(segment, field_constants::END_TIME)?;
+
+        Ok(CardinalityRollupSegmentCollector {
+            columns,
+            end_column,
+            path: Vec::with_capacity(self.labels.len()),
+            counts: HashMap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(Vec<String>, u64, u64)>>,
+    ) -> tantivy::Result<CardinalityNode> {
+        // The same path can appear in several segments, so sort to group them
+        let mut paths: Vec<_> = segment_fruits.into_iter().flatten().collect();
+        paths.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut root = CardinalityNode::default();
+        for (path, total, active) in paths.iter() {
+            root.add(path, *total, *active);
+        }
+
+        Ok(root)
+    }
+}
+
+// Missing values get an ord past the end of any dictionary
+const MISSING_ORD: u64 = u64::MAX;
+
+pub struct CardinalityRollupSegmentCollector {
+    columns: Vec<Option<StrColumn>>,
+    end_column: Option<Column<i64>>,
+    // Reused buffer for the current doc's ords
+    path: Vec<u64>,
+    // Full label path of ords to (total, active)
+    counts: HashMap<Vec<u64>, (u64, u64)>,
+}
+
+impl LimitedSegmentCollector for CardinalityRollupSegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        _limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        self.path.clear();
+        for column in self.columns.iter() {
+            let ord = column
+                .as_ref()
+                .and_then(|c| c.term_ords(doc).next())
+                .unwrap_or(MISSING_ORD);
+
+            self.path.push(ord);
+        }
+
+        let active = self
+            .end_column
+            .as_ref()
+            .and_then(|c| c.first(doc))
+            .is_some_and(|end| end == i64::MAX) as u64;
+
+        if let Some(counts) = self.counts.get_mut(self.path.as_slice()) {
+            counts.0 += 1;
+            counts.1 += active;
+        } else {
+            self.counts.insert(self.path.clone(), (1, active));
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for CardinalityRollupSegmentCollector {
+    type Fruit = Vec<(Vec<String>, u64, u64)>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        // Ords are only meaningful within a segment, so convert to values.  Each
+        // ord is looked up once per label.
+        let mut values: Vec<HashMap<u64, String>> = vec![HashMap::new(); self.columns.len()];
+
+        self.counts
+            .into_iter()
+            .map(|(path, (total, active))| {
+                let path = path
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, ord)| {
+                        values[idx]
+                            .entry(ord)
+                            .or_insert_with(|| {
+                                let mut value = String::new();
+                                if let Some(column) = &self.columns[idx] {
+                                    if ord != MISSING_ORD {
+                                        let _ = column.ord_to_str(ord, &mut value);
+                                    }
+                                }
+
+                                value
+                            })
+                            .clone()
+                    })
+                    .collect();
+
+                (path, total, active)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cardinality_rollup() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let collector = CardinalityRollupCollector::new(
+            labels(&["col2", "f1"]),
+            Some(index.json_field),
+            column_cache,
+        );
+
+        let root = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(root.total, 2);
+        assert_eq!(root.active, 0);
+        assert_eq!(
+            root.children
+                .iter()
+                .map(|c| (c.value.as_str(), c.total, c.children[0].value.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("abc", 1, "othervalue"), ("def", 1, "value")]
+        );
+    }
+
+    #[test]
+    fn test_cardinality_rollup_missing_label() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let collector = CardinalityRollupCollector::new(
+            labels(&["col2", "not_found"]),
+            Some(index.json_field),
+            column_cache,
+        );
+
+        let root = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        for child in root.children.iter() {
+            assert_eq!(child.children.len(), 1);
+            assert_eq!(child.children[0].value, "");
+            assert_eq!(child.children[0].total, 1);
+        }
+    }
+
+    #[test]
+    fn test_cardinality_rollup_multi_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        let collector = CardinalityRollupCollector::new(
+            labels(&["col2", "col1"]),
+            Some(index.json_field),
+            column_cache,
+        );
+
+        let root = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(root.total, 30);
+        assert_eq!(root.children.len(), 1);
+
+        let def = &root.children[0];
+        assert_eq!(def.value, "def");
+        assert_eq!(def.total, 30);
+        assert_eq!(
+            def.children
+                .iter()
+                .map(|c| (c.value.as_str(), c.total))
+                .collect::<Vec<_>>(),
+            vec![("V0", 10), ("V1", 10), ("V2", 10)]
+        );
+    }
+}
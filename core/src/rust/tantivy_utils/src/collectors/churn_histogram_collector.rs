@@ -0,0 +1,257 @@
+//! Collector for series start and end counts bucketed over time
+
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::Column,
+    TantivyError,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::field_constants::{END_TIME, START_TIME};
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// Number of series started and ended in each time bucket
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ChurnHistogram {
+    pub started: Vec<u64>,
+    pub ended: Vec<u64>,
+}
+
+impl ChurnHistogram {
+    fn new(buckets: usize) -> Self {
+        Self {
+            started: vec![0; buckets],
+            ended: vec![0; buckets],
+        }
+    }
+}
+
+/// Buckets matching docs by start time and end time into fixed width bins
+///
+/// Bucket `i` covers `[start + i * bucket_width, start + (i + 1) * bucket_width)`
+/// and the last bucket is cut at `end`.  Times outside `[start, end)` are not
+/// counted, and series that are still ingesting have not ended.
+pub struct ChurnHistogramCollector {
+    start: i64,
+    end: i64,
+    bucket_width: i64,
+    column_cache: ColumnCache,
+}
+
+impl ChurnHistogramCollector {
+    /// `bucket_width` must be positive
+    pub fn new(start: i64, end: i64, bucket_width: i64, column_cache: ColumnCache) -> Self {
+        Self {
+            start,
+            end,
+            bucket_width,
+            column_cache,
+        }
+    }
+
+    /// Number of buckets needed to cover the range
+    pub fn bucket_count(&self) -> usize {
+        if self.end <= self.start || self.bucket_width <= 0 {
+            return 0;
+        }
+
+        let span = self.end.abs_diff(self.start);
+        let width = self.bucket_width as u64;
+
+        span.div_ceil(width) as usize
+    }
+}
+
+impl LimitedCollector for ChurnHistogramCollector {
+    fn limit(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl Collector for ChurnHistogramCollector {
+    type Fruit = ChurnHistogram;
+
+    type Child = ChurnHistogramSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<ChurnHistogramSegmentCollector> {
+        let start_time_column: Column<i64> = self
+            .column_cache
+            .get_column(segment, START_TIME)?
+            .ok_or_else(|| TantivyError::FieldNotFound(START_TIME.to_string()))?;
+
+        let end_time_column: Column<i64> = self
+            .column_cache
+            .get_column(segment, END_TIME)?
+            .ok_or_else(|| TantivyError::FieldNotFound(END_TIME.to_string()))?;
+
+        Ok(ChurnHistogramSegmentCollector {
+            start: self.start,
+            end: self.end,
+            bucket_width: self.bucket_width,
+            start_time_column,
+            end_time_column,
+            histogram: ChurnHistogram::new(self.bucket_count()),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<ChurnHistogram>) -> tantivy::Result<ChurnHistogram> {
+        let mut result = ChurnHistogram::new(self.bucket_count());
+
+        for histogram in segment_fruits {
+            for (total, count) in result.started.iter_mut().zip(histogram.started) {
+                *total += count;
+            }
+
+            for (total, count) in result.ended.iter_mut().zip(histogram.ended) {
+                *total += count;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+pub struct ChurnHistogramSegmentCollector {
+    start: i64,
+    end: i64,
+    bucket_width: i64,
+    start_time_column: Column<i64>,
+    end_time_column: Column<i64>,
+    histogram: ChurnHistogram,
+}
+
+impl ChurnHistogramSegmentCollector {
+    // Bucket for a time, if it is in range
+    fn bucket(&self, time: i64) -> Option<usize> {
+        if time < self.start || time >= self.end {
+            return None;
+        }
+
+        Some((time.abs_diff(self.start) / self.bucket_width as u64) as usize)
+    }
+}
+
+impl LimitedSegmentCollector for ChurnHistogramSegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        _limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        if let Some(bucket) = self
+            .start_time_column
+            .first(doc)
+            .and_then(|t| self.bucket(t))
+        {
+            if let Some(count) = self.histogram.started.get_mut(bucket) {
+                *count += 1;
+            }
+        }
+
+        // Series still ingesting have an end time of i64::MAX, which is never in range
+        if let Some(bucket) = self.end_time_column.first(doc).and_then(|t| self.bucket(t)) {
+            if let Some(count) = self.histogram.ended.get_mut(bucket) {
+                *count += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for ChurnHistogramSegmentCollector {
+    type Fruit = ChurnHistogram;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    #[test]
+    fn test_churn_histogram() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        // Docs start at 1234 and 4321, end at 1235 and 10000
+        let collector = ChurnHistogramCollector::new(1000, 5000, 1000, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            ChurnHistogram {
+                started: vec![1, 0, 0, 1],
+                ended: vec![1, 0, 0, 0],
+            }
+        );
+    }
+
+    #[test]
+    fn test_churn_histogram_multi_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        // Doc N starts at N * 100 and ends 50 later, last bucket is partial
+        let collector = ChurnHistogramCollector::new(0, 2500, 1000, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            ChurnHistogram {
+                started: vec![10, 10, 5],
+                ended: vec![10, 10, 5],
+            }
+        );
+    }
+
+    #[test]
+    fn test_churn_histogram_bucket_count() {
+        let cache = ColumnCache::default();
+
+        assert_eq!(
+            ChurnHistogramCollector::new(0, 2500, 1000, cache.clone()).bucket_count(),
+            3
+        );
+        assert_eq!(
+            ChurnHistogramCollector::new(0, 2000, 1000, cache.clone()).bucket_count(),
+            2
+        );
+        assert_eq!(
+            ChurnHistogramCollector::new(i64::MIN, i64::MAX, i64::MAX, cache.clone())
+                .bucket_count(),
+            3
+        );
+        assert_eq!(
+            ChurnHistogramCollector::new(1000, 0, 1000, cache).bucket_count(),
+            0
+        );
+    }
+}
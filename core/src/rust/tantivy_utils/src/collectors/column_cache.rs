@@ -5,6 +5,7 @@ use std::sync::Arc;
 use quick_cache::{sync::Cache, Equivalent};
 use tantivy::{
     columnar::{BytesColumn, Column, DynamicColumn, HasAssociatedColumnType, StrColumn},
+    schema::Field,
     SegmentId, SegmentReader,
 };
 
@@ -130,6 +131,31 @@ impl ColumnCache {
             }
         }
     }
+
+    /// Get the string column holding values for a label
+    ///
+    /// Labels that are not schema fields resolve to a sub column of the
+    /// default map field, as `find_field_with_default` does.
+    pub fn get_label_column(
+        &self,
+        reader: &SegmentReader,
+        label: &str,
+        default_field: Option<Field>,
+    ) -> tantivy::Result<Option<StrColumn>> {
+        let Some((field, prefix)) = reader
+            .schema()
+            .find_field_with_default(label, default_field)
+        else {
+            return Ok(None);
+        };
+
+        let field_name = reader.schema().get_field_entry(field).name();
+        if prefix.is_empty() {
+            self.get_str_column(reader, field_name)
+        } else {
+            self.get_str_column(reader, &format!("{}.{}", field_name, prefix))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -137,7 +163,7 @@ mod tests {
     use crate::field_constants::{PART_ID, PART_KEY};
     use std::hash::{DefaultHasher, Hash, Hasher};
 
-    use crate::test_utils::{build_test_schema, COL1_NAME};
+    use crate::test_utils::{build_test_schema, COL1_NAME, JSON_ATTRIBUTE1_NAME};
 
     use super::*;
 
@@ -267,4 +293,33 @@ mod tests {
         assert_eq!(cache.cache.misses(), 1);
         assert_eq!(cache.cache.hits(), 1);
     }
+
+    #[test]
+    fn test_label_column() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+        let reader = index.searcher.segment_readers().first().unwrap();
+
+        // Schema field
+        let column = cache
+            .get_label_column(reader, COL1_NAME, Some(index.json_field))
+            .expect("Should succeed")
+            .expect("Should return one item");
+        assert_eq!(column.num_terms(), 2);
+
+        // Map field sub column
+        let column = cache
+            .get_label_column(reader, JSON_ATTRIBUTE1_NAME, Some(index.json_field))
+            .expect("Should succeed")
+            .expect("Should return one item");
+        let mut value = String::new();
+        column.ord_to_str(0, &mut value).expect("Should succeed");
+        assert_eq!(value, "othervalue");
+
+        // Unknown labels with no default field
+        assert!(cache
+            .get_label_column(reader, JSON_ATTRIBUTE1_NAME, None)
+            .expect("Should succeed")
+            .is_none());
+    }
 }
@@ -0,0 +1,156 @@
+//! Collector for series counts grouped by label values
+
+use hashbrown::HashMap;
+use tantivy::{collector::Collector, schema::Field, SegmentReader};
+
+use crate::collectors::column_cache::ColumnCache;
+
+use super::cardinality_rollup_collector::{
+    CardinalityRollupCollector, CardinalityRollupSegmentCollector,
+};
+use super::limited_collector::LimitedCollector;
+
+/// Number of series sharing one combination of label values
+#[derive(Debug, PartialEq, Eq)]
+pub struct LabelGroupCount {
+    /// Values in the order the labels were requested, empty if missing
+    pub values: Vec<String>,
+    /// Number of series in the group
+    pub count: u64,
+}
+
+/// Counts matching series grouped by the values of a list of labels and returns
+/// the largest groups
+///
+/// Every matching doc must be seen to get exact counts, so the limit applies
+/// to the number of groups returned rather than docs collected.
+pub struct CountByLabelsCollector {
+    rollup: CardinalityRollupCollector,
+    limit: usize,
+}
+
+impl CountByLabelsCollector {
+    pub fn new(
+        labels: Vec<String>,
+        default_field: Option<Field>,
+        limit: usize,
+        column_cache: ColumnCache,
+    ) -> Self {
+        Self {
+            rollup: CardinalityRollupCollector::new(labels, default_field, column_cache),
+            limit,
+        }
+    }
+}
+
+impl LimitedCollector for CountByLabelsCollector {
+    fn limit(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl Collector for CountByLabelsCollector {
+    type Fruit = Vec<LabelGroupCount>;
+
+    type Child = CardinalityRollupSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<CardinalityRollupSegmentCollector> {
+        self.rollup.for_segment(segment_local_id, segment)
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(Vec<String>, u64, u64)>>,
+    ) -> tantivy::Result<Vec<LabelGroupCount>> {
+        let mut counts: HashMap<Vec<String>, u64> = HashMap::new();
+
+        for (values, total, _active) in segment_fruits.into_iter().flatten() {
+            *counts.entry(values).or_insert(0) += total;
+        }
+
+        let mut results: Vec<_> = counts
+            .into_iter()
+            .map(|(values, count)| LabelGroupCount { values, count })
+            .collect();
+
+        // Largest groups first
+        results.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.values.cmp(&b.values)));
+        results.truncate(self.limit);
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    fn group(values: &[&str], count: u64) -> LabelGroupCount {
+        LabelGroupCount {
+            values: values.iter().map(|v| v.to_string()).collect(),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_count_by_labels() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let collector = CountByLabelsCollector::new(
+            vec!["col1".to_string(), "f2".to_string()],
+            Some(index.json_field),
+            100,
+            column_cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            vec![
+                group(&["ABC", "value2"], 1),
+                group(&["DEF", "othervalue2"], 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_by_labels_limit() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        let collector = CountByLabelsCollector::new(
+            vec!["col2".to_string(), "col1".to_string()],
+            Some(index.json_field),
+            2,
+            column_cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        // Groups spanning segments are merged before the limit
+        assert_eq!(
+            results,
+            vec![group(&["def", "V0"], 10), group(&["def", "V1"], 10)]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Filter collector that keeps docs that ended at or before a time
+
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::Column,
+    SegmentReader, TantivyError,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::field_constants;
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// Filters results to docs with an end time at or before a given time
+///
+/// This lets callers run a coarser cached end time query, such as one rounded
+/// up to a time bucket, and still get exact results.
+pub struct EndTimeFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    /// Inner collector
+    collector: &'a T,
+    /// Max end time, inclusive
+    ended_before: i64,
+    /// Column cache
+    column_cache: ColumnCache,
+}
+
+impl<'a, T> EndTimeFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    pub fn new(collector: &'a T, ended_before: i64, column_cache: ColumnCache) -> Self {
+        Self {
+            collector,
+            ended_before,
+            column_cache,
+        }
+    }
+}
+
+impl<'a, T> LimitedCollector for EndTimeFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    fn limit(&self) -> usize {
+        self.collector.limit()
+    }
+
+    fn truncated(&self) -> bool {
+        self.collector.truncated()
+    }
+
+    fn skip_segment(&self, reader: &SegmentReader) -> Result<bool, TantivyError> {
+        let end_column: Option<Column<i64>> = self
+            .column_cache
+            .get_column(reader, field_constants::END_TIME)?;
+
+        // Docs missing an end time never match, so the min of the values
+        // present is a usable bound even for sparse columns
+        if let Some(end_column) = end_column {
+            if end_column.values.num_vals() > 0 && end_column.min_value() > self.ended_before {
+                return Ok(true);
+            }
+        }
+
+        self.collector.skip_segment(reader)
+    }
+}
+
+impl<'a, T> Collector for EndTimeFilter<'a, T>
+where
+    T: LimitedCollector,
+    T::Child: LimitedSegmentCollector,
+{
+    type Fruit = T::Fruit;
+
+    type Child = EndTimeFilterSegmentCollector<T::Child>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let end_column = self
+            .column_cache
+            .get_column(segment, field_constants::END_TIME)?
+            .ok_or_else(|| TantivyError::FieldNotFound(field_constants::END_TIME.to_string()))?;
+
+        let collector = self.collector.for_segment(segment_local_id, segment)?;
+
+        Ok(EndTimeFilterSegmentCollector::<T::Child> {
+            end_column,
+            ended_before: self.ended_before,
+            collector,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<Self::Child as tantivy::collector::SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<Self::Fruit> {
+        self.collector.merge_fruits(segment_fruits)
+    }
+}
+
+pub struct EndTimeFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    collector: T,
+    end_column: Column<i64>,
+    ended_before: i64,
+}
+
+impl<T> LimitedSegmentCollector for EndTimeFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        score: tantivy::Score,
+        limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        let doc_end = self.end_column.first(doc).unwrap_or(i64::MAX);
+        if doc_end > self.ended_before {
+            return Ok(());
+        }
+
+        self.collector.collect_with_limiter(doc, score, limiter)
+    }
+}
+
+impl<T> SegmentCollector for EndTimeFilterSegmentCollector<T>
+where
+    T: LimitedSegmentCollector,
+{
+    type Fruit = T::Fruit;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.collector.harvest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::{collector::Count, query::AllQuery};
+
+    use crate::{collectors::limited_collector::UnlimitedCollector, test_utils::build_test_schema};
+
+    use super::*;
+
+    #[test]
+    fn test_end_time_filter() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let query = AllQuery;
+        let collector = UnlimitedCollector::new(Count);
+
+        for (ended_before, expected) in [(0, 0), (1235, 1), (9999, 1), (10000, 2), (i64::MAX, 2)] {
+            let filter = EndTimeFilter::new(&collector, ended_before, cache.clone());
+            let result = index
+                .searcher
+                .search(&query, &filter)
+                .expect("Should succeed");
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_end_time_filter_skip_segment() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+        let collector = UnlimitedCollector::new(Count);
+        let reader = index.searcher.segment_readers().first().unwrap();
+
+        let filter = EndTimeFilter::new(&collector, 1000, cache.clone());
+        assert!(filter.skip_segment(reader).expect("Should succeed"));
+
+        let filter = EndTimeFilter::new(&collector, 1235, cache);
+        assert!(!filter.skip_segment(reader).expect("Should succeed"));
+    }
+}
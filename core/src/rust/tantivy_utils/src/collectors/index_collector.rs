@@ -3,6 +3,8 @@
 
 use tantivy::{collector::SegmentCollector, Searcher, SegmentReader, TantivyError};
 
+use crate::query::search_stats::SearchStats;
+
 use super::limited_collector::{LimitCounter, LimitedCollector, LimitedSegmentCollector};
 
 /// Index Segment collector
@@ -11,6 +13,9 @@ where
     Self::Child: LimitedSegmentCollector,
 {
     /// Colllect data across an entire index segment
+    ///
+    /// If the limiter is already at its limit, collect nothing but mark it
+    /// exceeded if the segment has anything that would have been collected.
     fn collect_over_index(
         &self,
         reader: &SegmentReader,
@@ -19,6 +24,21 @@ where
 }
 
 pub fn collect_from_index<C>(searcher: &Searcher, collector: C) -> Result<C::Fruit, TantivyError>
+where
+    C: IndexCollector,
+    C::Child: LimitedSegmentCollector,
+{
+    let mut stats = SearchStats::default();
+
+    collect_from_index_with_stats(searcher, collector, &mut stats)
+}
+
+/// Collect across an entire index, recording what the collection did in `stats`
+pub fn collect_from_index_with_stats<C>(
+    searcher: &Searcher,
+    collector: C,
+    stats: &mut SearchStats,
+) -> Result<C::Fruit, TantivyError>
 where
     C: IndexCollector,
     C::Child: LimitedSegmentCollector,
@@ -30,14 +50,27 @@ where
     let mut limiter = LimitCounter::new(collector.limit());
 
     for segment_reader in segment_readers.iter() {
+        // Once the limit is filled exactly, later segments are only checked for
+        // anything that would be cut
+        let at_limit = limiter.at_limit();
+        let examined_before = limiter.examined();
+
         let results = collector.collect_over_index(segment_reader, &mut limiter)?;
 
-        fruits.push(results);
+        if !at_limit {
+            stats.add_segment(limiter.examined() - examined_before, false);
+            fruits.push(results);
+        }
 
-        if limiter.at_limit() {
+        if limiter.exceeded() {
             break;
         }
     }
 
-    collector.merge_fruits(fruits)
+    let fruit = collector.merge_fruits(fruits)?;
+
+    stats.limit_hit = limiter.exceeded() || collector.truncated();
+    stats.segments_after_limit = segment_readers.len() as u32 - stats.segments_read;
+
+    Ok(fruit)
 }
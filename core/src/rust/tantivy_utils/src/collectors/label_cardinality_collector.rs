@@ -0,0 +1,314 @@
+//! Collector for the number of distinct values of each label
+
+use hashbrown::HashMap;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::StrColumn,
+    schema::Field,
+    SegmentReader,
+};
+use tantivy_common::BitSet;
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::field_constants::{self, facet_field_name};
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+mod distinct_counter;
+
+pub use distinct_counter::{hash_value, DistinctCounter};
+
+/// Cardinality of a single label
+#[derive(Debug, PartialEq, Eq)]
+pub struct LabelCardinality {
+    /// Label name
+    pub label: String,
+    /// Number of distinct values, exact up to the threshold and estimated after
+    pub distinct_values: u64,
+    /// Number of series with this label
+    pub series: u64,
+}
+
+/// Collects distinct value and series counts for every label of matching docs
+///
+/// Labels are found from the label list facet, and values are read from each
+/// label's string column.  Distinct values are counted exactly up to
+/// `exact_threshold` and estimated with a HyperLogLog sketch beyond that.
+pub struct LabelCardinalityCollector {
+    default_field: Option<Field>,
+    exact_threshold: usize,
+    column_cache: ColumnCache,
+}
+
+impl LabelCardinalityCollector {
+    pub fn new(
+        default_field: Option<Field>,
+        exact_threshold: usize,
+        column_cache: ColumnCache,
+    ) -> Self {
+        Self {
+            default_field,
+            exact_threshold,
+            column_cache,
+        }
+    }
+}
+
+impl LimitedCollector for LabelCardinalityCollector {
+    fn limit(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl Collector for LabelCardinalityCollector {
+    type Fruit = Vec<LabelCardinality>;
+
+    type Child = LabelCardinalitySegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: tantivy::SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<LabelCardinalitySegmentCollector> {
+        let label_column = self
+            .column_cache
+            .get_str_column(segment, &facet_field_name(field_constants::LABEL_LIST))?;
+
+        // Label names per segment are few, so resolve all their columns up front
+        let mut labels = vec![];
+        if let Some(label_column) = &label_column {
+            let mut stream = label_column.dictionary().stream()?;
+            while stream.advance() {
+                let label = String::from_utf8_lossy(stream.key()).to_string();
+                let column =
+                    self.column_cache
+                        .get_label_column(segment, &label, self.default_field)?;
+                let values = column
+                    .as_ref()
+                    .map(|c| BitSet::with_max_value(c.num_terms() as u32));
+
+                labels.push(SegmentLabel {
+                    label,
+                    column,
+                    values,
+                    series: 0,
+                });
+            }
+        }
+
+        Ok(LabelCardinalitySegmentCollector {
+            label_column,
+            labels,
+            exact_threshold: self.exact_threshold,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<HashMap<String, (DistinctCounter, u64)>>,
+    ) -> tantivy::Result<Vec<LabelCardinality>> {
+        let mut results: HashMap<String, (DistinctCounter, u64)> = HashMap::new();
+
+        for map in segment_fruits.into_iter() {
+            for (label, (counter, series)) in map.into_iter() {
+                let entry = results
+                    .entry(label)
+                    .or_insert_with(|| (DistinctCounter::new(self.exact_threshold), 0));
+
+                entry.0.merge(counter);
+                entry.1 += series;
+            }
+        }
+
+        let mut results: Vec<_> = results
+            .into_iter()
+            .map(|(label, (counter, series))| LabelCardinality {
+                label,
+                distinct_values: counter.count(),
+                series,
+            })
+            .collect();
+
+        // Highest cardinality first
+        results.sort_by(|a, b| {
+            b.distinct_values
+                .cmp(&a.distinct_values)
+                .then_with(|| a.label.cmp(&b.label))
+        });
+
+        Ok(results)
+    }
+}
+
+// Per segment state for one label, indexed by label ord
+struct SegmentLabel {
+    label: String,
+    column: Option<StrColumn>,
+    // Value ords seen
+    values: Option<BitSet>,
+    series: u64,
+}
+
+pub struct LabelCardinalitySegmentCollector {
+    label_column: Option<StrColumn>,
+    labels: Vec<SegmentLabel>,
+    exact_threshold: usize,
+}
+
+impl LimitedSegmentCollector for LabelCardinalitySegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        _limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        if let Some(label_column) = &self.label_column {
+            for label_ord in label_column.term_ords(doc) {
+                let Some(label) = self.labels.get_mut(label_ord as usize) else {
+                    continue;
+                };
+
+                label.series += 1;
+
+                if let (Some(column), Some(values)) = (&label.column, &mut label.values) {
+                    for value_ord in column.term_ords(doc) {
+                        values.insert(value_ord as u32);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for LabelCardinalitySegmentCollector {
+    type Fruit = HashMap<String, (DistinctCounter, u64)>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        let mut results = HashMap::new();
+
+        for label in self.labels.into_iter() {
+            if label.series == 0 {
+                continue;
+            }
+
+            let mut counter = DistinctCounter::new(self.exact_threshold);
+
+            // Value ords are only meaningful within a segment, so hash the values
+            // themselves.  Streaming the dictionary in order is much cheaper than
+            // looking up each ord.
+            if let (Some(column), Some(values)) = (&label.column, &label.values) {
+                if values.len() > 0 {
+                    if let Ok(mut stream) = column.dictionary().stream() {
+                        while stream.advance() {
+                            if values.contains(stream.term_ord() as u32) {
+                                counter.insert(hash_value(stream.key()));
+                            }
+                        }
+                    }
+                }
+            }
+
+            results.insert(label.label, (counter, label.series));
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    #[test]
+    fn test_label_cardinality_collector() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let collector =
+            LabelCardinalityCollector::new(Some(index.json_field), usize::MAX, column_cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            ["col1", "col2", "f1", "f2"]
+                .into_iter()
+                .map(|label| LabelCardinality {
+                    label: label.to_string(),
+                    distinct_values: 2,
+                    series: 2,
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_label_cardinality_collector_multi_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        let collector =
+            LabelCardinalityCollector::new(Some(index.json_field), usize::MAX, column_cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        // Values repeat across segments and are only counted once
+        assert_eq!(
+            results,
+            vec![
+                LabelCardinality {
+                    label: "col1".to_string(),
+                    distinct_values: 3,
+                    series: 30,
+                },
+                LabelCardinality {
+                    label: "col2".to_string(),
+                    distinct_values: 1,
+                    series: 30,
+                },
+                LabelCardinality {
+                    label: "f1".to_string(),
+                    distinct_values: 1,
+                    series: 30,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_cardinality_collector_approximate() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        // A threshold of 1 forces sketches for col1, which are very accurate at small sizes
+        let collector = LabelCardinalityCollector::new(Some(index.json_field), 1, column_cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results[0].label, "col1");
+        assert_eq!(results[0].distinct_values, 3);
+    }
+}
@@ -0,0 +1,206 @@
+//! Distinct value counting - exact for small sets, HyperLogLog for large ones
+
+use std::hash::{DefaultHasher, Hasher};
+
+use hashbrown::HashSet;
+
+/// Hash a value for distinct counting
+///
+/// Hashes must be stable across segments so counts can be merged.
+pub fn hash_value(value: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(value);
+
+    hasher.finish()
+}
+
+/// Counts distinct value hashes
+///
+/// Hashes are kept exactly until there are more than `exact_threshold` of them,
+/// at which point the counter switches to a HyperLogLog sketch.
+pub struct DistinctCounter {
+    exact_threshold: usize,
+    state: CounterState,
+}
+
+enum CounterState {
+    Exact(HashSet<u64>),
+    Approximate(HyperLogLog),
+}
+
+impl DistinctCounter {
+    pub fn new(exact_threshold: usize) -> Self {
+        Self {
+            exact_threshold,
+            state: CounterState::Exact(HashSet::new()),
+        }
+    }
+
+    /// Add a value hash
+    pub fn insert(&mut self, hash: u64) {
+        match &mut self.state {
+            CounterState::Exact(hashes) => {
+                hashes.insert(hash);
+
+                if hashes.len() > self.exact_threshold {
+                    self.switch_to_approximate();
+                }
+            }
+            CounterState::Approximate(sketch) => sketch.insert(hash),
+        }
+    }
+
+    /// Merge in the values from another counter
+    pub fn merge(&mut self, other: DistinctCounter) {
+        match other.state {
+            CounterState::Exact(hashes) => {
+                for hash in hashes {
+                    self.insert(hash);
+                }
+            }
+            CounterState::Approximate(other_sketch) => {
+                self.switch_to_approximate();
+
+                if let CounterState::Approximate(sketch) = &mut self.state {
+                    sketch.merge(&other_sketch);
+                }
+            }
+        }
+    }
+
+    /// Number of distinct values seen
+    pub fn count(&self) -> u64 {
+        match &self.state {
+            CounterState::Exact(hashes) => hashes.len() as u64,
+            CounterState::Approximate(sketch) => sketch.count(),
+        }
+    }
+
+    /// True if the count is an estimate
+    pub fn is_approximate(&self) -> bool {
+        matches!(self.state, CounterState::Approximate(_))
+    }
+
+    fn switch_to_approximate(&mut self) {
+        if let CounterState::Exact(hashes) = &self.state {
+            let mut sketch = HyperLogLog::new();
+            for hash in hashes {
+                sketch.insert(*hash);
+            }
+
+            self.state = CounterState::Approximate(sketch);
+        }
+    }
+}
+
+// 2^12 registers gives a standard error of about 1.6%
+const PRECISION: u32 = 12;
+const REGISTER_COUNT: usize = 1 << PRECISION;
+
+/// HyperLogLog cardinality sketch over pre-hashed values
+struct HyperLogLog {
+    registers: Box<[u8]>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; REGISTER_COUNT].into_boxed_slice(),
+        }
+    }
+
+    fn insert(&mut self, hash: u64) {
+        // Top bits pick the register, the position of the first set bit in the
+        // rest is the rank.  The guard bit caps the rank for all zero hashes.
+        let index = (hash >> (64 - PRECISION)) as usize;
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = (rest.leading_zeros() + 1) as u8;
+
+        if self.registers[index] < rank {
+            self.registers[index] = rank;
+        }
+    }
+
+    fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if *register < *other {
+                *register = *other;
+            }
+        }
+    }
+
+    fn count(&self) -> u64 {
+        let m = REGISTER_COUNT as f64;
+        let alpha = 0.7213 / (1.0 + (1.079 / m));
+
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // Linear counting is more accurate for small cardinalities
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_range(counter: &mut DistinctCounter, range: std::ops::Range<u64>) {
+        for i in range {
+            counter.insert(hash_value(&i.to_le_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_exact_count() {
+        let mut counter = DistinctCounter::new(100);
+
+        insert_range(&mut counter, 0..50);
+        insert_range(&mut counter, 25..75);
+
+        assert!(!counter.is_approximate());
+        assert_eq!(counter.count(), 75);
+    }
+
+    #[test]
+    fn test_approximate_count() {
+        let mut counter = DistinctCounter::new(100);
+
+        insert_range(&mut counter, 0..100_000);
+
+        assert!(counter.is_approximate());
+
+        let count = counter.count() as f64;
+        assert!((count - 100_000.0).abs() < 100_000.0 * 0.05, "{}", count);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut exact = DistinctCounter::new(1000);
+        insert_range(&mut exact, 0..500);
+
+        let mut other_exact = DistinctCounter::new(1000);
+        insert_range(&mut other_exact, 250..750);
+
+        exact.merge(other_exact);
+
+        assert!(!exact.is_approximate());
+        assert_eq!(exact.count(), 750);
+
+        // Going past the threshold switches to a sketch
+        let mut approximate = DistinctCounter::new(1000);
+        insert_range(&mut approximate, 500..20_000);
+
+        exact.merge(approximate);
+
+        assert!(exact.is_approximate());
+
+        let count = exact.count() as f64;
+        assert!((count - 20_000.0).abs() < 20_000.0 * 0.05, "{}", count);
+    }
+}
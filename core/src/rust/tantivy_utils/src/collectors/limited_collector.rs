@@ -2,14 +2,15 @@
 
 use tantivy::{
     collector::{Collector, SegmentCollector},
-    query::Weight,
+    fastfield::AliveBitSet,
+    query::{Scorer, Weight},
     DocId, Score, SegmentReader, TantivyError, TERMINATED,
 };
 
 mod limit_counter;
 mod unlimited_collector;
 
-pub use limit_counter::{LimitCounter, LimitCounterOptionExt};
+pub use limit_counter::{LimitCounter, LimitCounterOptionExt, ParallelLimit};
 pub use unlimited_collector::{UnlimitedCollector, UnlimitedSegmentCollector};
 
 /// Marker struct for exceeding limits as an error
@@ -41,6 +42,24 @@ where
     /// Returns configured limit
     fn limit(&self) -> usize;
 
+    /// Returns true if no docs in a segment can be collected
+    ///
+    /// This lets a search skip the segment entirely before building query
+    /// weights or loading cached results.  It must be conservative - when in
+    /// doubt, do not skip.
+    fn skip_segment(&self, _reader: &SegmentReader) -> Result<bool, TantivyError> {
+        Ok(false)
+    }
+
+    /// Returns true if the collector dropped results that its limit did not count
+    ///
+    /// Collectors that keep their own bounded results, rather than stopping at
+    /// the limiter, report here whether anything was left out.  Only valid after
+    /// `merge_fruits`.
+    fn truncated(&self) -> bool {
+        false
+    }
+
     fn collect_segment_with_limiter(
         &self,
         weight: &dyn Weight,
@@ -54,21 +73,30 @@ where
         // This is an extension of the logic that the base Collector trait provides:
         // For each document the scorer produces:
         //    * Check if it is alive if we have an alive_bitset
+        //    * Count it as examined
         //    * Collect it with the limiter method
-        //    * If the collect method returns an error that signals we're at the limit, abort
+        //    * If the collect method returns an error that signals we're at the limit, abort,
+        //      noting if there were more docs that were not collected
         //
         // This code does not handle scoring, in part because there's no usage of scoring in FiloDB.
         match (reader.alive_bitset(), self.requires_scoring()) {
             (Some(alive_bitset), false) => {
                 let mut doc = scorer.doc();
                 while doc != TERMINATED {
-                    if alive_bitset.is_alive(doc)
-                        && segment_collector
+                    if alive_bitset.is_alive(doc) {
+                        limiter.examine();
+
+                        if segment_collector
                             .collect_with_limiter(doc, scorer.score(), Some(limiter))
                             .is_err()
-                    {
-                        // Hit limit
-                        break;
+                        {
+                            // Hit limit
+                            scorer.advance();
+                            if has_alive_doc(scorer.as_mut(), Some(alive_bitset)) {
+                                limiter.mark_exceeded();
+                            }
+                            break;
+                        }
                     }
                     doc = scorer.advance();
                 }
@@ -76,10 +104,15 @@ where
             (None, false) => {
                 let mut doc = scorer.doc();
                 while doc != TERMINATED {
+                    limiter.examine();
+
                     if segment_collector
                         .collect_with_limiter(doc, scorer.score(), Some(limiter))
                         .is_err()
                     {
+                        if scorer.advance() != TERMINATED {
+                            limiter.mark_exceeded();
+                        }
                         break;
                     }
                     doc = scorer.advance();
@@ -95,3 +128,16 @@ where
         Ok(segment_collector.harvest())
     }
 }
+
+/// Returns true if the scorer is on or before a live doc
+pub(crate) fn has_alive_doc(scorer: &mut dyn Scorer, alive_bitset: Option<&AliveBitSet>) -> bool {
+    let mut doc = scorer.doc();
+    while doc != TERMINATED {
+        if alive_bitset.map_or(true, |alive_bitset| alive_bitset.is_alive(doc)) {
+            return true;
+        }
+        doc = scorer.advance();
+    }
+
+    false
+}
@@ -1,16 +1,55 @@
 //! Counter for limiting
 
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
 use super::{LimitExceeded, LimitResult};
 
+// Summing earlier segments' counts is O(segments), so while far from the limit
+// it is only re-read once per this many items
+const PRECEDING_REFRESH_INTERVAL: usize = 64;
+
 /// Counter to keep track of and enforce a limit
 pub struct LimitCounter {
     limit: usize,
     count: usize,
+    // Docs offered for collection, whether or not they counted
+    examined: u64,
+    // A doc was offered after the limit was reached
+    exceeded: bool,
+    parallel: Option<(Arc<ParallelLimit>, usize)>,
+    // Last known items collected by earlier parallel segments.  These only grow,
+    // so a stale value can delay stopping but never stops a segment too early.
+    preceding: usize,
 }
 
 impl LimitCounter {
     pub fn new(limit: usize) -> Self {
-        Self { limit, count: 0 }
+        Self {
+            limit,
+            count: 0,
+            examined: 0,
+            exceeded: false,
+            parallel: None,
+            preceding: 0,
+        }
+    }
+
+    /// Create a counter for one segment of a search running segments in parallel
+    ///
+    /// Items collected by earlier segments count against the limit, matching
+    /// what a sequential search over the segments in order would see
+    pub fn for_parallel_segment(limit: Arc<ParallelLimit>, segment_ord: usize) -> Self {
+        Self {
+            limit: limit.limit,
+            count: 0,
+            examined: 0,
+            exceeded: false,
+            preceding: limit.preceding(segment_ord),
+            parallel: Some((limit, segment_ord)),
+        }
     }
 
     /// Increment the seen items, fail if hit the limit
@@ -18,15 +57,98 @@ impl LimitCounter {
     pub fn increment(&mut self) -> LimitResult {
         self.count += 1;
 
-        if self.count >= self.limit {
+        if let Some((parallel, segment_ord)) = &self.parallel {
+            if let Some(count) = parallel.counts.get(*segment_ord) {
+                count.store(self.count, Ordering::Relaxed);
+            }
+
+            // Refresh periodically, or on every item once close enough to the limit
+            // that a stale value could miss it
+            let total = self.count.saturating_add(self.preceding);
+            if self.count % PRECEDING_REFRESH_INTERVAL == 0
+                || total.saturating_add(PRECEDING_REFRESH_INTERVAL) >= self.limit
+            {
+                self.preceding = parallel.preceding(*segment_ord);
+            }
+        }
+
+        if self.at_limit() {
             Err(LimitExceeded)
         } else {
             Ok(())
         }
     }
 
+    /// Record a doc being offered for collection
+    #[inline]
+    pub fn examine(&mut self) {
+        self.examined += 1;
+    }
+
+    /// Number of docs offered for collection
+    pub fn examined(&self) -> u64 {
+        self.examined
+    }
+
     pub fn at_limit(&self) -> bool {
-        self.count >= self.limit
+        self.count.saturating_add(self.preceding) >= self.limit
+    }
+
+    /// Record that more items were available after the limit was reached
+    pub fn mark_exceeded(&mut self) {
+        self.exceeded = true;
+
+        if let Some((parallel, _)) = &self.parallel {
+            parallel.exceeded.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// True if items were cut by the limit
+    ///
+    /// Unlike `at_limit` this is false when exactly `limit` items exist.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+/// Limit state shared by segments that are collected in parallel
+///
+/// Each segment publishes how many items it has collected so far.  A segment
+/// can stop once it plus the segments before it reach the limit, since its
+/// remaining items would be cut when the fruits are merged in segment order.
+pub struct ParallelLimit {
+    limit: usize,
+    counts: Vec<AtomicUsize>,
+    exceeded: AtomicBool,
+}
+
+impl ParallelLimit {
+    pub fn new(limit: usize, segment_count: usize) -> Self {
+        Self {
+            limit,
+            counts: (0..segment_count).map(|_| AtomicUsize::new(0)).collect(),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// True if all segments together have reached the limit
+    pub fn at_limit(&self) -> bool {
+        self.preceding(self.counts.len()) >= self.limit
+    }
+
+    /// True if any segment had items cut by the limit
+    ///
+    /// Segments can briefly collect past the limit before they see what earlier
+    /// segments collected, and those extra items are cut when merging.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed) || self.preceding(self.counts.len()) > self.limit
+    }
+
+    // Items collected so far by segments before this one
+    fn preceding(&self, segment_ord: usize) -> usize {
+        self.counts.iter().take(segment_ord).fold(0usize, |acc, c| {
+            acc.saturating_add(c.load(Ordering::Relaxed))
+        })
     }
 }
 
@@ -62,5 +184,92 @@ mod tests {
         // Count 2
         assert!(counter.increment().is_err());
         assert!(counter.at_limit());
+        assert!(!counter.exceeded());
+
+        counter.mark_exceeded();
+        assert!(counter.exceeded());
+    }
+
+    #[test]
+    fn test_parallel_limit_counter() {
+        let limit = Arc::new(ParallelLimit::new(3, 2));
+
+        let mut first = LimitCounter::for_parallel_segment(limit.clone(), 0);
+        let mut second = LimitCounter::for_parallel_segment(limit, 1);
+
+        // Later segments see items from earlier ones
+        assert!(second.increment().is_ok());
+        assert!(first.increment().is_ok());
+        assert!(second.increment().is_err());
+        assert!(second.at_limit());
+
+        // Earlier segments don't see items from later ones
+        assert!(!first.at_limit());
+        assert!(first.increment().is_ok());
+        assert!(first.increment().is_err());
+        assert!(first.at_limit());
+    }
+
+    #[test]
+    fn test_parallel_limit_counter_refresh() {
+        let limit = Arc::new(ParallelLimit::new(1000, 2));
+
+        let mut first = LimitCounter::for_parallel_segment(limit.clone(), 0);
+        let mut second = LimitCounter::for_parallel_segment(limit, 1);
+
+        for _ in 0..500 {
+            assert!(first.increment().is_ok());
+        }
+
+        // Far from the limit, earlier segments are only re-read periodically
+        assert!(second.increment().is_ok());
+        assert_eq!(second.preceding, 0);
+
+        for _ in 1..PRECEDING_REFRESH_INTERVAL {
+            assert!(second.increment().is_ok());
+        }
+        assert_eq!(second.preceding, 500);
+
+        // Close to the limit, they are re-read on every item
+        for _ in 0..(1000 - 500 - PRECEDING_REFRESH_INTERVAL * 2) {
+            assert!(second.increment().is_ok());
+        }
+        while second.increment().is_ok() {}
+        assert_eq!(second.count + 500, 1000);
+    }
+
+    #[test]
+    fn test_parallel_limit_at_limit() {
+        let limit = Arc::new(ParallelLimit::new(2, 2));
+
+        let mut first = LimitCounter::for_parallel_segment(limit.clone(), 0);
+        let mut second = LimitCounter::for_parallel_segment(limit.clone(), 1);
+
+        assert!(second.increment().is_ok());
+        assert!(!limit.at_limit());
+
+        // The first segment doesn't see the second, but together they're at the limit
+        assert!(first.increment().is_ok());
+        assert!(limit.at_limit());
+        assert!(!limit.exceeded());
+
+        // Together they collected more than the limit
+        assert!(first.increment().is_err());
+        assert!(limit.exceeded());
+    }
+
+    #[test]
+    fn test_parallel_limit_mark_exceeded() {
+        let limit = Arc::new(ParallelLimit::new(1, 2));
+
+        let mut first = LimitCounter::for_parallel_segment(limit.clone(), 0);
+        assert!(first.increment().is_err());
+
+        let mut second = LimitCounter::for_parallel_segment(limit.clone(), 1);
+        assert!(second.at_limit());
+        assert!(!limit.exceeded());
+
+        second.mark_exceeded();
+        assert!(limit.exceeded());
     }
 }
@@ -0,0 +1,231 @@
+//! Collector for the values of several labels in one pass
+
+use hashbrown::HashMap;
+use nohash_hasher::IntMap;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::StrColumn,
+    schema::Field,
+    SegmentReader,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+
+use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
+
+/// Counts the values of a list of labels over matching docs
+///
+/// Each doc is visited once and its ords are counted for every label, so the
+/// query runs once no matter how many labels are requested.  Results are per
+/// label in request order, each the `top_k` most common values with the number
+/// of series holding them.
+pub struct MultiLabelValuesCollector {
+    labels: Vec<String>,
+    default_field: Option<Field>,
+    top_k: usize,
+    column_cache: ColumnCache,
+}
+
+impl MultiLabelValuesCollector {
+    pub fn new(
+        labels: Vec<String>,
+        default_field: Option<Field>,
+        top_k: usize,
+        column_cache: ColumnCache,
+    ) -> Self {
+        Self {
+            labels,
+            default_field,
+            top_k,
+            column_cache,
+        }
+    }
+}
+
+impl LimitedCollector for MultiLabelValuesCollector {
+    fn limit(&self) -> usize {
+        // Counts must be exact to pick the top values
+        usize::MAX
+    }
+}
+
+impl Collector for MultiLabelValuesCollector {
+    type Fruit = Vec<Vec<(String, u64)>>;
+
+    type Child = MultiLabelValuesSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: tantivy::SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<MultiLabelValuesSegmentCollector> {
+        let columns = self
+            .labels
+            .iter()
+            .map(|label| {
+                self.column_cache
+                    .get_label_column(segment, label, self.default_field)
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+
+        let counts = vec![IntMap::default(); columns.len()];
+
+        Ok(MultiLabelValuesSegmentCollector { columns, counts })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<HashMap<String, u64>>>,
+    ) -> tantivy::Result<Vec<Vec<(String, u64)>>> {
+        let mut merged: Vec<HashMap<String, u64>> = vec![HashMap::new(); self.labels.len()];
+
+        for segment in segment_fruits {
+            for (totals, counts) in merged.iter_mut().zip(segment) {
+                for (value, count) in counts {
+                    *totals.entry(value).or_insert(0) += count;
+                }
+            }
+        }
+
+        Ok(merged
+            .into_iter()
+            .map(|counts| {
+                let mut values: Vec<_> = counts.into_iter().collect();
+                values.sort_by(|(value_a, count_a), (value_b, count_b)| {
+                    count_b.cmp(count_a).then_with(|| value_a.cmp(value_b))
+                });
+                values.truncate(self.top_k);
+
+                values
+            })
+            .collect())
+    }
+}
+
+pub struct MultiLabelValuesSegmentCollector {
+    columns: Vec<Option<StrColumn>>,
+    // Counts of each ord seen, per label
+    counts: Vec<IntMap<u64, u64>>,
+}
+
+impl LimitedSegmentCollector for MultiLabelValuesSegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        _limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        for (column, counts) in self.columns.iter().zip(self.counts.iter_mut()) {
+            if let Some(column) = column {
+                for ord in column.term_ords(doc) {
+                    *counts.entry(ord).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for MultiLabelValuesSegmentCollector {
+    type Fruit = Vec<HashMap<String, u64>>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        // Ords are only meaningful within a segment, so convert to values
+        self.columns
+            .iter()
+            .zip(self.counts)
+            .map(|(column, counts)| {
+                let Some(column) = column else {
+                    return HashMap::new();
+                };
+
+                counts
+                    .into_iter()
+                    .map(|(ord, count)| {
+                        let mut value = String::new();
+                        let _ = column.ord_to_str(ord, &mut value);
+
+                        (value, count)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    fn values(values: &[(&str, u64)]) -> Vec<(String, u64)> {
+        values.iter().map(|(v, c)| (v.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn test_multi_label_values() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = MultiLabelValuesCollector::new(
+            vec![
+                "col1".to_string(),
+                "f2".to_string(),
+                "not_found".to_string(),
+            ],
+            Some(index.json_field),
+            100,
+            cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            vec![
+                values(&[("ABC", 1), ("DEF", 1)]),
+                values(&[("othervalue2", 1), ("value2", 1)]),
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_label_values_top_k() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector = MultiLabelValuesCollector::new(
+            vec!["col1".to_string(), "col2".to_string()],
+            Some(index.json_field),
+            2,
+            cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        // Counts are merged across segments before picking the top values
+        assert_eq!(
+            results,
+            vec![values(&[("V0", 10), ("V1", 10)]), values(&[("def", 30)])]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Collector for the first records in a fixed order, using a top-K heap
+
+use super::bounded_record_collector::{BoundedRecordCollector, RecordPriority};
+
+pub use super::bounded_record_collector::OrderedRecord;
+
+/// Order of records applied before the limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordOrder {
+    /// Ascending part ID
+    PartId,
+    /// Ascending start time
+    StartTime,
+    /// Descending end time, so series still ingesting come first
+    EndTimeDesc,
+}
+
+impl RecordPriority for RecordOrder {
+    type Key = (i64, i32);
+
+    // Sort key for a record, ties are broken by part ID so results are stable
+    fn key(&self, part_id: i32, start_time: i64, end_time: i64) -> (i64, i32) {
+        let primary = match self {
+            RecordOrder::PartId => part_id as i64,
+            RecordOrder::StartTime => start_time,
+            // Bitwise not reverses the order without overflowing
+            RecordOrder::EndTimeDesc => !end_time,
+        };
+
+        (primary, part_id)
+    }
+}
+
+/// Collects the first `limit` matching records in a `RecordOrder`
+///
+/// Every match is seen, so the results do not depend on segment layout.  See
+/// `BoundedRecordCollector`.
+pub type OrderedRecordCollector = BoundedRecordCollector<RecordOrder>;
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::{
+        collectors::column_cache::ColumnCache,
+        test_utils::{build_multi_segment_test_index, build_test_schema},
+    };
+
+    use super::*;
+
+    fn part_ids(results: &[OrderedRecord]) -> Vec<i32> {
+        results.iter().map(|r| r.part_id).collect()
+    }
+
+    #[test]
+    fn test_ordered_by_part_id() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector = OrderedRecordCollector::new(RecordOrder::PartId, 5, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(part_ids(&results), vec![0, 1, 2, 3, 4]);
+        assert_eq!(results[2].record.start_time, 200);
+        assert_eq!(results[2].record.end_time, 250);
+    }
+
+    #[test]
+    fn test_ordered_by_start_time() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = OrderedRecordCollector::new(RecordOrder::StartTime, 10, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(part_ids(&results), vec![1, 10]);
+    }
+
+    #[test]
+    fn test_ordered_by_end_time_desc() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector = OrderedRecordCollector::new(RecordOrder::EndTimeDesc, 3, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(part_ids(&results), vec![29, 28, 27]);
+    }
+
+    #[test]
+    fn test_ordered_zero_limit() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = OrderedRecordCollector::new(RecordOrder::PartId, 0, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert!(results.is_empty());
+    }
+}
@@ -6,7 +6,8 @@ use crate::field_constants;
 use tantivy::{
     collector::{Collector, SegmentCollector},
     columnar::Column,
-    TantivyError,
+    schema::IndexRecordOption,
+    DocSet, Searcher, TantivyError, Term, TERMINATED,
 };
 
 use crate::collectors::column_cache::ColumnCache;
@@ -105,6 +106,55 @@ impl SegmentCollector for PartIdSegmentCollector {
     }
 }
 
+/// Look up the part ID for each of many part keys
+///
+/// Part keys are looked up directly in each segment's term dictionary, and
+/// part IDs read from the segment's column, so this avoids building and
+/// caching a query per key.  Results line up with the input, with -1 for part
+/// keys that are not in the index.
+pub fn part_ids_from_part_keys(
+    searcher: &Searcher,
+    column_cache: &ColumnCache,
+    part_keys: &[&[u8]],
+) -> tantivy::Result<Vec<i32>> {
+    let part_key_field = searcher.schema().get_field(field_constants::PART_KEY)?;
+
+    let mut results = vec![-1; part_keys.len()];
+    for segment in searcher.segment_readers() {
+        let column: Column<i64> = column_cache
+            .get_column(segment, field_constants::PART_ID)?
+            .ok_or_else(|| TantivyError::FieldNotFound(field_constants::PART_ID.to_string()))?;
+        let inverted_index = segment.inverted_index(part_key_field)?;
+
+        for (result, part_key) in results.iter_mut().zip(part_keys.iter()) {
+            if *result != -1 {
+                continue;
+            }
+
+            let term = Term::from_field_bytes(part_key_field, part_key);
+            let Some(mut postings) =
+                inverted_index.read_postings(&term, IndexRecordOption::Basic)?
+            else {
+                continue;
+            };
+
+            let mut doc = postings.doc();
+            while doc != TERMINATED {
+                if !segment.is_deleted(doc) {
+                    if let Some(part_id) = column.first(doc) {
+                        *result = part_id as i32;
+                        break;
+                    }
+                }
+
+                doc = postings.advance();
+            }
+        }
+    }
+
+    Ok(results)
+}
+
 #[cfg(test)]
 mod tests {
     use hashbrown::HashSet;
@@ -150,4 +200,19 @@ mod tests {
         // Which doc matches first is non deterministic, just check length
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_part_ids_from_part_keys() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let results = part_ids_from_part_keys(
+            &index.searcher,
+            &cache,
+            &[b"BB".as_slice(), b"CC".as_slice(), b"AA".as_slice()],
+        )
+        .expect("Should succeed");
+
+        assert_eq!(results, vec![10, -1, 1]);
+    }
 }
@@ -0,0 +1,182 @@
+//! Collector for part IDs along with the docs holding their part keys
+
+use std::cmp::min;
+
+use crate::field_constants;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::Column,
+    DocAddress, TantivyError,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+
+use super::limited_collector::{
+    LimitCounterOptionExt, LimitResult, LimitedCollector, LimitedSegmentCollector,
+};
+
+/// Collects part IDs and doc addresses, ready to load part keys with
+/// `resolve_part_keys`
+pub struct PartIdKeyCollector {
+    limit: usize,
+    column_cache: ColumnCache,
+}
+
+impl PartIdKeyCollector {
+    pub fn new(limit: usize, column_cache: ColumnCache) -> Self {
+        Self {
+            limit,
+            column_cache,
+        }
+    }
+}
+
+impl LimitedCollector for PartIdKeyCollector {
+    fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl Collector for PartIdKeyCollector {
+    type Fruit = Vec<(DocAddress, i32)>;
+
+    type Child = PartIdKeySegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<PartIdKeySegmentCollector> {
+        let column: Column<i64> = self
+            .column_cache
+            .get_column(segment, field_constants::PART_ID)?
+            .ok_or_else(|| TantivyError::FieldNotFound(field_constants::PART_ID.to_string()))?;
+
+        Ok(PartIdKeySegmentCollector {
+            segment_ord: segment_local_id,
+            column,
+            docs: Vec::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<(DocAddress, i32)>>,
+    ) -> tantivy::Result<Vec<(DocAddress, i32)>> {
+        let len: usize = min(segment_fruits.iter().map(|x| x.len()).sum(), self.limit);
+
+        let mut result = Vec::with_capacity(len);
+        for docs in segment_fruits {
+            result.extend(docs.into_iter().take(self.limit - result.len()));
+        }
+
+        Ok(result)
+    }
+}
+
+pub struct PartIdKeySegmentCollector {
+    segment_ord: u32,
+    column: Column<i64>,
+    docs: Vec<(DocAddress, i32)>,
+}
+
+impl LimitedSegmentCollector for PartIdKeySegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        mut limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        if let Some(val) = self.column.first(doc) {
+            self.docs
+                .push((DocAddress::new(self.segment_ord, doc), val as i32));
+            limiter.increment()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for PartIdKeySegmentCollector {
+    type Fruit = Vec<(DocAddress, i32)>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.docs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::query::AllQuery;
+
+    use crate::{
+        collectors::part_key_collector::resolve_part_keys,
+        test_utils::{build_multi_segment_test_index, build_test_schema},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_part_id_key_collector() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = PartIdKeyCollector::new(usize::MAX, cache.clone());
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        let mut results =
+            resolve_part_keys(&index.searcher, &cache, results).expect("Should succeed");
+        results.sort();
+
+        assert_eq!(results, vec![(1, vec![0x41, 0x41]), (10, vec![0x42, 0x42])]);
+    }
+
+    #[test]
+    fn test_part_id_key_collector_multi_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector = PartIdKeyCollector::new(usize::MAX, cache.clone());
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        let results = resolve_part_keys(&index.searcher, &cache, results).expect("Should succeed");
+
+        let mut part_ids: Vec<_> = results.iter().map(|(id, _)| *id).collect();
+        part_ids.sort();
+
+        assert_eq!(part_ids, (0..30).collect::<Vec<_>>());
+        assert!(results.iter().all(|(_, key)| key == &[0x41, 0x41]));
+    }
+
+    #[test]
+    fn test_part_id_key_collector_with_limit() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = PartIdKeyCollector::new(1, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        // Which doc matches first is non deterministic, just check length
+        assert_eq!(results.len(), 1);
+    }
+}
@@ -1,11 +1,21 @@
 //! Collector for part key binary data
 
+use std::io;
+
 use tantivy::{
     collector::{Collector, SegmentCollector},
-    schema::OwnedValue,
-    DocAddress, Searcher, TantivyDocument, TantivyError,
+    columnar::BytesColumn,
+    schema::{
+        document::{DeserializeError, DocumentDeserialize, DocumentDeserializer},
+        Field, OwnedValue,
+    },
+    store::StoreReader,
+    DocAddress, DocId, Searcher, SegmentOrdinal, TantivyDocument, TantivyError,
 };
 
+use tantivy_common::{BinarySerializable, VInt};
+
+use crate::collectors::column_cache::ColumnCache;
 use crate::field_constants::PART_KEY;
 
 use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
@@ -20,17 +30,167 @@ impl UnresolvedPartKey {
         let doc_data = searcher.doc::<TantivyDocument>(self.0)?;
         let part_key_field = searcher.schema().get_field(PART_KEY)?;
 
-        let Some(OwnedValue::Bytes(part_key)) = doc_data
-            .into_iter()
-            .filter(|x| x.field == part_key_field)
-            .map(|x| x.value)
-            .next()
-        else {
-            return Err(TantivyError::FieldNotFound(PART_KEY.to_string()));
+        part_key_from_doc(doc_data, part_key_field)
+    }
+}
+
+/// Load part keys for many docs at once
+///
+/// Docs are read in address order.  Segments with a part key fast field read
+/// it from the column.  Otherwise one store reader is opened per segment, so
+/// each doc store block is decompressed once no matter how many docs it holds,
+/// and only the part key is deserialized.  Results are returned in address
+/// order, paired with the value given for each doc.
+pub fn resolve_part_keys<T>(
+    searcher: &Searcher,
+    column_cache: &ColumnCache,
+    mut docs: Vec<(DocAddress, T)>,
+) -> Result<Vec<(T, Vec<u8>)>, TantivyError> {
+    docs.sort_by_key(|(doc, _)| *doc);
+
+    let part_key_field = searcher.schema().get_field(PART_KEY)?;
+
+    let mut results = Vec::with_capacity(docs.len());
+    let mut source: Option<(SegmentOrdinal, PartKeySource)> = None;
+    for (doc, value) in docs.into_iter() {
+        if source.as_ref().map(|(ord, _)| *ord) != Some(doc.segment_ord) {
+            let segment = searcher.segment_reader(doc.segment_ord);
+            let segment_source = match column_cache.get_bytes_column(segment, PART_KEY)? {
+                Some(column) => PartKeySource::Column(column),
+                None => PartKeySource::Store(segment.get_store_reader(1)?, part_key_field),
+            };
+
+            source = Some((doc.segment_ord, segment_source));
+        }
+
+        let Some((_, source)) = &source else {
+            continue;
+        };
+
+        results.push((value, source.part_key(doc.doc_id)?));
+    }
+
+    Ok(results)
+}
+
+// Where to read part keys from for a segment
+enum PartKeySource {
+    Column(BytesColumn),
+    Store(StoreReader, Field),
+}
+
+impl PartKeySource {
+    fn part_key(&self, doc: DocId) -> Result<Vec<u8>, TantivyError> {
+        match self {
+            Self::Column(column) => {
+                let mut part_key = vec![];
+                if let Some(ord) = column.term_ords(doc).next() {
+                    column.ord_to_bytes(ord, &mut part_key)?;
+                    Ok(part_key)
+                } else {
+                    Err(TantivyError::FieldNotFound(PART_KEY.to_string()))
+                }
+            }
+            Self::Store(reader, part_key_field) => {
+                let doc_bytes = reader.get_document_bytes(doc)?;
+                if let Some(part_key) = find_stored_bytes(doc_bytes.as_slice(), *part_key_field)? {
+                    return Ok(part_key);
+                }
+
+                // A value that can't be skipped came first, decode the doc instead.  The field
+                // to keep isn't known at deserialize time, so pull it out after.
+                let doc = reader.get::<PartKeyDocument>(doc)?;
+                doc.0
+                    .into_iter()
+                    .find(|(field, _)| field == part_key_field)
+                    .map(|(_, part_key)| part_key)
+                    .ok_or_else(|| TantivyError::FieldNotFound(PART_KEY.to_string()))
+            }
+        }
+    }
+}
+
+// Doc store value type codes.  These are part of tantivy's on disk doc store
+// format, so they can't change without breaking existing indexes.
+const TEXT_CODE: u8 = 0;
+const U64_CODE: u8 = 1;
+const I64_CODE: u8 = 2;
+const HIERARCHICAL_FACET_CODE: u8 = 3;
+const BYTES_CODE: u8 = 4;
+const DATE_CODE: u8 = 5;
+const F64_CODE: u8 = 6;
+const BOOL_CODE: u8 = 9;
+const IP_CODE: u8 = 10;
+const NULL_CODE: u8 = 11;
+
+// Find a bytes value in a serialized stored doc, skipping the values before
+// it without decoding them.  Returns none if the field isn't found or a value
+// that can't be skipped cheaply, such as an object, comes first.
+fn find_stored_bytes(mut doc: &[u8], field: Field) -> io::Result<Option<Vec<u8>>> {
+    let field_count = VInt::deserialize(&mut doc)?.val();
+
+    for _ in 0..field_count {
+        let doc_field = Field::from_field_id(u32::deserialize(&mut doc)?);
+        let type_code = u8::deserialize(&mut doc)?;
+
+        let skip = match type_code {
+            TEXT_CODE | HIERARCHICAL_FACET_CODE | BYTES_CODE => {
+                let len = VInt::deserialize(&mut doc)?.val() as usize;
+                if type_code == BYTES_CODE && doc_field == field {
+                    return doc
+                        .get(..len)
+                        .map(|bytes| Some(bytes.to_vec()))
+                        .ok_or_else(|| io::ErrorKind::UnexpectedEof.into());
+                }
+
+                len
+            }
+            U64_CODE | I64_CODE | F64_CODE | DATE_CODE => 8,
+            BOOL_CODE => 1,
+            IP_CODE => 16,
+            NULL_CODE => 0,
+            _ => return Ok(None),
         };
 
-        Ok(part_key)
+        doc = doc
+            .get(skip..)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
     }
+
+    Ok(None)
+}
+
+// Stored document that keeps only bytes values, skipping the cost of building
+// a full TantivyDocument
+struct PartKeyDocument(Vec<(Field, Vec<u8>)>);
+
+impl DocumentDeserialize for PartKeyDocument {
+    fn deserialize<'de, D>(mut deserializer: D) -> Result<Self, DeserializeError>
+    where
+        D: DocumentDeserializer<'de>,
+    {
+        let mut values = Vec::with_capacity(1);
+        while let Some((field, value)) = deserializer.next_field::<OwnedValue>()? {
+            if let OwnedValue::Bytes(bytes) = value {
+                values.push((field, bytes));
+            }
+        }
+
+        Ok(Self(values))
+    }
+}
+
+fn part_key_from_doc(doc: TantivyDocument, part_key_field: Field) -> Result<Vec<u8>, TantivyError> {
+    let Some(OwnedValue::Bytes(part_key)) = doc
+        .into_iter()
+        .filter(|x| x.field == part_key_field)
+        .map(|x| x.value)
+        .next()
+    else {
+        return Err(TantivyError::FieldNotFound(PART_KEY.to_string()));
+    };
+
+    Ok(part_key)
 }
 
 impl PartKeyCollector {
@@ -114,8 +274,8 @@ impl SegmentCollector for PartKeySegmentCollector {
 mod tests {
     use tantivy::{
         query::{EmptyQuery, TermQuery},
-        schema::IndexRecordOption,
-        Term,
+        schema::{IndexRecordOption, SchemaBuilder, STORED},
+        Index, Term,
     };
 
     use crate::test_utils::{build_test_schema, COL1_NAME};
@@ -159,4 +319,77 @@ mod tests {
         // Which doc matches first is non deterministic, just check length
         assert_eq!(results.map(|r| r.resolve(&index.searcher).unwrap()), None);
     }
+
+    #[test]
+    fn test_part_key_sources_match() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+        let reader = index.searcher.segment_readers().first().unwrap();
+        let part_key_field = index.schema.get_field(PART_KEY).unwrap();
+
+        // The test schema has a part key fast field, also check the doc store path
+        let column = PartKeySource::Column(
+            column_cache
+                .get_bytes_column(reader, PART_KEY)
+                .unwrap()
+                .unwrap(),
+        );
+        let store = PartKeySource::Store(reader.get_store_reader(1).unwrap(), part_key_field);
+
+        for doc in 0..reader.max_doc() {
+            assert_eq!(
+                column.part_key(doc).expect("Should succeed"),
+                store.part_key(doc).expect("Should succeed")
+            );
+        }
+
+        assert_eq!(store.part_key(0).expect("Should succeed"), vec![0x41, 0x41]);
+    }
+
+    #[test]
+    fn test_find_stored_bytes() {
+        let mut builder = SchemaBuilder::new();
+        let text = builder.add_text_field("text", STORED);
+        let number = builder.add_i64_field("number", STORED);
+        let flag = builder.add_bool_field("flag", STORED);
+        let json = builder.add_json_field("json", STORED);
+        let part_key = builder.add_bytes_field(PART_KEY, STORED);
+        let schema = builder.build();
+
+        let index = Index::create_in_ram(schema);
+        let mut writer = index.writer::<TantivyDocument>(15_000_000).unwrap();
+
+        // Scalar values before the part key are skipped
+        let mut doc = TantivyDocument::new();
+        doc.add_text(text, "label value");
+        doc.add_i64(number, 42);
+        doc.add_bool(flag, true);
+        doc.add_bytes(part_key, vec![1, 2, 3]);
+        writer.add_document(doc).unwrap();
+
+        // An object can't be skipped cheaply
+        let mut doc = TantivyDocument::new();
+        doc.add_object(json, [("a".to_string(), OwnedValue::from("b"))].into());
+        doc.add_bytes(part_key, vec![4, 5]);
+        writer.add_document(doc).unwrap();
+
+        writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let store = searcher.segment_reader(0).get_store_reader(1).unwrap();
+
+        let find = |doc, field| {
+            let bytes = store.get_document_bytes(doc).unwrap();
+            find_stored_bytes(bytes.as_slice(), field).expect("Should succeed")
+        };
+
+        assert_eq!(find(0, part_key), Some(vec![1, 2, 3]));
+        assert_eq!(find(0, text), None);
+        assert_eq!(find(1, part_key), None);
+
+        // Either way the store source finds the part key
+        let source = PartKeySource::Store(store, part_key);
+        assert_eq!(source.part_key(0).expect("Should succeed"), vec![1, 2, 3]);
+        assert_eq!(source.part_key(1).expect("Should succeed"), vec![4, 5]);
+    }
 }
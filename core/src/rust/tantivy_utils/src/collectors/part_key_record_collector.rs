@@ -4,6 +4,7 @@ use std::cmp::min;
 use std::mem::size_of;
 
 use crate::collectors::column_cache::ColumnCache;
+use crate::collectors::part_key_collector::resolve_part_keys;
 use crate::field_constants::{END_TIME, PART_KEY, START_TIME};
 use tantivy::schema::OwnedValue;
 use tantivy::{
@@ -26,7 +27,7 @@ pub struct UnresolvedPartKeyRecord {
 }
 
 impl UnresolvedPartKeyRecord {
-    pub fn resolve(self, searcher: &Searcher) -> Result<PartKeyRecord, TantivyError> {
+    pub fn resolve(&self, searcher: &Searcher) -> Result<PartKeyRecord, TantivyError> {
         let doc_data = searcher.doc::<TantivyDocument>(self.doc_id)?;
         let part_key_field = searcher.schema().get_field(PART_KEY)?;
 
@@ -47,6 +48,34 @@ impl UnresolvedPartKeyRecord {
     }
 }
 
+/// Resolve many records at once, keeping the order they were given in
+///
+/// This reads each doc store block once, see `resolve_part_keys`.
+pub fn resolve_part_key_records(
+    searcher: &Searcher,
+    column_cache: &ColumnCache,
+    records: &[UnresolvedPartKeyRecord],
+) -> Result<Vec<PartKeyRecord>, TantivyError> {
+    let docs = records
+        .iter()
+        .enumerate()
+        .map(|(idx, r)| (r.doc_id, (idx, r.start_time, r.end_time)))
+        .collect();
+
+    // Keys come back in doc address order, restore the caller's order
+    let mut resolved = resolve_part_keys(searcher, column_cache, docs)?;
+    resolved.sort_unstable_by_key(|((idx, _, _), _)| *idx);
+
+    Ok(resolved
+        .into_iter()
+        .map(|((_, start_time, end_time), part_key)| PartKeyRecord {
+            part_key,
+            start_time,
+            end_time,
+        })
+        .collect())
+}
+
 /// Records returned from queries
 #[derive(Debug, PartialEq, Hash, PartialOrd, Eq)]
 pub struct PartKeyRecord {
@@ -193,7 +222,7 @@ mod tests {
 
     use tantivy::query::AllQuery;
 
-    use crate::test_utils::build_test_schema;
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
 
     use super::*;
 
@@ -250,6 +279,36 @@ mod tests {
         assert_eq!(results.len(), 1);
     }
 
+    #[test]
+    fn test_resolve_part_key_records() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        let collector = PartKeyRecordCollector::new(usize::MAX, column_cache.clone());
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        // Resolve in reverse doc order to check the input order is kept
+        let mut results = results;
+        results.sort_by_key(|r| std::cmp::Reverse(r.doc_id));
+        let expected_start_times: Vec<_> = results.iter().map(|r| r.start_time).collect();
+
+        let resolved = resolve_part_key_records(&index.searcher, &column_cache, &results)
+            .expect("Should succeed");
+
+        let mut start_times: Vec<_> = resolved.iter().map(|r| r.start_time).collect();
+        assert_eq!(start_times, expected_start_times);
+
+        start_times.sort();
+        assert_eq!(start_times, (0..30).map(|i| i * 100).collect::<Vec<_>>());
+        assert!(resolved
+            .iter()
+            .all(|r| r.part_key == [0x41, 0x41] && r.end_time == r.start_time + 50));
+    }
+
     #[test]
     fn test_part_key_record_serialize() {
         let record = PartKeyRecord {
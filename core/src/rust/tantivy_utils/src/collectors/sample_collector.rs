@@ -0,0 +1,145 @@
+//! Collector for a random sample of matching records
+
+use tantivy::collector::Collector;
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::collectors::part_key_record_collector::UnresolvedPartKeyRecord;
+
+use super::{
+    bounded_record_collector::{
+        BoundedRecordCollector, BoundedRecordSegmentCollector, RecordPriority,
+    },
+    limited_collector::LimitedCollector,
+};
+
+/// Collects a uniform random sample of up to `sample_size` matching records
+///
+/// This is reservoir sampling by random priority - each record gets a priority
+/// from hashing its part ID with the seed, and the lowest priorities are kept.
+/// Segments keep their own reservoirs which merge by taking the lowest overall,
+/// so the same seed returns the same sample regardless of segment layout or
+/// parallelism.
+pub struct SampleCollector {
+    inner: BoundedRecordCollector<SamplePriority>,
+}
+
+impl SampleCollector {
+    pub fn new(sample_size: usize, seed: u64, column_cache: ColumnCache) -> Self {
+        Self {
+            inner: BoundedRecordCollector::new(SamplePriority { seed }, sample_size, column_cache),
+        }
+    }
+}
+
+impl LimitedCollector for SampleCollector {
+    fn limit(&self) -> usize {
+        // Every match must be seen for the sample to be uniform
+        self.inner.limit()
+    }
+}
+
+impl Collector for SampleCollector {
+    type Fruit = Vec<UnresolvedPartKeyRecord>;
+
+    type Child = BoundedRecordSegmentCollector<SamplePriority>;
+
+    fn for_segment(
+        &self,
+        segment_local_id: tantivy::SegmentOrdinal,
+        segment: &tantivy::SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        self.inner.for_segment(segment_local_id, segment)
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<Self::Child as tantivy::collector::SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<Vec<UnresolvedPartKeyRecord>> {
+        Ok(self
+            .inner
+            .merge_fruits(segment_fruits)?
+            .into_iter()
+            .map(|r| r.record)
+            .collect())
+    }
+}
+
+/// Random priority of a record, from its part ID and the sample seed
+#[derive(Clone, Copy)]
+pub struct SamplePriority {
+    seed: u64,
+}
+
+impl RecordPriority for SamplePriority {
+    // Ties are broken by part ID so results are stable
+    type Key = (u64, i32);
+
+    fn key(&self, part_id: i32, _start_time: i64, _end_time: i64) -> (u64, i32) {
+        (mix(self.seed ^ mix(part_id as i64 as u64)), part_id)
+    }
+}
+
+// SplitMix64 finalizer, spreads nearby inputs across the whole range
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashSet;
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::build_multi_segment_test_index;
+
+    use super::*;
+
+    // Start times are unique per doc in the test index, so use them as an ID
+    fn sample(seed: u64, sample_size: usize) -> Vec<i64> {
+        let index = build_multi_segment_test_index(3, 10);
+        let collector = SampleCollector::new(sample_size, seed, ColumnCache::default());
+
+        index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed")
+            .into_iter()
+            .map(|r| r.start_time)
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_size() {
+        let results = sample(1, 5);
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results.iter().collect::<HashSet<_>>().len(), 5);
+    }
+
+    #[test]
+    fn test_sample_seed() {
+        // Same seed gives the same sample, different seeds differ
+        assert_eq!(sample(1, 5), sample(1, 5));
+        assert_ne!(sample(1, 5), sample(2, 5));
+    }
+
+    #[test]
+    fn test_sample_larger_than_matches() {
+        let mut results = sample(1, 100);
+        results.sort();
+
+        assert_eq!(results, (0..30).map(|i| i * 100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_sample_zero() {
+        assert!(sample(1, 0).is_empty());
+    }
+}
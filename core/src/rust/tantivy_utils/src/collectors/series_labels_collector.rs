@@ -0,0 +1,280 @@
+//! Collector for the values of selected labels of each matching series
+
+use std::cmp::min;
+
+use hashbrown::HashMap;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::{Column, StrColumn},
+    schema::Field,
+    SegmentReader, TantivyError,
+};
+
+use crate::collectors::column_cache::ColumnCache;
+use crate::field_constants;
+
+use super::limited_collector::{
+    LimitCounterOptionExt, LimitResult, LimitedCollector, LimitedSegmentCollector,
+};
+
+/// Requested label values of one series
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct SeriesLabels {
+    pub part_id: i32,
+    /// Values in the order the labels were requested, empty if missing
+    pub values: Vec<String>,
+}
+
+/// Collects the part ID and the values of a list of labels for each matching doc
+///
+/// Values are read from string columns, so neither the doc store nor the
+/// part key is needed.
+pub struct SeriesLabelsCollector {
+    labels: Vec<String>,
+    default_field: Option<Field>,
+    limit: usize,
+    column_cache: ColumnCache,
+}
+
+impl SeriesLabelsCollector {
+    pub fn new(
+        labels: Vec<String>,
+        default_field: Option<Field>,
+        limit: usize,
+        column_cache: ColumnCache,
+    ) -> Self {
+        Self {
+            labels,
+            default_field,
+            limit,
+            column_cache,
+        }
+    }
+}
+
+impl LimitedCollector for SeriesLabelsCollector {
+    fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl Collector for SeriesLabelsCollector {
+    type Fruit = Vec<SeriesLabels>;
+
+    type Child = SeriesLabelsSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: tantivy::SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<SeriesLabelsSegmentCollector> {
+        let part_id_column: Column<i64> = self
+            .column_cache
+            .get_column(segment, field_constants::PART_ID)?
+            .ok_or_else(|| TantivyError::FieldNotFound(field_constants::PART_ID.to_string()))?;
+
+        let columns = self
+            .labels
+            .iter()
+            .map(|label| {
+                self.column_cache
+                    .get_label_column(segment, label, self.default_field)
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+
+        Ok(SeriesLabelsSegmentCollector {
+            part_id_column,
+            columns,
+            part_ids: vec![],
+            ords: vec![],
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<Vec<SeriesLabels>>,
+    ) -> tantivy::Result<Vec<SeriesLabels>> {
+        let len: usize = min(segment_fruits.iter().map(|x| x.len()).sum(), self.limit);
+
+        let mut result = Vec::with_capacity(len);
+        for series in segment_fruits {
+            result.extend(series.into_iter().take(self.limit - result.len()));
+        }
+
+        Ok(result)
+    }
+}
+
+// Missing values get an ord past the end of any dictionary
+const MISSING_ORD: u64 = u64::MAX;
+
+pub struct SeriesLabelsSegmentCollector {
+    part_id_column: Column<i64>,
+    columns: Vec<Option<StrColumn>>,
+    part_ids: Vec<i32>,
+    // Value ords of each doc, one per label
+    ords: Vec<u64>,
+}
+
+impl LimitedSegmentCollector for SeriesLabelsSegmentCollector {
+    fn collect_with_limiter(
+        &mut self,
+        doc: tantivy::DocId,
+        _score: tantivy::Score,
+        mut limiter: Option<&mut super::limited_collector::LimitCounter>,
+    ) -> LimitResult {
+        let Some(part_id) = self.part_id_column.first(doc) else {
+            return Ok(());
+        };
+
+        self.part_ids.push(part_id as i32);
+        for column in self.columns.iter() {
+            let ord = column
+                .as_ref()
+                .and_then(|c| c.term_ords(doc).next())
+                .unwrap_or(MISSING_ORD);
+
+            self.ords.push(ord);
+        }
+
+        limiter.increment()?;
+
+        Ok(())
+    }
+}
+
+impl SegmentCollector for SeriesLabelsSegmentCollector {
+    type Fruit = Vec<SeriesLabels>;
+
+    fn collect(&mut self, doc: tantivy::DocId, score: tantivy::Score) {
+        let _ = self.collect_with_limiter(doc, score, None);
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        // Series often share values, so look up each ord once per label
+        let mut values: Vec<HashMap<u64, String>> = vec![HashMap::new(); self.columns.len()];
+        let label_count = self.columns.len();
+
+        self.part_ids
+            .into_iter()
+            .enumerate()
+            .map(|(idx, part_id)| {
+                let ords = self.ords.iter().skip(idx * label_count).take(label_count);
+
+                let values = ords
+                    .zip(self.columns.iter())
+                    .zip(values.iter_mut())
+                    .map(|((ord, column), values)| {
+                        values
+                            .entry(*ord)
+                            .or_insert_with(|| {
+                                let mut value = String::new();
+                                if let Some(column) = column {
+                                    if *ord != MISSING_ORD {
+                                        let _ = column.ord_to_str(*ord, &mut value);
+                                    }
+                                }
+
+                                value
+                            })
+                            .clone()
+                    })
+                    .collect();
+
+                SeriesLabels { part_id, values }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashSet;
+    use tantivy::query::AllQuery;
+
+    use crate::test_utils::{build_multi_segment_test_index, build_test_schema};
+
+    use super::*;
+
+    fn series(part_id: i32, values: &[&str]) -> SeriesLabels {
+        SeriesLabels {
+            part_id,
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_series_labels_collector() {
+        let index = build_test_schema();
+        let cache = ColumnCache::default();
+
+        let collector = SeriesLabelsCollector::new(
+            vec![
+                "col1".to_string(),
+                "f2".to_string(),
+                "not_found".to_string(),
+            ],
+            Some(index.json_field),
+            usize::MAX,
+            cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results.into_iter().collect::<HashSet<_>>(),
+            [
+                series(1, &["ABC", "value2", ""]),
+                series(10, &["DEF", "othervalue2", ""])
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_series_labels_collector_multi_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector = SeriesLabelsCollector::new(
+            vec!["col1".to_string()],
+            Some(index.json_field),
+            usize::MAX,
+            cache,
+        );
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 30);
+        for result in results {
+            assert_eq!(result.values, vec![format!("V{}", result.part_id % 3)]);
+        }
+    }
+
+    #[test]
+    fn test_series_labels_collector_with_limit() {
+        let index = build_multi_segment_test_index(3, 10);
+        let cache = ColumnCache::default();
+
+        let collector =
+            SeriesLabelsCollector::new(vec!["col1".to_string()], Some(index.json_field), 5, cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 5);
+    }
+}
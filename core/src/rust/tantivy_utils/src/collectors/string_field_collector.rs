@@ -1,30 +1,54 @@
 //! Collector to string values from a document
 
 use core::str;
-use std::collections::hash_map::Entry;
+use std::{
+    collections::{hash_map::Entry, BTreeMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use hashbrown::HashMap;
 use nohash_hasher::IntMap;
 use tantivy::{
     collector::{Collector, SegmentCollector},
     columnar::StrColumn,
-    TantivyError,
+    schema::IndexRecordOption,
+    DocSet, TantivyError, TERMINATED,
 };
 
-use crate::collectors::column_cache::ColumnCache;
+use crate::{collectors::column_cache::ColumnCache, query::JSON_PREFIX_SEPARATOR};
 
 use super::{
     index_collector::IndexCollector,
     limited_collector::{
         LimitCounter, LimitCounterOptionExt, LimitResult, LimitedCollector, LimitedSegmentCollector,
     },
+    value_matcher::ValueMatcher,
 };
 
+/// Order of values returned by a `StringFieldCollector`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueOrder {
+    /// Highest count first, ties broken by value
+    #[default]
+    CountDesc,
+    /// Ascending by value
+    Lexicographic,
+}
+
 pub struct StringFieldCollector<'a> {
     field: &'a str,
     limit: usize,
     term_limit: usize,
     column_cache: ColumnCache,
+    order: ValueOrder,
+    after: Option<&'a str>,
+    matcher: Option<&'a ValueMatcher>,
+    exact_counts: bool,
+    // Values were dropped by the term limit or by keeping the smallest values
+    truncated: Arc<AtomicBool>,
 }
 
 impl<'a> StringFieldCollector<'a> {
@@ -34,13 +58,69 @@ impl<'a> StringFieldCollector<'a> {
             limit,
             term_limit,
             column_cache,
+            order: ValueOrder::CountDesc,
+            after: None,
+            matcher: None,
+            exact_counts: false,
+            truncated: Arc::new(AtomicBool::new(false)),
         }
     }
+
+    /// Set the order values are returned in
+    ///
+    /// In lexicographic order the smallest values are kept rather than stopping
+    /// at the first `limit` values found, so results are exact and can be paged.
+    pub fn with_order(mut self, order: ValueOrder) -> Self {
+        self.order = order;
+
+        self
+    }
+
+    /// Only return values that sort after `after`, used as a paging cursor
+    pub fn with_after(mut self, after: Option<&'a str>) -> Self {
+        self.after = after;
+
+        self
+    }
+
+    /// Only return values accepted by `matcher`
+    ///
+    /// Values are filtered before they count against any limit.
+    pub fn with_matcher(mut self, matcher: Option<&'a ValueMatcher>) -> Self {
+        self.matcher = matcher;
+
+        self
+    }
+
+    /// Count only live docs when collecting over the index
+    ///
+    /// By default counts come from the term dictionary, which still includes
+    /// deleted docs until their segment is merged.  Exact counts walk the
+    /// postings of each term in segments with deletes, so they are slower.
+    pub fn with_exact_counts(mut self, exact_counts: bool) -> Self {
+        self.exact_counts = exact_counts;
+
+        self
+    }
+
+    // Keep values that sort after the cursor, if any
+    fn is_after_cursor(&self, value: &str) -> bool {
+        self.after.map_or(true, |after| value > after)
+    }
 }
 
 impl<'a> LimitedCollector for StringFieldCollector<'a> {
     fn limit(&self) -> usize {
-        self.limit
+        match self.order {
+            ValueOrder::CountDesc => self.limit,
+            // Every segment keeps its own smallest values, so there is no
+            // point where collection can stop early
+            ValueOrder::Lexicographic => usize::MAX,
+        }
+    }
+
+    fn truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
     }
 }
 
@@ -56,10 +136,36 @@ impl<'a> Collector for StringFieldCollector<'a> {
     ) -> tantivy::Result<StringFieldSegmentCollector> {
         let column = self.column_cache.get_str_column(segment, self.field)?;
 
+        // First ord that sorts after the cursor
+        let min_ord = match (&column, self.after) {
+            (Some(column), Some(after)) => {
+                let mut stream = column.dictionary().range().gt(after).into_stream()?;
+
+                if stream.advance() {
+                    stream.term_ord()
+                } else {
+                    column.dictionary().num_terms() as u64
+                }
+            }
+            _ => 0,
+        };
+
+        let docs = match self.order {
+            ValueOrder::CountDesc => OrdCounts::All(IntMap::default()),
+            ValueOrder::Lexicographic => {
+                OrdCounts::Smallest(BTreeMap::new(), self.limit.min(self.term_limit))
+            }
+        };
+
         Ok(StringFieldSegmentCollector {
             column,
-            docs: IntMap::default(),
+            docs,
+            min_ord,
             term_limit: self.term_limit,
+            matcher: self.matcher.cloned(),
+            ord_matches: IntMap::default(),
+            value_buffer: Vec::new(),
+            truncated: self.truncated.clone(),
         })
     }
 
@@ -84,7 +190,20 @@ impl<'a> Collector for StringFieldCollector<'a> {
         }
 
         let mut results: Vec<_> = results.drain().collect();
-        results.sort_by(|(_, count_a), (_, count_b)| count_b.cmp(count_a));
+        match self.order {
+            ValueOrder::CountDesc => {
+                results.sort_by(|(value_a, count_a), (value_b, count_b)| {
+                    count_b.cmp(count_a).then_with(|| value_a.cmp(value_b))
+                });
+            }
+            ValueOrder::Lexicographic => {
+                results.sort_by(|(value_a, _), (value_b, _)| value_a.cmp(value_b));
+            }
+        }
+
+        if results.len() > self.limit {
+            self.truncated.store(true, Ordering::Relaxed);
+        }
 
         let results = results.into_iter().take(self.limit).collect();
 
@@ -92,10 +211,40 @@ impl<'a> Collector for StringFieldCollector<'a> {
     }
 }
 
+// Counts of each term ord seen in a segment
+enum OrdCounts {
+    // Every ord seen, up to the term limit
+    All(IntMap<u64, u64>),
+    // Only the smallest ords seen, up to the given count
+    Smallest(BTreeMap<u64, u64>, usize),
+}
+
 pub struct StringFieldSegmentCollector {
     column: Option<StrColumn>,
-    docs: IntMap<u64, u64>,
+    docs: OrdCounts,
+    min_ord: u64,
     term_limit: usize,
+    matcher: Option<ValueMatcher>,
+    // Matcher results for ords already seen
+    ord_matches: IntMap<u64, bool>,
+    // Scratch space for decoding values to match
+    value_buffer: Vec<u8>,
+    truncated: Arc<AtomicBool>,
+}
+
+// Check if the value for an ord passes the matcher, remembering the result
+fn ord_matches(
+    matcher: &ValueMatcher,
+    cache: &mut IntMap<u64, bool>,
+    column: &StrColumn,
+    ord: u64,
+    buffer: &mut Vec<u8>,
+) -> bool {
+    *cache.entry(ord).or_insert_with(|| {
+        buffer.clear();
+
+        matches!(column.ord_to_bytes(ord, buffer), Ok(true)) && matcher.matches(buffer)
+    })
 }
 
 impl LimitedSegmentCollector for StringFieldSegmentCollector {
@@ -107,19 +256,62 @@ impl LimitedSegmentCollector for StringFieldSegmentCollector {
     ) -> LimitResult {
         if let Some(column) = &self.column {
             for ord in column.term_ords(doc) {
-                if self.docs.len() >= self.term_limit {
-                    break;
+                if ord < self.min_ord {
+                    continue;
                 }
 
-                // We wait to translate to strings later to reduce
-                // the number of times we have to copy the data out
-                // to one per ord
-                let entry = self.docs.entry(ord);
-                let increment = matches!(entry, Entry::Vacant(_));
-                *entry.or_insert(0) += 1;
+                if let Some(matcher) = &self.matcher {
+                    if !ord_matches(
+                        matcher,
+                        &mut self.ord_matches,
+                        column,
+                        ord,
+                        &mut self.value_buffer,
+                    ) {
+                        continue;
+                    }
+                }
 
-                if increment {
-                    limiter.increment()?;
+                match &mut self.docs {
+                    OrdCounts::All(docs) => {
+                        if docs.len() >= self.term_limit {
+                            self.truncated.store(true, Ordering::Relaxed);
+                            break;
+                        }
+
+                        // We wait to translate to strings later to reduce
+                        // the number of times we have to copy the data out
+                        // to one per ord
+                        let entry = docs.entry(ord);
+                        let increment = matches!(entry, Entry::Vacant(_));
+                        *entry.or_insert(0) += 1;
+
+                        if increment {
+                            limiter.increment()?;
+                        }
+                    }
+                    OrdCounts::Smallest(docs, max_len) => {
+                        if let Some(count) = docs.get_mut(&ord) {
+                            *count += 1;
+                            continue;
+                        }
+
+                        if docs.len() >= *max_len {
+                            // Ords are in value order, so only keep this one if it is
+                            // smaller than the largest kept.  An ord dropped here can
+                            // never be kept later, so kept counts are exact.
+                            self.truncated.store(true, Ordering::Relaxed);
+
+                            match docs.last_key_value() {
+                                Some((largest, _)) if ord < *largest => {
+                                    docs.pop_last();
+                                }
+                                _ => continue,
+                            }
+                        }
+
+                        docs.insert(ord, 1);
+                    }
                 }
             }
         }
@@ -136,20 +328,30 @@ impl SegmentCollector for StringFieldSegmentCollector {
     }
 
     fn harvest(self) -> Self::Fruit {
-        self.docs
-            .into_iter()
-            .map(|(ord, count)| {
-                if let Some(column) = &self.column {
-                    let mut value = String::new();
-                    let _ = column.ord_to_str(ord, &mut value);
-
-                    (value, count)
-                } else {
-                    (String::new(), count)
-                }
-            })
-            .filter(|(k, _v)| !k.is_empty())
-            .collect()
+        let column = self.column;
+        let to_value = |(ord, count): (u64, u64)| {
+            if let Some(column) = &column {
+                let mut value = String::new();
+                let _ = column.ord_to_str(ord, &mut value);
+
+                (value, count)
+            } else {
+                (String::new(), count)
+            }
+        };
+
+        match self.docs {
+            OrdCounts::All(docs) => docs
+                .into_iter()
+                .map(to_value)
+                .filter(|(k, _v)| !k.is_empty())
+                .collect(),
+            OrdCounts::Smallest(docs, _) => docs
+                .into_iter()
+                .map(to_value)
+                .filter(|(k, _v)| !k.is_empty())
+                .collect(),
+        }
     }
 }
 
@@ -169,19 +371,46 @@ impl<'a> IndexCollector for StringFieldCollector<'a> {
             HashMap::new()
         };
 
-        if limiter.at_limit() {
-            return Ok(ret);
-        }
-
-        let index_reader = reader.inverted_index(field)?;
-        let mut index_reader = index_reader.terms().range();
+        let inverted_index = reader.inverted_index(field)?;
+        let mut index_reader = inverted_index.terms().range();
         if !prefix.is_empty() {
             // Only look at prefix range
             index_reader = index_reader.ge(format!("{}\0", prefix));
             index_reader = index_reader.lt(format!("{}\u{001}", prefix));
         }
+
+        let term = |value: &str| {
+            if prefix.is_empty() {
+                value.to_string()
+            } else {
+                format!("{}{}{}", prefix, JSON_PREFIX_SEPARATOR, value)
+            }
+        };
+
+        // Start at the cursor or the matcher prefix, whichever is later, so
+        // prefix matches only walk terms with the prefix
+        let match_prefix = match self.matcher {
+            Some(ValueMatcher::Prefix(match_prefix)) => Some(match_prefix.as_str()),
+            _ => None,
+        };
+        index_reader = match (self.after, match_prefix) {
+            (Some(after), Some(match_prefix)) if match_prefix > after => {
+                index_reader.ge(term(match_prefix))
+            }
+            (Some(after), _) => index_reader.gt(term(after)),
+            (None, Some(match_prefix)) => index_reader.ge(term(match_prefix)),
+            (None, None) => index_reader,
+        };
+
+        // Terms are walked in order, so in lexicographic order each segment
+        // can stop once it has its smallest values
+        let segment_limit = match self.order {
+            ValueOrder::CountDesc => usize::MAX,
+            ValueOrder::Lexicographic => self.limit.min(self.term_limit),
+        };
+
         let mut index_reader = index_reader.into_stream()?;
-        while !limiter.at_limit() && index_reader.advance() {
+        while index_reader.advance() {
             let mut key_bytes = index_reader.key();
             if !prefix.is_empty() {
                 // Skip prefix
@@ -194,10 +423,61 @@ impl<'a> IndexCollector for StringFieldCollector<'a> {
             let key = str::from_utf8(key_bytes)
                 .map_err(|e| TantivyError::InternalError(e.to_string()))?;
 
+            if !self.is_after_cursor(key) {
+                continue;
+            }
+
+            if let Some(matcher) = self.matcher {
+                if !matcher.matches(key_bytes) {
+                    if match_prefix.is_some() {
+                        // Terms are sorted, so no later term has the prefix
+                        break;
+                    }
+
+                    continue;
+                }
+            }
+
+            let term_info = index_reader.value();
+            let count = match (self.exact_counts, reader.alive_bitset()) {
+                (true, Some(alive_bitset)) => {
+                    let mut postings = inverted_index
+                        .read_postings_from_terminfo(term_info, IndexRecordOption::Basic)?;
+
+                    let mut count = 0u64;
+                    let mut doc = postings.doc();
+                    while doc != TERMINATED {
+                        count += alive_bitset.is_alive(doc) as u64;
+                        doc = postings.advance();
+                    }
+
+                    count
+                }
+                // Without deletes the doc freq is exact
+                _ => term_info.doc_freq as u64,
+            };
+
+            if count == 0 {
+                // Every doc with this value is deleted
+                continue;
+            }
+
+            if limiter.at_limit() {
+                // A value past the limit
+                limiter.mark_exceeded();
+                break;
+            }
+
+            if ret.len() >= segment_limit {
+                // This segment has more values than it keeps
+                self.truncated.store(true, Ordering::Relaxed);
+                break;
+            }
+
             // capture it
-            ret.insert(key.to_string(), index_reader.value().doc_freq as u64);
+            ret.insert(key.to_string(), count);
 
-            // No need to check error, the check at the top of the while will handle it
+            // No need to check error, the limit is checked before capturing the next value
             let _ = limiter.increment();
         }
 
@@ -212,8 +492,12 @@ mod tests {
     use tantivy::query::AllQuery;
 
     use crate::{
-        collectors::index_collector::collect_from_index,
-        test_utils::{build_test_schema, COL1_NAME, JSON_COL_NAME},
+        collectors::index_collector::{collect_from_index, collect_from_index_with_stats},
+        query::search_stats::SearchStats,
+        test_utils::{
+            build_multi_segment_test_index, build_multi_segment_test_index_with_deletes,
+            build_test_index_with_col1_values, build_test_schema, COL1_NAME, JSON_COL_NAME,
+        },
     };
 
     use super::*;
@@ -367,4 +651,322 @@ mod tests {
         // Which doc matches first is non deterministic, just check length
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_string_field_collector_lexicographic() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let collector = StringFieldCollector::new(COL1_NAME, 1, usize::MAX, column_cache.clone())
+            .with_order(ValueOrder::Lexicographic);
+        let query = AllQuery;
+
+        let results = index
+            .searcher
+            .search(&query, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results, vec![("ABC".to_string(), 1)]);
+
+        // Next page
+        let collector = StringFieldCollector::new(COL1_NAME, 1, usize::MAX, column_cache.clone())
+            .with_order(ValueOrder::Lexicographic)
+            .with_after(Some("ABC"));
+
+        let results = index
+            .searcher
+            .search(&query, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results, vec![("DEF".to_string(), 1)]);
+
+        // Past the end
+        let collector = StringFieldCollector::new(COL1_NAME, 1, usize::MAX, column_cache)
+            .with_order(ValueOrder::Lexicographic)
+            .with_after(Some("DEF"));
+
+        let results = index
+            .searcher
+            .search(&query, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results, vec![]);
+    }
+
+    #[test]
+    fn test_string_field_index_collector_lexicographic() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let col_name = format!("{}.{}", JSON_COL_NAME, "f1");
+        let collector = StringFieldCollector::new(&col_name, 1, usize::MAX, column_cache.clone())
+            .with_order(ValueOrder::Lexicographic);
+
+        let results = collect_from_index(&index.searcher, collector).expect("Should succeed");
+
+        assert_eq!(results, vec![("othervalue".to_string(), 1)]);
+
+        let collector = StringFieldCollector::new(&col_name, 1, usize::MAX, column_cache)
+            .with_order(ValueOrder::Lexicographic)
+            .with_after(Some("othervalue"));
+
+        let results = collect_from_index(&index.searcher, collector).expect("Should succeed");
+
+        assert_eq!(results, vec![("value".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_string_field_collector_lexicographic_paging() {
+        // 30 docs over 3 segments, with values V0, V1 and V2 10 times each
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+        let query = AllQuery;
+
+        let mut after = None;
+        let mut pages = vec![];
+        loop {
+            let collector =
+                StringFieldCollector::new(COL1_NAME, 2, usize::MAX, column_cache.clone())
+                    .with_order(ValueOrder::Lexicographic)
+                    .with_after(after.as_deref());
+
+            let results = index
+                .searcher
+                .search(&query, &collector)
+                .expect("Should succeed");
+
+            if results.is_empty() {
+                break;
+            }
+
+            after = results.last().map(|(value, _)| value.clone());
+            pages.push(results);
+        }
+
+        assert_eq!(
+            pages,
+            vec![
+                vec![("V0".to_string(), 10), ("V1".to_string(), 10)],
+                vec![("V2".to_string(), 10)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_field_collector_lexicographic_truncated() {
+        // 30 docs over 3 segments, with values V0, V1 and V2 10 times each
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        for (limit, truncated) in [(2, true), (3, false)] {
+            let collector =
+                StringFieldCollector::new(COL1_NAME, limit, usize::MAX, column_cache.clone())
+                    .with_order(ValueOrder::Lexicographic);
+
+            let results = index
+                .searcher
+                .search(&AllQuery, &collector)
+                .expect("Should succeed");
+
+            assert_eq!(results.len(), limit);
+            assert_eq!(collector.truncated(), truncated);
+
+            let collector =
+                StringFieldCollector::new(COL1_NAME, limit, usize::MAX, column_cache.clone())
+                    .with_order(ValueOrder::Lexicographic);
+
+            let mut stats = SearchStats::default();
+            let results = collect_from_index_with_stats(&index.searcher, collector, &mut stats)
+                .expect("Should succeed");
+
+            assert_eq!(results.len(), limit);
+            assert_eq!(stats.limit_hit, truncated);
+        }
+    }
+
+    #[test]
+    fn test_string_field_index_collector_limit_boundary() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        // Two values, so only a limit of one cuts results
+        for (limit, limit_hit) in [(1, true), (2, false)] {
+            let collector =
+                StringFieldCollector::new(COL1_NAME, limit, usize::MAX, column_cache.clone());
+
+            let mut stats = SearchStats::default();
+            let results = collect_from_index_with_stats(&index.searcher, collector, &mut stats)
+                .expect("Should succeed");
+
+            assert_eq!(results.len(), limit);
+            assert_eq!(stats.limit_hit, limit_hit);
+        }
+    }
+
+    #[test]
+    fn test_string_field_collector_count_order() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        // Equal counts are ordered by value
+        let collector = StringFieldCollector::new(COL1_NAME, usize::MAX, usize::MAX, column_cache);
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(
+            results,
+            vec![("ABC".to_string(), 1), ("DEF".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_string_field_collector_matcher() {
+        let index = build_multi_segment_test_index(3, 10);
+        let column_cache = ColumnCache::default();
+
+        for matcher in [
+            ValueMatcher::Prefix("V1".to_string()),
+            ValueMatcher::Substring("1".to_string()),
+            ValueMatcher::regex(".*1").expect("Should succeed"),
+        ] {
+            // Limit of 1 is only reached by matching values
+            let collector =
+                StringFieldCollector::new(COL1_NAME, 1, usize::MAX, column_cache.clone())
+                    .with_matcher(Some(&matcher));
+
+            let results = index
+                .searcher
+                .search(&AllQuery, &collector)
+                .expect("Should succeed");
+
+            assert_eq!(results, vec![("V1".to_string(), 10)], "{:?}", matcher);
+
+            let collector =
+                StringFieldCollector::new(COL1_NAME, 1, usize::MAX, column_cache.clone())
+                    .with_matcher(Some(&matcher));
+
+            let results = collect_from_index(&index.searcher, collector).expect("Should succeed");
+
+            // The index collector stops at the first segment that hits the limit, so
+            // only check the value
+            assert_eq!(results.len(), 1, "{:?}", matcher);
+            assert_eq!(results[0].0, "V1", "{:?}", matcher);
+        }
+    }
+
+    #[test]
+    fn test_string_field_collector_matcher_json() {
+        let index = build_test_schema();
+        let column_cache = ColumnCache::default();
+
+        let col_name = format!("{}.{}", JSON_COL_NAME, "f1");
+        let matcher = ValueMatcher::Prefix("val".to_string());
+
+        let collector =
+            StringFieldCollector::new(&col_name, usize::MAX, usize::MAX, column_cache.clone())
+                .with_matcher(Some(&matcher));
+
+        let results = index
+            .searcher
+            .search(&AllQuery, &collector)
+            .expect("Should succeed");
+
+        assert_eq!(results, vec![("value".to_string(), 1)]);
+
+        let collector = StringFieldCollector::new(&col_name, usize::MAX, usize::MAX, column_cache)
+            .with_matcher(Some(&matcher));
+
+        let results = collect_from_index(&index.searcher, collector).expect("Should succeed");
+
+        assert_eq!(results, vec![("value".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_string_field_index_collector_matcher_term_limit() {
+        let index = build_test_index_with_col1_values((0..10_010).map(|i| format!("v{:05}", i)));
+        let column_cache = ColumnCache::default();
+
+        // Matches all sort after the first 10k terms
+        let term_limit = 10_000;
+        for matcher in [
+            ValueMatcher::Prefix("v1000".to_string()),
+            ValueMatcher::Substring("v1000".to_string()),
+            ValueMatcher::regex("v1000.").expect("Should succeed"),
+        ] {
+            let collector =
+                StringFieldCollector::new(COL1_NAME, term_limit, term_limit, column_cache.clone())
+                    .with_matcher(Some(&matcher));
+
+            let mut results =
+                collect_from_index(&index.searcher, collector).expect("Should succeed");
+            results.sort();
+
+            let expected: Vec<_> = (10_000..10_010)
+                .map(|i| (format!("v{:05}", i), 1))
+                .collect();
+            assert_eq!(results, expected, "{:?}", matcher);
+        }
+
+        // The cursor and the prefix both bound where terms are walked from
+        let matcher = ValueMatcher::Prefix("v1000".to_string());
+        for (after, first) in [("v0", "v10000"), ("v10005", "v10006"), ("v2", "")] {
+            let collector =
+                StringFieldCollector::new(COL1_NAME, 1, term_limit, column_cache.clone())
+                    .with_order(ValueOrder::Lexicographic)
+                    .with_after(Some(after))
+                    .with_matcher(Some(&matcher));
+
+            let results = collect_from_index(&index.searcher, collector).expect("Should succeed");
+
+            assert_eq!(
+                results
+                    .first()
+                    .map(|(value, _)| value.as_str())
+                    .unwrap_or(""),
+                first,
+                "{}",
+                after
+            );
+        }
+    }
+
+    #[test]
+    fn test_string_field_index_collector_exact_counts() {
+        // Deletes V0 three times and V1 once
+        let index = build_multi_segment_test_index_with_deletes(3, 10, &[0, 3, 6, 1]);
+
+        let collect = |exact_counts| {
+            let collector = StringFieldCollector::new(
+                COL1_NAME,
+                usize::MAX,
+                usize::MAX,
+                ColumnCache::default(),
+            )
+            .with_exact_counts(exact_counts);
+
+            collect_from_index(&index.searcher, collector).expect("Should succeed")
+        };
+
+        // Doc freq still includes deleted docs
+        assert_eq!(
+            collect(false),
+            vec![
+                ("V0".to_string(), 10),
+                ("V1".to_string(), 10),
+                ("V2".to_string(), 10)
+            ]
+        );
+
+        assert_eq!(
+            collect(true),
+            vec![
+                ("V2".to_string(), 10),
+                ("V1".to_string(), 9),
+                ("V0".to_string(), 7)
+            ]
+        );
+    }
 }
@@ -1,17 +1,42 @@
 //! Filter collector that applies a start and end time range
 
+use std::sync::Arc;
+
 use tantivy::{
     collector::{Collector, SegmentCollector},
-    columnar::Column,
-    TantivyError,
+    columnar::{Cardinality, Column},
+    SegmentReader, TantivyError,
 };
+use tantivy_common::BitSet;
 
 use crate::collectors::column_cache::ColumnCache;
 use crate::field_constants;
 
 use super::limited_collector::{LimitResult, LimitedCollector, LimitedSegmentCollector};
 
+/// Source of per segment bitsets of docs active within a time range
+///
+/// Implementations are expected to cache results, as the same bucketed
+/// ranges are requested over and over by queries with sliding windows.
+pub trait ActiveDocsSource: Sync {
+    /// Docs in a segment that started at or before `end` and ended at or after `start`
+    ///
+    /// A `start` of 0 or less means no bound on end time, and an `end` of
+    /// `i64::MAX` means no bound on start time, matching `TimeRangeFilter`.
+    fn active_docs(
+        &self,
+        segment: &SegmentReader,
+        start: i64,
+        end: i64,
+    ) -> tantivy::Result<Arc<BitSet>>;
+}
+
 /// Filters results on a time range
+///
+/// If an `ActiveDocsSource` is configured the range is widened and narrowed to
+/// bucket boundaries.  Docs active anywhere in the widened range but not active
+/// across the whole narrowed range are in an edge bucket, and only those are
+/// checked against the exact range.
 pub struct TimeRangeFilter<'a, T>
 where
     T: LimitedCollector,
@@ -25,6 +50,8 @@ where
     end: i64,
     /// Column cache
     column_cache: ColumnCache,
+    /// Source for bucketed active docs, and the bucket size
+    active_docs: Option<(&'a dyn ActiveDocsSource, i64)>,
 }
 
 impl<'a, T> TimeRangeFilter<'a, T>
@@ -38,6 +65,49 @@ where
             start,
             end,
             column_cache,
+            active_docs: None,
+        }
+    }
+
+    /// Use bucketed active doc bitsets from `source` to avoid per doc time checks
+    pub fn with_active_docs(mut self, source: &'a dyn ActiveDocsSource, bucket_size: i64) -> Self {
+        if bucket_size > 0 {
+            self.active_docs = Some((source, bucket_size));
+        }
+
+        self
+    }
+}
+
+/// Time ranges to fetch active docs for
+#[derive(Debug, PartialEq, Eq)]
+struct BucketedRange {
+    /// Every doc active in this range matches the exact range
+    inner: (i64, i64),
+    /// Every doc matching the exact range is active in this range
+    outer: (i64, i64),
+}
+
+impl BucketedRange {
+    fn new(start: i64, end: i64, bucket_size: i64) -> Self {
+        let floor = |time: i64| time - time.rem_euclid(bucket_size);
+
+        // Unbounded sides stay unbounded
+        let (inner_start, outer_start) = if start > 0 {
+            (floor(start).saturating_add(bucket_size), floor(start))
+        } else {
+            (start, start)
+        };
+
+        let (inner_end, outer_end) = if end < i64::MAX {
+            (floor(end), floor(end).saturating_add(bucket_size - 1))
+        } else {
+            (end, end)
+        };
+
+        Self {
+            inner: (inner_start, inner_end),
+            outer: (outer_start, outer_end),
         }
     }
 }
@@ -50,6 +120,44 @@ where
     fn limit(&self) -> usize {
         self.collector.limit()
     }
+
+    fn truncated(&self) -> bool {
+        self.collector.truncated()
+    }
+
+    fn skip_segment(&self, reader: &SegmentReader) -> Result<bool, TantivyError> {
+        // Docs missing a time are treated as unbounded, so only columns with a
+        // value for every doc give usable segment bounds
+        if self.end < i64::MAX {
+            let start_column: Option<Column<i64>> = self
+                .column_cache
+                .get_column(reader, field_constants::START_TIME)?;
+
+            if let Some(start_column) = start_column {
+                if start_column.get_cardinality() == Cardinality::Full
+                    && start_column.min_value() > self.end
+                {
+                    return Ok(true);
+                }
+            }
+        }
+
+        if self.start > 0 {
+            let end_column: Option<Column<i64>> = self
+                .column_cache
+                .get_column(reader, field_constants::END_TIME)?;
+
+            if let Some(end_column) = end_column {
+                if end_column.get_cardinality() == Cardinality::Full
+                    && end_column.max_value() < self.start
+                {
+                    return Ok(true);
+                }
+            }
+        }
+
+        self.collector.skip_segment(reader)
+    }
 }
 
 impl<'a, T> Collector for TimeRangeFilter<'a, T>
@@ -90,11 +198,24 @@ where
             None
         };
 
+        let active_docs = match self.active_docs {
+            Some((source, bucket_size)) if self.start > 0 || self.end < i64::MAX => {
+                let range = BucketedRange::new(self.start, self.end, bucket_size);
+
+                Some(ActiveDocs {
+                    inner: source.active_docs(segment, range.inner.0, range.inner.1)?,
+                    outer: source.active_docs(segment, range.outer.0, range.outer.1)?,
+                })
+            }
+            _ => None,
+        };
+
         let collector = self.collector.for_segment(segment_local_id, segment)?;
 
         Ok(TimeRangeFilterSegmentCollector::<T::Child> {
             start_column,
             end_column,
+            active_docs,
             start_time: self.start,
             end_time: self.end,
             collector,
@@ -113,11 +234,18 @@ where
     }
 }
 
+// Bucketed active docs for a segment
+struct ActiveDocs {
+    inner: Arc<BitSet>,
+    outer: Arc<BitSet>,
+}
+
 pub struct TimeRangeFilterSegmentCollector<T>
 where
     T: LimitedSegmentCollector,
 {
     collector: T,
+    active_docs: Option<ActiveDocs>,
     start_column: Option<Column<i64>>,
     end_column: Option<Column<i64>>,
     start_time: i64,
@@ -134,6 +262,16 @@ where
         score: tantivy::Score,
         limiter: Option<&mut super::limited_collector::LimitCounter>,
     ) -> LimitResult {
+        if let Some(active_docs) = &self.active_docs {
+            if active_docs.inner.contains(doc) {
+                return self.collector.collect_with_limiter(doc, score, limiter);
+            }
+
+            if !active_docs.outer.contains(doc) {
+                return Ok(());
+            }
+        }
+
         if let Some(start_column) = &self.start_column {
             let doc_start = start_column.first(doc).unwrap_or(0);
             if doc_start > self.end_time {
@@ -169,9 +307,14 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use tantivy::{collector::Count, query::AllQuery};
 
-    use crate::{collectors::limited_collector::UnlimitedCollector, test_utils::build_test_schema};
+    use crate::{
+        collectors::limited_collector::UnlimitedCollector,
+        test_utils::{build_multi_segment_test_index, build_test_schema},
+    };
 
     use super::*;
 
@@ -259,4 +402,148 @@ mod tests {
         // Should match no docs - out of range
         assert_eq!(result, 0);
     }
+
+    // Computes active docs directly from the time columns
+    #[derive(Default)]
+    struct TestActiveDocsSource {
+        column_cache: ColumnCache,
+        calls: AtomicUsize,
+    }
+
+    impl ActiveDocsSource for TestActiveDocsSource {
+        fn active_docs(
+            &self,
+            segment: &SegmentReader,
+            start: i64,
+            end: i64,
+        ) -> tantivy::Result<Arc<BitSet>> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+
+            let start_column: Column<i64> = self
+                .column_cache
+                .get_column(segment, field_constants::START_TIME)?
+                .unwrap();
+            let end_column: Column<i64> = self
+                .column_cache
+                .get_column(segment, field_constants::END_TIME)?
+                .unwrap();
+
+            let mut docs = BitSet::with_max_value(segment.max_doc());
+            for doc in 0..segment.max_doc() {
+                let doc_start = start_column.first(doc).unwrap_or(0);
+                let doc_end = end_column.first(doc).unwrap_or(i64::MAX);
+
+                if (end == i64::MAX || doc_start <= end) && (start <= 0 || doc_end >= start) {
+                    docs.insert(doc);
+                }
+            }
+
+            Ok(Arc::new(docs))
+        }
+    }
+
+    #[test]
+    fn test_time_filter_skip_segment() {
+        let index = build_multi_segment_test_index(3, 10);
+        let collector = UnlimitedCollector::new(Count);
+
+        // Segments hold docs ending by 950, 1950 and 2950 and starting at 0, 1000 and 2000
+        for (start, end, expected) in [
+            (0, i64::MAX, [false, false, false]),
+            (1000, i64::MAX, [true, false, false]),
+            (2951, i64::MAX, [true, true, true]),
+            (0, 999, [false, true, true]),
+            (1000, 1999, [true, false, true]),
+        ] {
+            let filter = TimeRangeFilter::new(&collector, start, end, ColumnCache::default());
+
+            // Segment readers are not in commit order, so order them by start time
+            let mut skipped: Vec<_> = index
+                .searcher
+                .segment_readers()
+                .iter()
+                .map(|r| {
+                    let start_column: Column<i64> = ColumnCache::default()
+                        .get_column(r, field_constants::START_TIME)
+                        .unwrap()
+                        .unwrap();
+
+                    (
+                        start_column.min_value(),
+                        filter.skip_segment(r).expect("Should succeed"),
+                    )
+                })
+                .collect();
+            skipped.sort();
+
+            let skipped: Vec<_> = skipped.into_iter().map(|(_, skip)| skip).collect();
+
+            assert_eq!(skipped, expected, "{} - {}", start, end);
+        }
+    }
+
+    #[test]
+    fn test_bucketed_range() {
+        let range = BucketedRange::new(1050, 1250, 100);
+
+        assert_eq!(range.inner, (1100, 1200));
+        assert_eq!(range.outer, (1000, 1299));
+
+        let range = BucketedRange::new(0, 1250, 100);
+
+        assert_eq!(range.inner, (0, 1200));
+        assert_eq!(range.outer, (0, 1299));
+
+        let range = BucketedRange::new(1050, i64::MAX, 100);
+
+        assert_eq!(range.inner, (1100, i64::MAX));
+        assert_eq!(range.outer, (1000, i64::MAX));
+    }
+
+    #[test]
+    fn test_time_filter_active_docs() {
+        let index = build_multi_segment_test_index(3, 10);
+        let source = TestActiveDocsSource::default();
+
+        for (start, end) in [
+            (0, i64::MAX),
+            (0, 1234),
+            (1234, i64::MAX),
+            (1049, 1051),
+            (1050, 1050),
+            (101, 2899),
+            (5000, 6000),
+        ] {
+            let collector = UnlimitedCollector::new(Count);
+
+            let exact = TimeRangeFilter::new(&collector, start, end, ColumnCache::default());
+            let expected = index
+                .searcher
+                .search(&AllQuery, &exact)
+                .expect("Should succeed");
+
+            for bucket_size in [1, 7, 100, 1000, 1_000_000] {
+                let bucketed = TimeRangeFilter::new(&collector, start, end, ColumnCache::default())
+                    .with_active_docs(&source, bucket_size);
+                let result = index
+                    .searcher
+                    .search(&AllQuery, &bucketed)
+                    .expect("Should succeed");
+
+                assert_eq!(result, expected, "{} - {} in {}", start, end, bucket_size);
+            }
+        }
+
+        // Unbounded ranges never need active docs
+        let calls = source.calls.load(Ordering::Relaxed);
+        let collector = UnlimitedCollector::new(Count);
+        let bucketed = TimeRangeFilter::new(&collector, 0, i64::MAX, ColumnCache::default())
+            .with_active_docs(&source, 100);
+        index
+            .searcher
+            .search(&AllQuery, &bucketed)
+            .expect("Should succeed");
+
+        assert_eq!(source.calls.load(Ordering::Relaxed), calls);
+    }
 }
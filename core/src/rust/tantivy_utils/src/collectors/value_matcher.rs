@@ -0,0 +1,95 @@
+//! Matchers for filtering collected string values
+
+use std::sync::Arc;
+
+use tantivy::TantivyError;
+use tantivy_fst::{Automaton, Regex};
+
+use crate::query::range_aware_regex::create_regex;
+
+/// Filter for string values, applied by collectors before limits are counted
+#[derive(Debug, Clone)]
+pub enum ValueMatcher {
+    /// Values starting with a prefix
+    Prefix(String),
+    /// Values containing a substring
+    Substring(String),
+    /// Values fully matching a regex, using the same engine as `RangeAwareRegexQuery`
+    Regex(Arc<Regex>),
+}
+
+impl ValueMatcher {
+    /// Build a regex matcher from a pattern
+    pub fn regex(pattern: &str) -> Result<Self, TantivyError> {
+        Ok(Self::Regex(Arc::new(create_regex(pattern)?)))
+    }
+
+    /// Returns true if the value passes the matcher
+    pub fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            Self::Prefix(prefix) => value.starts_with(prefix.as_bytes()),
+            Self::Substring(substring) => {
+                let substring = substring.as_bytes();
+
+                substring.is_empty() || value.windows(substring.len()).any(|w| w == substring)
+            }
+            Self::Regex(regex) => {
+                let mut state = regex.start();
+                for byte in value {
+                    if !regex.can_match(&state) {
+                        return false;
+                    }
+
+                    state = regex.accept(&state, *byte);
+                }
+
+                regex.is_match(&state)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix() {
+        let matcher = ValueMatcher::Prefix("api-".to_string());
+
+        assert!(matcher.matches(b"api-server"));
+        assert!(matcher.matches(b"api-"));
+        assert!(!matcher.matches(b"api"));
+        assert!(!matcher.matches(b"web-api-server"));
+    }
+
+    #[test]
+    fn test_substring() {
+        let matcher = ValueMatcher::Substring("canary".to_string());
+
+        assert!(matcher.matches(b"canary"));
+        assert!(matcher.matches(b"web-canary-1"));
+        assert!(!matcher.matches(b"canar"));
+
+        let matcher = ValueMatcher::Substring(String::new());
+
+        assert!(matcher.matches(b""));
+        assert!(matcher.matches(b"abc"));
+    }
+
+    #[test]
+    fn test_regex() {
+        let matcher = ValueMatcher::regex("api-[0-9]+").expect("Should succeed");
+
+        assert!(matcher.matches(b"api-1"));
+        assert!(matcher.matches(b"api-123"));
+        // Regex must match the full value
+        assert!(!matcher.matches(b"api-1x"));
+        assert!(!matcher.matches(b"xapi-1"));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(ValueMatcher::regex("api-(").is_err());
+    }
+}
@@ -4,6 +4,15 @@ pub fn facet_field_name(name: &str) -> String {
     format!("{}{}", FACET_FIELD_PREFIX, name)
 }
 
+/// Fields that are never returned as label names, matching `ignoreIndexNames`
+/// and `FACET_FIELD_PREFIX` in PartKeyIndex.scala
+pub fn is_internal_field(name: &str) -> bool {
+    matches!(
+        name,
+        DOCUMENT_ID | PART_ID | PART_KEY | START_TIME | END_TIME
+    ) || name.starts_with(FACET_FIELD_PREFIX)
+}
+
 // These should be kept in sync with the constants in  PartKeyIndex.scala
 // as they're fields that can be directly queried via incoming filters
 // or fields that are filtered out of label lists
@@ -4,6 +4,7 @@ pub mod bitset_weight;
 pub mod cache;
 pub mod prefix_query;
 pub mod range_aware_regex;
+pub mod search_stats;
 pub mod shared_doc_set;
 
 pub const JSON_PREFIX_SEPARATOR: &str = "\0s";
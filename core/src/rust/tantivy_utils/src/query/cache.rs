@@ -1,21 +1,27 @@
 //! Cached query support
 
-use std::{hash::Hash, sync::Arc};
+use std::{
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
 
 use quick_cache::{sync::Cache, Equivalent, Weighter};
 use tantivy::{
     collector::SegmentCollector,
     query::{EnableScoring, Query, Weight},
     schema::{Field, Schema},
-    Searcher, SegmentId, TantivyError,
+    Executor, Searcher, SegmentId, SegmentReader, TantivyError,
 };
 use tantivy_common::BitSet;
 
 use crate::collectors::limited_collector::{
-    LimitCounter, LimitedCollector, LimitedSegmentCollector,
+    has_alive_doc, LimitCounter, LimitedCollector, LimitedSegmentCollector, ParallelLimit,
 };
 
-use super::bitset_weight::BitSetWeight;
+use super::{bitset_weight::BitSetWeight, search_stats::SearchStats};
 
 /// Cache for query results
 ///
@@ -26,13 +32,125 @@ use super::bitset_weight::BitSetWeight;
 /// The bitfield size in bits will be equal to the number of documents in the
 /// segment.  We keep the BitSet in an Arc to reduce data copies as once created
 /// the field is immutable.
+///
+/// Entries are stored in a `QueryCachePool`.  By default each cache gets its own
+/// pool, but a pool can be shared across many caches so they share one budget.
+/// Hit / miss and size stats are always tracked per cache.
 pub struct QueryCache<QueryType, WeighterType>
 where
     QueryType: CachableQuery,
     WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>> + Default + Clone,
 {
-    // Cache of query -> docs
-    cache: Cache<(SegmentId, QueryType), Arc<BitSet>, WeighterType>,
+    // Storage for cache entries, possibly shared with other caches
+    pool: Arc<QueryCachePool<QueryType, WeighterType>>,
+    // ID of this cache in the pool
+    owner_id: u64,
+    // Stats for this cache only
+    stats: Arc<QueryCacheStats>,
+    // Weak references to every inserted entry, used for introspection and
+    // cleanup.  Evicted entries are pruned lazily.
+    entries: Mutex<Vec<Weak<CachedDocs<QueryType>>>>,
+    // Weighter used to report per entry weights
+    weighter: WeighterType,
+}
+
+// Key of an item in the pool - owner ID and then segment ID / query
+type PoolKey<QueryType> = (u64, (SegmentId, QueryType));
+
+/// Storage for query cache entries with a single weight budget
+///
+/// Every `QueryCache` using the pool has a unique owner ID that is part of the
+/// key, so entries from different indexes never collide but all count against
+/// the same capacity and compete for eviction.
+pub struct QueryCachePool<QueryType, WeighterType>
+where
+    QueryType: CachableQuery,
+    WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>> + Default + Clone,
+{
+    cache: Cache<PoolKey<QueryType>, Arc<CachedDocs<QueryType>>, EntryWeighter<WeighterType>>,
+    next_owner_id: AtomicU64,
+}
+
+impl<QueryType, WeighterType> QueryCachePool<QueryType, WeighterType>
+where
+    QueryType: CachableQuery,
+    WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>> + Default + Clone,
+{
+    pub fn new(estimated_items_count: u64, weight_capacity: u64) -> Self {
+        Self {
+            cache: Cache::with_weighter(
+                estimated_items_count as usize,
+                weight_capacity,
+                EntryWeighter::default(),
+            ),
+            next_owner_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Gets the size of all entries in the pool, in bytes
+    pub fn size(&self) -> u64 {
+        self.cache.weight()
+    }
+
+    /// Gets the max size of the pool, in bytes
+    pub fn capacity(&self) -> u64 {
+        self.cache.capacity()
+    }
+}
+
+/// Per cache stats
+#[derive(Default)]
+struct QueryCacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    weight: AtomicU64,
+    // Entries owned by the cache that are still in the pool
+    live: AtomicU64,
+}
+
+/// A cached set of docs for a segment and query, along with usage stats
+struct CachedDocs<QueryType> {
+    segment_id: SegmentId,
+    query: QueryType,
+    docs: Arc<BitSet>,
+    hits: AtomicU64,
+    // Weight charged to the owning cache's stats, released on drop
+    weight: u64,
+    stats: Arc<QueryCacheStats>,
+}
+
+impl<QueryType> Drop for CachedDocs<QueryType> {
+    fn drop(&mut self) {
+        self.stats.weight.fetch_sub(self.weight, Ordering::Relaxed);
+        self.stats.live.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Adapts a weighter over the raw bitset to the cached entry type
+#[derive(Clone, Default)]
+struct EntryWeighter<WeighterType>(WeighterType);
+
+impl<QueryType, WeighterType> Weighter<PoolKey<QueryType>, Arc<CachedDocs<QueryType>>>
+    for EntryWeighter<WeighterType>
+where
+    WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>>,
+{
+    fn weight(&self, key: &PoolKey<QueryType>, val: &Arc<CachedDocs<QueryType>>) -> u64 {
+        self.0.weight(&key.1, &val.docs)
+    }
+}
+
+/// Point in time information about a single query cache entry
+#[derive(Debug, Clone)]
+pub struct QueryCacheEntryInfo<QueryType> {
+    pub segment_id: SegmentId,
+    pub query: QueryType,
+    /// Number of matching docs in the cached bitset
+    pub cardinality: usize,
+    /// Weight of the entry as computed by the cache weighter
+    pub weight: u64,
+    /// Number of times this entry was served from cache
+    pub hits: u64,
 }
 
 /// Trait for cachable query keys
@@ -70,6 +188,30 @@ where
     }
 }
 
+// Helper to avoid cloning the query just to do a pool lookup
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolQueryKey<'a, QueryType>(u64, CachableQueryKey<'a, QueryType>)
+where
+    QueryType: Clone + PartialEq + Eq;
+
+impl<'a, QueryType> From<PoolQueryKey<'a, QueryType>> for PoolKey<QueryType>
+where
+    QueryType: Clone + PartialEq + Eq,
+{
+    fn from(value: PoolQueryKey<'a, QueryType>) -> Self {
+        (value.0, value.1.into())
+    }
+}
+
+impl<'a, QueryType> Equivalent<PoolKey<QueryType>> for PoolQueryKey<'a, QueryType>
+where
+    QueryType: Clone + PartialEq + Eq,
+{
+    fn equivalent(&self, key: &PoolKey<QueryType>) -> bool {
+        self.0 == key.0 && self.1.equivalent(&key.1)
+    }
+}
+
 // Tuning parameters for query cache
 const DEFAULT_QUERY_CACHE_MAX_SIZE_BYTES: u64 = 50_000_000;
 // Rough estimate of bitset size - 250k docs
@@ -80,23 +222,170 @@ where
     QueryType: CachableQuery,
     WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>> + Default + Clone,
 {
+    /// Create a cache with its own dedicated pool
     pub fn new(estimated_items_count: u64, weight_capacity: u64) -> Self {
+        Self::with_pool(Arc::new(QueryCachePool::new(
+            estimated_items_count,
+            weight_capacity,
+        )))
+    }
+
+    /// Create a cache that stores entries in a possibly shared pool
+    pub fn with_pool(pool: Arc<QueryCachePool<QueryType, WeighterType>>) -> Self {
+        let owner_id = pool.next_owner_id.fetch_add(1, Ordering::Relaxed);
+
         Self {
-            cache: Cache::with_weighter(
-                estimated_items_count as usize,
-                weight_capacity,
-                WeighterType::default(),
-            ),
+            pool,
+            owner_id,
+            stats: Arc::new(QueryCacheStats::default()),
+            entries: Mutex::new(Vec::new()),
+            weighter: WeighterType::default(),
         }
     }
 
     pub fn query_cache_stats(&self) -> (u64, u64) {
-        (self.cache.hits(), self.cache.misses())
+        (
+            self.stats.hits.load(Ordering::Relaxed),
+            self.stats.misses.load(Ordering::Relaxed),
+        )
     }
 
     /// Gets the current cache size, in bytes
+    ///
+    /// For a shared pool this is the size of the entries owned by this cache
     pub fn size(&self) -> u64 {
-        self.cache.weight()
+        self.stats.weight.load(Ordering::Relaxed)
+    }
+
+    /// Gets info on every entry currently in the cache
+    ///
+    /// This walks the entire cache and is meant for debugging and tuning only
+    pub fn entries(&self) -> Vec<QueryCacheEntryInfo<QueryType>> {
+        self.live_entries()
+            .into_iter()
+            .map(|e| {
+                let key = (e.segment_id, e.query.clone());
+
+                QueryCacheEntryInfo {
+                    weight: self.weighter.weight(&key, &e.docs),
+                    segment_id: key.0,
+                    query: key.1,
+                    cardinality: e.docs.len(),
+                    hits: e.hits.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    // Entries owned by this cache that are still present in the pool
+    fn live_entries(&self) -> Vec<Arc<CachedDocs<QueryType>>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        entries.retain(|e| e.strong_count() > 0);
+
+        entries
+            .iter()
+            .filter_map(|e| e.upgrade())
+            .filter(|e| {
+                // An entry can outlive its eviction while a query is still using it
+                let key = PoolQueryKey(self.owner_id, CachableQueryKey(e.segment_id, &e.query));
+                matches!(self.pool.cache.peek(&key), Some(cached) if Arc::ptr_eq(&cached, e))
+            })
+            .collect()
+    }
+
+    fn insert(&self, key: (SegmentId, QueryType), docs: Arc<BitSet>) {
+        let weight = self.weighter.weight(&key, &docs);
+        self.stats.weight.fetch_add(weight, Ordering::Relaxed);
+        self.stats.live.fetch_add(1, Ordering::Relaxed);
+
+        let entry = Arc::new(CachedDocs {
+            segment_id: key.0,
+            query: key.1.clone(),
+            docs,
+            hits: AtomicU64::new(0),
+            weight,
+            stats: self.stats.clone(),
+        });
+
+        let weak = Arc::downgrade(&entry);
+        self.pool.cache.insert((self.owner_id, key), entry);
+
+        // Untracked entries would never be released from a shared pool, so recover from poisoning
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Prune evicted entries once the list has grown well past this cache's live count.
+        // The pool may be shared, so its size says nothing about this list.
+        let live = self.stats.live.load(Ordering::Relaxed) as usize;
+        if entries.len() > (live * 2) + 64 {
+            entries.retain(|e| e.strong_count() > 0);
+        }
+
+        entries.push(weak);
+    }
+
+    // Get cached docs for a segment, updating stats
+    fn get_cached(&self, segment_id: SegmentId, cachable_query: &QueryType) -> Option<Arc<BitSet>> {
+        let cache_key = PoolQueryKey(self.owner_id, CachableQueryKey(segment_id, cachable_query));
+
+        if let Some(entry) = self.pool.cache.get(&cache_key) {
+            self.stats.hits.fetch_add(1, Ordering::Relaxed);
+            entry.hits.fetch_add(1, Ordering::Relaxed);
+
+            Some(entry.docs.clone())
+        } else {
+            self.stats.misses.fetch_add(1, Ordering::Relaxed);
+
+            None
+        }
+    }
+
+    // Run the query on a segment and cache the results if allowed
+    fn load_docs(
+        &self,
+        weight: &dyn Weight,
+        segment_reader: &SegmentReader,
+        cachable_query: &QueryType,
+    ) -> Result<Arc<BitSet>, TantivyError> {
+        let mut bitset = BitSet::with_max_value(segment_reader.max_doc());
+
+        weight.for_each_no_score(segment_reader, &mut |docs| {
+            for doc in docs.iter().cloned() {
+                bitset.insert(doc);
+            }
+        })?;
+
+        let bitset = Arc::new(bitset);
+
+        if cachable_query.should_cache() {
+            self.insert(
+                (segment_reader.segment_id(), cachable_query.clone()),
+                bitset.clone(),
+            );
+        }
+
+        Ok(bitset)
+    }
+
+    /// Get the docs matching a cachable query in a single segment
+    ///
+    /// This is used for helper bitsets, such as time range filters, that are
+    /// looked up while another query is being collected.
+    pub fn segment_docs(
+        &self,
+        schema: &Schema,
+        default_field: Option<Field>,
+        segment_reader: &SegmentReader,
+        cachable_query: &QueryType,
+    ) -> Result<Arc<BitSet>, TantivyError> {
+        if let Some(docs) = self.get_cached(segment_reader.segment_id(), cachable_query) {
+            return Ok(docs);
+        }
+
+        let query = cachable_query.to_query(schema, default_field)?;
+        let weight = query.weight(EnableScoring::disabled_from_schema(schema))?;
+
+        self.load_docs(weight.as_ref(), segment_reader, cachable_query)
     }
 
     /// Execute a cachable query
@@ -108,6 +397,32 @@ where
         cachable_query: QueryType,
         collector: C,
     ) -> Result<C::Fruit, TantivyError>
+    where
+        C: LimitedCollector,
+        C::Child: LimitedSegmentCollector,
+    {
+        let mut stats = SearchStats::default();
+
+        self.search_with_stats(
+            searcher,
+            schema,
+            default_field,
+            cachable_query,
+            collector,
+            &mut stats,
+        )
+    }
+
+    /// Execute a cachable query, recording what the search did in `stats`
+    pub fn search_with_stats<C>(
+        &self,
+        searcher: &Searcher,
+        schema: &Schema,
+        default_field: Option<Field>,
+        cachable_query: QueryType,
+        collector: C,
+        stats: &mut SearchStats,
+    ) -> Result<C::Fruit, TantivyError>
     where
         C: LimitedCollector,
         C::Child: LimitedSegmentCollector,
@@ -122,51 +437,58 @@ where
 
         let mut limiter = LimitCounter::new(collector.limit());
 
-        // Note - the query optimizations here only work for the single threaded querying.  That matches
+        // Build query if needed.  We do this lazily as it may be expensive to parse a regex, for example.
+        // This can give a 2-4x speedup in some cases.
+        let build_weight = || {
+            cachable_query
+                .to_query(schema, default_field)?
+                .weight(scoring)
+        };
+
+        // Note - the lazy query evaluation here only works for single threaded querying.  That matches
         // the pattern FiloDB uses because it will dispatch multiple queries at a time on different threads,
-        // so this results in net improvement anyway.  If we need to change to the multithreaded executor
-        // in the future then the lazy query evaluation code will need some work
+        // so this results in net improvement anyway.  See `search_parallel` for the multithreaded version.
         for (segment_ord, segment_reader) in segment_readers.iter().enumerate() {
-            // Is it cached
-            let cache_key = CachableQueryKey(segment_reader.segment_id(), &cachable_query);
-
-            let docs = if let Some(docs) = self.cache.get(&cache_key) {
-                // Cache hit
-                docs
-            } else {
-                // Build query if needed.  We do this lazily as it may be expensive to parse a regex, for example.
-                // This can give a 2-4x speedup in some cases.
-                let weight = if let Some(weight) = &query_weight {
-                    weight
-                } else {
-                    let query = cachable_query.to_query(schema, default_field)?;
-                    let weight = query.weight(scoring)?;
-
-                    query_weight = Some(weight);
-
-                    // Unwrap is safe here because we just set the value
-                    #[allow(clippy::unwrap_used)]
-                    query_weight.as_ref().unwrap()
-                };
+            // Skip segments the collector can't match before touching the cache or query
+            if collector.skip_segment(segment_reader)? {
+                stats.segments_skipped += 1;
+                continue;
+            }
 
-                // Load bit set
-                let mut bitset = BitSet::with_max_value(segment_reader.max_doc());
+            let cached = self.get_cached(segment_reader.segment_id(), &cachable_query);
 
-                weight.for_each_no_score(segment_reader, &mut |docs| {
-                    for doc in docs.iter().cloned() {
-                        bitset.insert(doc);
+            if limiter.at_limit() {
+                // Earlier segments filled the limit exactly, so any match here is cut.  Only the
+                // first live doc matters, so the segment's docs are not loaded.
+                let mut scorer = match cached {
+                    Some(docs) => BitSetWeight::new(docs).scorer(segment_reader, 1.0)?,
+                    None => {
+                        lazy_weight(&mut query_weight, build_weight)?.scorer(segment_reader, 1.0)?
                     }
-                })?;
-
-                let bitset = Arc::new(bitset);
+                };
 
-                if cachable_query.should_cache() {
-                    self.cache.insert(cache_key.into(), bitset.clone());
+                if has_alive_doc(scorer.as_mut(), segment_reader.alive_bitset()) {
+                    limiter.mark_exceeded();
+                    break;
                 }
 
-                bitset
+                continue;
+            }
+
+            let (docs, from_cache) = match cached {
+                Some(docs) => (docs, true),
+                None => (
+                    self.load_docs(
+                        lazy_weight(&mut query_weight, build_weight)?,
+                        segment_reader,
+                        &cachable_query,
+                    )?,
+                    false,
+                ),
             };
 
+            let examined_before = limiter.examined();
+
             let weight = BitSetWeight::new(docs);
             let results = collector.collect_segment_with_limiter(
                 &weight,
@@ -175,14 +497,212 @@ where
                 &mut limiter,
             )?;
 
+            stats.add_segment(limiter.examined() - examined_before, from_cache);
             fruits.push(results);
 
-            if limiter.at_limit() {
+            if limiter.exceeded() {
                 break;
             }
         }
 
-        collector.merge_fruits(fruits)
+        let fruit = collector.merge_fruits(fruits)?;
+
+        stats.limit_hit = limiter.exceeded() || collector.truncated();
+        stats.segments_after_limit =
+            segment_readers.len() as u32 - stats.segments_read - stats.segments_skipped;
+
+        Ok(fruit)
+    }
+
+    /// Execute a cachable query, evaluating segments in parallel on an executor
+    ///
+    /// All segments are checked against the cache up front so the query weight is
+    /// built at most once and shared by all threads.  Limits are coordinated across
+    /// segments and fruits are merged in segment order, so collectors that truncate
+    /// to their limit when merging return the same results as `search`, as do
+    /// collectors with no limit.  Collectors that stop at a limit without truncating
+    /// in segment order, such as `StringFieldCollector` in count order, can return a
+    /// different subset as segments are collected in a different order.
+    pub fn search_parallel<C>(
+        &self,
+        executor: &Executor,
+        searcher: &Searcher,
+        schema: &Schema,
+        default_field: Option<Field>,
+        cachable_query: QueryType,
+        collector: C,
+    ) -> Result<C::Fruit, TantivyError>
+    where
+        C: LimitedCollector,
+        C::Child: LimitedSegmentCollector,
+        QueryType: Send + Sync,
+        WeighterType: Send + Sync,
+    {
+        let mut stats = SearchStats::default();
+
+        self.search_parallel_with_stats(
+            executor,
+            searcher,
+            schema,
+            default_field,
+            cachable_query,
+            collector,
+            &mut stats,
+        )
+    }
+
+    /// Execute a cachable query in parallel, recording what the search did in `stats`
+    #[allow(clippy::too_many_arguments)]
+    pub fn search_parallel_with_stats<C>(
+        &self,
+        executor: &Executor,
+        searcher: &Searcher,
+        schema: &Schema,
+        default_field: Option<Field>,
+        cachable_query: QueryType,
+        collector: C,
+        stats: &mut SearchStats,
+    ) -> Result<C::Fruit, TantivyError>
+    where
+        C: LimitedCollector,
+        C::Child: LimitedSegmentCollector,
+        QueryType: Send + Sync,
+        WeighterType: Send + Sync,
+    {
+        let segment_readers = searcher.segment_readers();
+
+        // None for segments the collector can skip, otherwise the cached docs if any
+        let cached = segment_readers
+            .iter()
+            .map(|r| {
+                if collector.skip_segment(r)? {
+                    Ok(None)
+                } else {
+                    Ok(Some(self.get_cached(r.segment_id(), &cachable_query)))
+                }
+            })
+            .collect::<Result<Vec<_>, TantivyError>>()?;
+        let skipped = cached.iter().filter(|c| c.is_none()).count() as u32;
+
+        let query_weight = if cached.iter().any(|c| matches!(c, Some(None))) {
+            let scoring = EnableScoring::disabled_from_searcher(searcher);
+            let query = cachable_query.to_query(schema, default_field)?;
+
+            Some(query.weight(scoring)?)
+        } else {
+            None
+        };
+
+        let limit = Arc::new(ParallelLimit::new(collector.limit(), segment_readers.len()));
+
+        let fruits = executor.map(
+            |(segment_ord, (segment_reader, docs))| {
+                let mut limiter = LimitCounter::for_parallel_segment(limit.clone(), segment_ord);
+                let at_limit = limiter.at_limit();
+
+                if at_limit && limit.exceeded() {
+                    // Earlier segments already filled the limit and results are known to be cut
+                    return Ok(None);
+                }
+
+                let Some(docs) = docs else {
+                    return Ok(None);
+                };
+
+                let query_weight = || {
+                    query_weight.as_deref().ok_or_else(|| {
+                        TantivyError::InternalError(
+                            "Query weight not built for uncached segment".to_string(),
+                        )
+                    })
+                };
+
+                if at_limit {
+                    // Earlier segments filled the limit exactly, so any match here is cut.  Only the
+                    // first live doc matters, so the segment's docs are not loaded.
+                    let mut scorer = match docs {
+                        Some(docs) => BitSetWeight::new(docs).scorer(segment_reader, 1.0)?,
+                        None => query_weight()?.scorer(segment_reader, 1.0)?,
+                    };
+
+                    if has_alive_doc(scorer.as_mut(), segment_reader.alive_bitset()) {
+                        limiter.mark_exceeded();
+                    }
+
+                    return Ok(None);
+                }
+
+                let (docs, from_cache) = match docs {
+                    Some(docs) => (docs, true),
+                    None => (
+                        self.load_docs(query_weight()?, segment_reader, &cachable_query)?,
+                        false,
+                    ),
+                };
+
+                let weight = BitSetWeight::new(docs);
+
+                let results = collector.collect_segment_with_limiter(
+                    &weight,
+                    segment_ord as u32,
+                    segment_reader,
+                    &mut limiter,
+                )?;
+
+                Ok(Some((results, limiter.examined(), from_cache)))
+            },
+            segment_readers.iter().zip(cached).enumerate(),
+        )?;
+
+        let mut segment_fruits = Vec::with_capacity(fruits.len());
+        for (results, examined, from_cache) in fruits.into_iter().flatten() {
+            stats.add_segment(examined, from_cache);
+            segment_fruits.push(results);
+        }
+
+        let fruit = collector.merge_fruits(segment_fruits)?;
+
+        stats.limit_hit = limit.exceeded() || collector.truncated();
+        stats.segments_skipped = skipped;
+        stats.segments_after_limit =
+            segment_readers.len() as u32 - stats.segments_read - stats.segments_skipped;
+
+        Ok(fruit)
+    }
+}
+
+// Get a query weight, building it on first use
+fn lazy_weight(
+    weight: &mut Option<Box<dyn Weight>>,
+    build: impl FnOnce() -> Result<Box<dyn Weight>, TantivyError>,
+) -> Result<&dyn Weight, TantivyError> {
+    if weight.is_none() {
+        *weight = Some(build()?);
+    }
+
+    weight
+        .as_deref()
+        .ok_or_else(|| TantivyError::InternalError("Query weight not built".to_string()))
+}
+
+impl<QueryType, WeighterType> Drop for QueryCache<QueryType, WeighterType>
+where
+    QueryType: CachableQuery,
+    WeighterType: Weighter<(SegmentId, QueryType), Arc<BitSet>> + Default + Clone,
+{
+    fn drop(&mut self) {
+        // Release our entries so they don't hold space in a shared pool until evicted
+        if Arc::strong_count(&self.pool) == 1 {
+            return;
+        }
+
+        for entry in self.live_entries() {
+            let key = PoolQueryKey(
+                self.owner_id,
+                CachableQueryKey(entry.segment_id, &entry.query),
+            );
+            self.pool.cache.remove(&key);
+        }
     }
 }
 
@@ -206,9 +726,19 @@ where
 mod tests {
     use std::hash::{DefaultHasher, Hasher};
 
-    use tantivy::query::AllQuery;
+    use tantivy::{collector::Count, query::AllQuery};
 
-    use crate::test_utils::build_test_schema;
+    use crate::{
+        collectors::{
+            column_cache::ColumnCache,
+            limited_collector::UnlimitedCollector,
+            ordered_record_collector::{OrderedRecordCollector, RecordOrder},
+            part_id_collector::PartIdCollector,
+            string_field_collector::{StringFieldCollector, ValueOrder},
+            time_range_filter::TimeRangeFilter,
+        },
+        test_utils::{build_multi_segment_test_index, build_test_schema},
+    };
 
     use super::*;
 
@@ -231,6 +761,15 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Default)]
+    pub struct TestWeighter;
+
+    impl Weighter<(SegmentId, TestQuery), Arc<BitSet>> for TestWeighter {
+        fn weight(&self, _key: &(SegmentId, TestQuery), val: &Arc<BitSet>) -> u64 {
+            val.max_value() as u64
+        }
+    }
+
     #[test]
     fn test_cache_key_equivilance() {
         let index = build_test_schema();
@@ -254,4 +793,531 @@ mod tests {
 
         assert_eq!(key_hash, owned_key_hash);
     }
+
+    #[test]
+    fn test_cache_entries() {
+        let index = build_test_schema();
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+
+        assert!(cache.entries().is_empty());
+
+        for _ in 0..3 {
+            let collector = UnlimitedCollector::new(Count);
+            let count = cache
+                .search(
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    collector,
+                )
+                .expect("Should succeed");
+
+            assert_eq!(count, 2);
+        }
+
+        let entries = cache.entries();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query, TestQuery::Test(1));
+        assert_eq!(entries[0].cardinality, 2);
+        assert_eq!(entries[0].weight, 2);
+        // First search populates, next two hit
+        assert_eq!(entries[0].hits, 2);
+    }
+
+    #[test]
+    fn test_pool_key_equivilance() {
+        let index = build_test_schema();
+        let reader = index.searcher.segment_readers().first().unwrap();
+
+        let query = TestQuery::Test(1234);
+
+        let key = PoolQueryKey(5, CachableQueryKey(reader.segment_id(), &query));
+        let owned_key: PoolKey<TestQuery> = key.clone().into();
+
+        assert!(key.equivalent(&owned_key));
+        assert!(
+            !PoolQueryKey(6, CachableQueryKey(reader.segment_id(), &query)).equivalent(&owned_key)
+        );
+
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let key_hash = hasher.finish();
+
+        let mut hasher = DefaultHasher::new();
+        owned_key.hash(&mut hasher);
+        let owned_key_hash = hasher.finish();
+
+        assert_eq!(key_hash, owned_key_hash);
+    }
+
+    #[test]
+    fn test_shared_pool() {
+        let index = build_test_schema();
+        let pool: Arc<QueryCachePool<TestQuery, TestWeighter>> =
+            Arc::new(QueryCachePool::new(10, 1000));
+
+        let cache1 = QueryCache::with_pool(pool.clone());
+        let cache2 = QueryCache::with_pool(pool.clone());
+
+        let search = |cache: &QueryCache<TestQuery, TestWeighter>| {
+            cache
+                .search(
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    UnlimitedCollector::new(Count),
+                )
+                .expect("Should succeed")
+        };
+
+        // Same query in two caches does not share entries
+        assert_eq!(search(&cache1), 2);
+        assert_eq!(search(&cache1), 2);
+        assert_eq!(search(&cache2), 2);
+
+        assert_eq!(cache1.query_cache_stats(), (1, 1));
+        assert_eq!(cache2.query_cache_stats(), (0, 1));
+
+        assert_eq!(cache1.entries().len(), 1);
+        assert_eq!(cache2.entries().len(), 1);
+
+        assert_eq!(cache1.size(), 2);
+        assert_eq!(cache2.size(), 2);
+        assert_eq!(pool.size(), 4);
+
+        // Dropping a cache releases its entries from the pool
+        drop(cache1);
+
+        assert_eq!(pool.size(), 2);
+        assert_eq!(cache2.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_shared_pool_entry_list_bounded() {
+        let index = build_test_schema();
+        let reader = index.searcher.segment_readers().first().unwrap();
+        let pool: Arc<QueryCachePool<TestQuery, TestWeighter>> =
+            Arc::new(QueryCachePool::new(1000, 100_000));
+
+        let busy = QueryCache::with_pool(pool.clone());
+        let quiet = QueryCache::with_pool(pool.clone());
+
+        let docs = Arc::new(BitSet::with_max_value(2));
+
+        // Another cache filling the pool must not let this cache's list grow with it
+        for i in 0..500 {
+            busy.insert((reader.segment_id(), TestQuery::Test(i)), docs.clone());
+        }
+
+        // Replacing the same key leaves one live entry and many dead ones
+        for _ in 0..500 {
+            quiet.insert((reader.segment_id(), TestQuery::Test(0)), docs.clone());
+        }
+
+        let list_len = quiet.entries.lock().map(|e| e.len()).unwrap_or(0);
+        assert!(list_len <= 2 + 64 + 1, "list has {} entries", list_len);
+        assert_eq!(quiet.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_shared_pool_poisoned_entry_list() {
+        let index = build_test_schema();
+        let reader = index.searcher.segment_readers().first().unwrap();
+        let pool: Arc<QueryCachePool<TestQuery, TestWeighter>> =
+            Arc::new(QueryCachePool::new(1000, 100_000));
+
+        let cache = QueryCache::with_pool(pool.clone());
+        let other = QueryCache::with_pool(pool.clone());
+
+        // Poison the entry list by panicking while holding it
+        std::thread::scope(|s| {
+            let _ = s
+                .spawn(|| {
+                    let _entries = cache.entries.lock().unwrap();
+                    std::panic::resume_unwind(Box::new("poison"));
+                })
+                .join();
+        });
+        assert!(cache.entries.is_poisoned());
+
+        let docs = Arc::new(BitSet::with_max_value(2));
+        cache.insert((reader.segment_id(), TestQuery::Test(1)), docs.clone());
+        other.insert((reader.segment_id(), TestQuery::Test(1)), docs);
+
+        // Entries inserted after poisoning are still tracked and released on drop
+        assert_eq!(cache.entries().len(), 1);
+        assert_eq!(pool.size(), 4);
+
+        drop(cache);
+
+        assert_eq!(pool.size(), 2);
+    }
+
+    #[test]
+    fn test_segment_docs() {
+        let index = build_test_schema();
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let reader = index.searcher.segment_readers().first().unwrap();
+
+        let docs = cache
+            .segment_docs(&index.schema, None, reader, &TestQuery::Test(1))
+            .expect("Should succeed");
+
+        assert_eq!(docs.len(), 2);
+        assert_eq!(cache.query_cache_stats(), (0, 1));
+
+        let docs = cache
+            .segment_docs(&index.schema, None, reader, &TestQuery::Test(1))
+            .expect("Should succeed");
+
+        assert_eq!(docs.len(), 2);
+        assert_eq!(cache.query_cache_stats(), (1, 1));
+    }
+
+    #[test]
+    fn test_search_skips_segments() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+
+        // First two segments end before 1000
+        let collector = UnlimitedCollector::new(Count);
+        let filter = || TimeRangeFilter::new(&collector, 1000, i64::MAX, ColumnCache::default());
+
+        let result = cache
+            .search(
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                filter(),
+            )
+            .expect("Should succeed");
+
+        assert_eq!(result, 10);
+        assert_eq!(cache.query_cache_stats(), (0, 2));
+
+        let result = cache
+            .search_parallel(
+                &executor,
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                filter(),
+            )
+            .expect("Should succeed");
+
+        assert_eq!(result, 10);
+        assert_eq!(cache.query_cache_stats(), (2, 2));
+    }
+
+    #[test]
+    fn test_search_parallel() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+
+        assert_eq!(index.searcher.segment_readers().len(), 4);
+
+        for limit in [1, 3, 5, 7, 19, 20, usize::MAX] {
+            let sequential = cache
+                .search(
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    PartIdCollector::new(limit, ColumnCache::default()),
+                )
+                .expect("Should succeed");
+
+            let parallel = cache
+                .search_parallel(
+                    &executor,
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    PartIdCollector::new(limit, ColumnCache::default()),
+                )
+                .expect("Should succeed");
+
+            assert_eq!(sequential.len(), limit.min(20));
+            assert_eq!(sequential, parallel);
+        }
+    }
+
+    #[test]
+    fn test_search_parallel_unlimited_collectors() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+
+        // Collectors that don't truncate when merging see every segment, so they match too
+        let search = |parallel: bool, collector| {
+            if parallel {
+                cache.search_parallel(
+                    &executor,
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    collector,
+                )
+            } else {
+                cache.search(
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    collector,
+                )
+            }
+            .expect("Should succeed")
+        };
+
+        for order in [RecordOrder::PartId, RecordOrder::EndTimeDesc] {
+            let collector = || OrderedRecordCollector::new(order, 7, ColumnCache::default());
+
+            assert_eq!(search(false, collector()), search(true, collector()));
+        }
+
+        let values = |parallel| {
+            let collector =
+                StringFieldCollector::new("col1", 2, usize::MAX, ColumnCache::default())
+                    .with_order(ValueOrder::Lexicographic);
+
+            if parallel {
+                cache.search_parallel(
+                    &executor,
+                    &index.searcher,
+                    &index.schema,
+                    Some(index.json_field),
+                    TestQuery::Test(1),
+                    collector,
+                )
+            } else {
+                cache.search(
+                    &index.searcher,
+                    &index.schema,
+                    Some(index.json_field),
+                    TestQuery::Test(1),
+                    collector,
+                )
+            }
+            .expect("Should succeed")
+        };
+
+        assert_eq!(values(false), values(true));
+        assert_eq!(
+            values(false),
+            vec![("V0".to_string(), 7), ("V1".to_string(), 7)]
+        );
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+
+        // Limit reached in the second segment, later segments are not read
+        let mut stats = SearchStats::default();
+        let results = cache
+            .search_with_stats(
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                PartIdCollector::new(7, ColumnCache::default()),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 7);
+        assert!(stats.limit_hit);
+        assert_eq!(stats.docs_examined, 7);
+        assert_eq!(stats.segments_read, 2);
+        assert_eq!(stats.segments_skipped, 0);
+        assert_eq!(stats.segments_after_limit, 2);
+        assert!(!stats.cache_served());
+
+        // Everything is cached now and no limit is hit
+        let mut stats = SearchStats::default();
+        let results = cache
+            .search_parallel_with_stats(
+                &executor,
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                PartIdCollector::new(50, ColumnCache::default()),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 20);
+        assert!(!stats.limit_hit);
+        assert_eq!(stats.docs_examined, 20);
+        assert_eq!(stats.segments_read, 4);
+        assert_eq!(stats.segments_skipped, 0);
+        assert_eq!(stats.segments_after_limit, 0);
+        assert!(!stats.cache_served());
+
+        let mut stats = SearchStats::default();
+        cache
+            .search_with_stats(
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                PartIdCollector::new(50, ColumnCache::default()),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert!(stats.cache_served());
+    }
+
+    #[test]
+    fn test_search_stats_limit_boundary() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+
+        let search = |limit: usize, parallel: bool| {
+            let mut stats = SearchStats::default();
+            let collector = PartIdCollector::new(limit, ColumnCache::default());
+            let results = if parallel {
+                cache.search_parallel_with_stats(
+                    &executor,
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    collector,
+                    &mut stats,
+                )
+            } else {
+                cache.search_with_stats(
+                    &index.searcher,
+                    &index.schema,
+                    None,
+                    TestQuery::Test(1),
+                    collector,
+                    &mut stats,
+                )
+            }
+            .expect("Should succeed");
+
+            (results.len(), stats.limit_hit)
+        };
+
+        for parallel in [false, true] {
+            // Exactly as many matches as the limit
+            assert_eq!(search(20, parallel), (20, false));
+
+            // Limit filled at the end of a segment with more segments after
+            assert_eq!(search(10, parallel), (10, true));
+
+            // Limit filled inside a segment
+            assert_eq!(search(7, parallel), (7, true));
+        }
+    }
+
+    #[test]
+    fn test_search_stats_skipped_and_after_limit() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+        let executor = Executor::multi_thread(2, "test-search-").expect("Should succeed");
+        let column_cache = ColumnCache::default();
+
+        // The first segment's docs end before the range, the limit is hit in the second
+        // segment that is read
+        let collector = PartIdCollector::new(7, column_cache.clone());
+        let filter = || TimeRangeFilter::new(&collector, 500, i64::MAX, column_cache.clone());
+
+        // Segment order isn't fixed.  The skipped segment is only reached if it comes before the
+        // second of the other segments, as the search stops once that is read.
+        let skipped_reached = index
+            .searcher
+            .segment_readers()
+            .iter()
+            .position(|r| filter().skip_segment(r).expect("Should succeed"))
+            .expect("Should succeed")
+            < 2;
+
+        let mut stats = SearchStats::default();
+        let results = cache
+            .search_with_stats(
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                filter(),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 7);
+        assert!(results.iter().all(|part_id| *part_id >= 5));
+        assert_eq!(
+            stats,
+            SearchStats {
+                limit_hit: true,
+                docs_examined: 7,
+                segments_read: 2,
+                segments_skipped: skipped_reached as u32,
+                segments_after_limit: 2 - skipped_reached as u32,
+                segments_cached: 0,
+            }
+        );
+
+        // In parallel the last segment may be read before the limit is known
+        let mut stats = SearchStats::default();
+        let results = cache
+            .search_parallel_with_stats(
+                &executor,
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                filter(),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 7);
+        assert!(stats.limit_hit);
+        // Skips are decided up front for every segment
+        assert_eq!(stats.segments_skipped, 1);
+        assert_eq!(stats.segments_read + stats.segments_after_limit, 3);
+    }
+
+    #[test]
+    fn test_search_at_limit_does_not_load_docs() {
+        let index = build_multi_segment_test_index(4, 5);
+        let cache: QueryCache<TestQuery, TestWeighter> = QueryCache::default();
+
+        let mut stats = SearchStats::default();
+        let results = cache
+            .search_with_stats(
+                &index.searcher,
+                &index.schema,
+                None,
+                TestQuery::Test(1),
+                PartIdCollector::new(5, ColumnCache::default()),
+                &mut stats,
+            )
+            .expect("Should succeed");
+
+        assert_eq!(results.len(), 5);
+        assert!(stats.limit_hit);
+
+        // The next segment is only probed for a match, so its docs are not loaded or cached
+        assert_eq!(cache.entries().len(), 1);
+    }
 }
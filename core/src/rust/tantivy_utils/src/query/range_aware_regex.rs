@@ -70,7 +70,7 @@ impl Query for RangeAwareRegexQuery {
     }
 }
 
-fn create_regex(pattern: &str) -> Result<Regex, TantivyError> {
+pub(crate) fn create_regex(pattern: &str) -> Result<Regex, TantivyError> {
     Regex::new(pattern)
         .map_err(|err| TantivyError::InvalidArgument(format!("RanageAwareRegexQuery: {err}")))
 }
@@ -0,0 +1,93 @@
+//! Stats about a single query execution
+
+/// What a search did to produce its results
+///
+/// Lets callers tell a result that was cut at the limit from one that
+/// happens to have exactly `limit` items, and see how much work was done.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Results were cut at the collector limit.  Not set when there were
+    /// exactly as many results as the limit.
+    pub limit_hit: bool,
+    /// Matching docs handed to the collector
+    pub docs_examined: u64,
+    /// Segments that were searched
+    pub segments_read: u32,
+    /// Segments not searched because the collector could not match them
+    pub segments_skipped: u32,
+    /// Segments not searched because the limit was reached first
+    pub segments_after_limit: u32,
+    /// Searched segments whose matches came from the query cache
+    pub segments_cached: u32,
+}
+
+impl SearchStats {
+    /// True if every searched segment was served from the query cache
+    pub fn cache_served(&self) -> bool {
+        self.segments_read > 0 && self.segments_cached == self.segments_read
+    }
+
+    /// Add the stats of another search, such as the same query over another index
+    pub fn add(&mut self, other: &SearchStats) {
+        self.limit_hit |= other.limit_hit;
+        self.docs_examined += other.docs_examined;
+        self.segments_read += other.segments_read;
+        self.segments_skipped += other.segments_skipped;
+        self.segments_after_limit += other.segments_after_limit;
+        self.segments_cached += other.segments_cached;
+    }
+
+    // Add the stats for one searched segment
+    pub(crate) fn add_segment(&mut self, docs_examined: u64, cached: bool) {
+        self.docs_examined += docs_examined;
+        self.segments_read += 1;
+        self.segments_cached += cached as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_served() {
+        let mut stats = SearchStats::default();
+        assert!(!stats.cache_served());
+
+        stats.add_segment(5, true);
+        assert!(stats.cache_served());
+
+        stats.add_segment(3, false);
+        assert!(!stats.cache_served());
+        assert_eq!(stats.docs_examined, 8);
+        assert_eq!(stats.segments_read, 2);
+        assert_eq!(stats.segments_cached, 1);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut stats = SearchStats::default();
+        stats.add_segment(5, true);
+
+        let mut other = SearchStats {
+            limit_hit: true,
+            segments_skipped: 2,
+            segments_after_limit: 1,
+            ..Default::default()
+        };
+        other.add_segment(3, false);
+
+        stats.add(&other);
+        assert_eq!(
+            stats,
+            SearchStats {
+                limit_hit: true,
+                docs_examined: 8,
+                segments_read: 2,
+                segments_skipped: 2,
+                segments_after_limit: 1,
+                segments_cached: 1,
+            }
+        );
+    }
+}
@@ -1,12 +1,13 @@
 //! Utilites for testing
 
-use crate::field_constants;
+use crate::field_constants::{self, facet_field_name};
 use tantivy::{
+    indexer::NoMergePolicy,
     schema::{
-        Field, JsonObjectOptions, Schema, SchemaBuilder, TextFieldIndexing, FAST, INDEXED, STORED,
-        STRING,
+        FacetOptions, Field, JsonObjectOptions, Schema, SchemaBuilder, TextFieldIndexing, FAST,
+        INDEXED, STORED, STRING,
     },
-    Index, Searcher, TantivyDocument,
+    Directory, Index, IndexSettings, Searcher, TantivyDocument, Term,
 };
 
 pub const COL1_NAME: &str = "col1";
@@ -21,9 +22,7 @@ pub struct TestIndex {
     pub json_field: Field,
 }
 
-// Allow unwraps since this is test code
-#[allow(clippy::unwrap_used)]
-pub fn build_test_schema() -> TestIndex {
+fn test_schema() -> Schema {
     let mut builder = SchemaBuilder::new();
 
     builder.add_text_field(COL1_NAME, STRING | FAST);
@@ -38,8 +37,18 @@ pub fn build_test_schema() -> TestIndex {
             .set_indexing_options(TextFieldIndexing::default().set_tokenizer("raw"))
             .set_fast(Some("raw")),
     );
+    builder.add_facet_field(
+        &facet_field_name(field_constants::LABEL_LIST),
+        FacetOptions::default(),
+    );
 
-    let schema = builder.build();
+    builder.build()
+}
+
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+pub fn build_test_schema() -> TestIndex {
+    let schema = test_schema();
 
     let index = Index::create_in_ram(schema.clone());
 
@@ -58,7 +67,8 @@ pub fn build_test_schema() -> TestIndex {
                 "json_col": {
                     "f1": "value",
                     "f2": "value2"
-                }
+                },
+                "$facet___labelList__": ["/col1", "/col2", "/f1", "/f2"]
             }"#,
         )
         .unwrap();
@@ -77,7 +87,8 @@ pub fn build_test_schema() -> TestIndex {
                 "json_col": {
                     "f1": "othervalue",
                     "f2": "othervalue2"
-                }
+                },
+                "$facet___labelList__": ["/col1", "/col2", "/f1", "/f2"]
             }"#,
         )
         .unwrap();
@@ -98,3 +109,205 @@ pub fn build_test_schema() -> TestIndex {
         json_field,
     }
 }
+
+/// Build an index with one doc missing its start time (part ID 1), one doc
+/// missing its end time (part ID 2) and one doc with start time 1000 and end
+/// time 2000 (part ID 3)
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+pub fn build_test_index_missing_times() -> TestIndex {
+    let schema = test_schema();
+
+    let index = Index::create_in_ram(schema.clone());
+
+    {
+        let mut writer = index.writer::<TantivyDocument>(50_000_000).unwrap();
+
+        for times in [
+            r#""__endTime__": 1500"#,
+            r#""__startTime__": 1500"#,
+            r#""__startTime__": 1000, "__endTime__": 2000"#,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, times)| (i + 1, times))
+        {
+            let doc = TantivyDocument::parse_json(
+                &schema,
+                &format!(
+                    r#"{{
+                        "col1": "ABC",
+                        "__partIdDv__": {},
+                        {},
+                        "__partKey__": "QUE="
+                    }}"#,
+                    times.0, times.1
+                ),
+            )
+            .unwrap();
+
+            writer.add_document(doc).unwrap();
+        }
+
+        writer.commit().unwrap();
+    }
+
+    let reader = index.reader().unwrap();
+    let searcher = reader.searcher();
+
+    let json_field = schema.get_field(JSON_COL_NAME).unwrap();
+
+    TestIndex {
+        schema,
+        searcher,
+        json_field,
+    }
+}
+
+/// Build a single segment index with a doc for each `col1` value, with the
+/// position of the value as the part ID
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+pub fn build_test_index_with_col1_values(values: impl IntoIterator<Item = String>) -> TestIndex {
+    let schema = test_schema();
+
+    let index = Index::create_in_ram(schema.clone());
+
+    {
+        let mut writer = index.writer::<TantivyDocument>(50_000_000).unwrap();
+        let col1 = schema.get_field(COL1_NAME).unwrap();
+        let part_id = schema.get_field(field_constants::PART_ID).unwrap();
+
+        for (i, value) in values.into_iter().enumerate() {
+            let mut doc = TantivyDocument::new();
+            doc.add_text(col1, value);
+            doc.add_i64(part_id, i as i64);
+
+            writer.add_document(doc).unwrap();
+        }
+
+        writer.commit().unwrap();
+    }
+
+    let reader = index.reader().unwrap();
+    let searcher = reader.searcher();
+
+    let json_field = schema.get_field(JSON_COL_NAME).unwrap();
+
+    TestIndex {
+        schema,
+        searcher,
+        json_field,
+    }
+}
+
+/// Build an index with the test schema spread over multiple segments
+///
+/// Doc N has part ID N, start time N * 100 and end time N * 100 + 50.
+/// `col1` cycles through the values V0, V1 and V2.
+pub fn build_multi_segment_test_index(segment_count: usize, docs_per_segment: usize) -> TestIndex {
+    build_multi_segment_test_index_with_deletes(segment_count, docs_per_segment, &[])
+}
+
+/// Build a multi segment index as `build_multi_segment_test_index`, then
+/// delete the docs with the given part IDs
+///
+/// Merges are disabled, so deleted docs stay in their segments and are only
+/// hidden by the alive bitset.
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+pub fn build_multi_segment_test_index_with_deletes(
+    segment_count: usize,
+    docs_per_segment: usize,
+    deleted_part_ids: &[i64],
+) -> TestIndex {
+    let schema = test_schema();
+
+    let index = Index::create_in_ram(schema.clone());
+
+    write_multi_segment_test_docs(&index, segment_count, docs_per_segment, deleted_part_ids);
+
+    let reader = index.reader().unwrap();
+    let searcher = reader.searcher();
+
+    let json_field = schema.get_field(JSON_COL_NAME).unwrap();
+
+    TestIndex {
+        schema,
+        searcher,
+        json_field,
+    }
+}
+
+/// Create an index with the test schema in `directory`, with the docs of
+/// `build_multi_segment_test_index`
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+pub fn create_multi_segment_test_index_in<D: Directory>(
+    directory: D,
+    segment_count: usize,
+    docs_per_segment: usize,
+) -> Index {
+    let index = Index::create(directory, test_schema(), IndexSettings::default()).unwrap();
+
+    write_multi_segment_test_docs(&index, segment_count, docs_per_segment, &[]);
+
+    index
+}
+
+// Allow unwraps since this is test code
+#[allow(clippy::unwrap_used)]
+fn write_multi_segment_test_docs(
+    index: &Index,
+    segment_count: usize,
+    docs_per_segment: usize,
+    deleted_part_ids: &[i64],
+) {
+    let schema = index.schema();
+    let mut writer = index.writer::<TantivyDocument>(50_000_000).unwrap();
+    writer.set_merge_policy(Box::new(NoMergePolicy));
+
+    for segment in 0..segment_count {
+        for i in 0..docs_per_segment {
+            let id = (segment * docs_per_segment) + i;
+
+            let doc = TantivyDocument::parse_json(
+                &schema,
+                &format!(
+                    r#"{{
+                        "col1": "V{}",
+                        "col2": "def",
+                        "__partIdDv__": {},
+                        "__startTime__": {},
+                        "__endTime__": {},
+                        "__partKey__": "QUE=",
+                        "json_col": {{
+                            "f1": "value"
+                        }},
+                        "$facet___labelList__": ["/col1", "/col2", "/f1"]
+                    }}"#,
+                    id % 3,
+                    id,
+                    id * 100,
+                    (id * 100) + 50
+                ),
+            )
+            .unwrap();
+
+            writer.add_document(doc).unwrap();
+        }
+
+        writer.commit().unwrap();
+    }
+
+    if !deleted_part_ids.is_empty() {
+        let part_id_field = schema.get_field(field_constants::PART_ID).unwrap();
+        for part_id in deleted_part_ids {
+            writer.delete_term(Term::from_field_i64(part_id_field, *part_id));
+        }
+
+        writer.commit().unwrap();
+    }
+
+    writer.wait_merging_threads().unwrap();
+}
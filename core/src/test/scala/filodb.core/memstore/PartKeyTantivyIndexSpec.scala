@@ -1,17 +1,20 @@
 package filodb.core.memstore
 
-import filodb.core.GdeltTestData.dataset6
+import filodb.core.GdeltTestData.{dataset6, partKeyFromRecords, readers, records}
 import filodb.core.{DatasetRef, TestData}
 import filodb.core.binaryrecord2.RecordBuilder
 import filodb.core.metadata.PartitionSchema
 import filodb.core.query.ColumnFilter
 import filodb.core.query.Filter.{Equals, EqualsRegex, In}
+import filodb.memory.format.UnsafeUtils.ZeroPointer
+import filodb.memory.format.ZeroCopyUTF8String.StringToUTF8
 import org.scalatest.BeforeAndAfter
 import org.scalatest.funspec.AnyFunSpec
 import org.scalatest.matchers.should.Matchers
 import org.scalatest.time.SpanSugar.convertIntToGrainOfTime
 
 import java.io.File
+import scala.collection.mutable.ArrayBuffer
 
 class PartKeyTantivyIndexSpec extends AnyFunSpec with Matchers with BeforeAndAfter with PartKeyIndexRawSpec {
   val keyIndex = new PartKeyTantivyIndex(dataset6.ref, dataset6.schema.partition, 0, 1.hour.toMillis,
@@ -39,8 +42,231 @@ class PartKeyTantivyIndexSpec extends AnyFunSpec with Matchers with BeforeAndAft
     new PartKeyTantivyIndex(ref, schema, shardNum, retentionMillis, diskLocation, lifecycleManager)
   }
 
+  // Part keys of the first n GDELT rows
+  private def gdeltPartKeys(n: Int): Seq[Array[Byte]] = {
+    partKeyFromRecords(dataset6, records(dataset6, readers.take(n)), Some(partBuilder))
+      .map(addr => partKeyOnHeap(dataset6.partKeySchema, ZeroPointer, addr))
+  }
+
+  // Add part keys with their position as part ID, starting at firstPartId
+  private def addPartKeys(index: PartKeyTantivyIndex, partKeys: Seq[Array[Byte]], firstPartId: Int = 0): Unit = {
+    partKeys.zipWithIndex.foreach { case (pk, i) =>
+      index.addPartKey(pk, firstPartId + i, 1000)()
+    }
+    index.refreshReadersBlocking()
+  }
+
   it should behave like commonPartKeyTests(keyIndex, partBuilder)
 
+  it("should report stats of the last query on the query thread") {
+    partKeyFromRecords(dataset6, records(dataset6, readers.take(10)), Some(partBuilder))
+      .zipWithIndex.foreach { case (addr, i) =>
+        keyIndex.addPartKey(partKeyOnHeap(dataset6.partKeySchema, ZeroPointer, addr), i, System.currentTimeMillis())()
+      }
+    keyIndex.refreshReadersBlocking()
+
+    val (limited, limitedStats) = keyIndex.withQueryStats(keyIndex.partIdsFromFilters(Nil, 0, Long.MaxValue, 4))
+    limited.length shouldEqual 4
+    limitedStats.limitHit shouldEqual true
+
+    val (all, allStats) = keyIndex.withQueryStats(keyIndex.partIdsFromFilters(Nil, 0, Long.MaxValue))
+    all.length shouldEqual 10
+    allStats.limitHit shouldEqual false
+    allStats.segmentsAfterLimit shouldEqual 0
+    allStats.docsExamined shouldEqual 10
+    keyIndex.lastQueryStats() shouldEqual allStats
+  }
+
+  it("should return label values with counts in either order, paged and filtered") {
+    addPartKeys(keyIndex, gdeltPartKeys(10))
+
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 2) shouldEqual
+      Seq(TermInfo("GOV".utf8, 3), TermInfo("AGR".utf8, 2))
+
+    val lexicographic = PartKeyTantivyIndex.LabelValuesSortLexicographic
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 3, lexicographic) shouldEqual
+      Seq(TermInfo("AFR".utf8, 1), TermInfo("AGR".utf8, 2), TermInfo("CHN".utf8, 1))
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 2, lexicographic, Some("CHN")) shouldEqual
+      Seq(TermInfo("COP".utf8, 1), TermInfo("CVL".utf8, 1))
+
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 10, lexicographic,
+      matchType = PartKeyTantivyIndex.LabelValuesMatchPrefix, matchValue = "C").map(_.term.toString) shouldEqual
+      Seq("CHN", "COP", "CVL")
+
+    // Negative limits return nothing rather than everything
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", -1) shouldBe empty
+
+    // Paging is only supported in lexicographic order
+    intercept[RuntimeException] {
+      keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 2, after = Some("CHN"))
+    }
+  }
+
+  it("should count series by label values, largest groups first") {
+    addPartKeys(keyIndex, gdeltPartKeys(10))
+
+    keyIndex.countByLabels(Nil, Seq("Actor2Code"), 0, Long.MaxValue, 2) shouldEqual Seq(
+      PartKeyTantivyIndex.LabelGroupCount(Seq("GOV"), 3),
+      PartKeyTantivyIndex.LabelGroupCount(Seq("AGR"), 2))
+
+    // Values are decoded in label order
+    keyIndex.countByLabels(Nil, Seq("Actor2Code", "Actor2Name"), 0, Long.MaxValue, 1) shouldEqual Seq(
+      PartKeyTantivyIndex.LabelGroupCount(Seq("AGR", "FARMER"), 2))
+
+    val gov = Seq(ColumnFilter("Actor2Code", Equals("GOV")))
+    keyIndex.countByLabels(gov, Seq("Actor2Name"), 0, Long.MaxValue, 10) shouldEqual Seq(
+      PartKeyTantivyIndex.LabelGroupCount(Seq("REGIME"), 2),
+      PartKeyTantivyIndex.LabelGroupCount(Seq("DEPUTY PRIME MINISTER"), 1))
+
+    // Missing labels decode as empty values
+    keyIndex.countByLabels(gov, Seq("missing", "Actor2Code"), 0, Long.MaxValue, 10) shouldEqual Seq(
+      PartKeyTantivyIndex.LabelGroupCount(Seq("", "GOV"), 3))
+  }
+
+  it("should visit every matching part key record in batches from a cursor") {
+    val partKeys = gdeltPartKeys(11)
+    addPartKeys(keyIndex, partKeys.take(10))
+
+    val visited = new ArrayBuffer[PartKeyLuceneIndexRecord]()
+    val count = keyIndex.foreachPartKeyRecordMatchingFilter(Nil, 0, Long.MaxValue, batchSize = 3) { record =>
+      // Batches keep reading the index as it was when the cursor opened
+      if (visited.isEmpty) {
+        addPartKeys(keyIndex, partKeys.drop(10), 10)
+      }
+      visited += record
+    }
+
+    count shouldEqual 10
+    visited.map(_.partKey.toSeq) should contain theSameElementsAs partKeys.take(10).map(_.toSeq)
+
+    val gov = Seq(ColumnFilter("Actor2Code", Equals("GOV")))
+    keyIndex.foreachPartKeyRecordMatchingFilter(gov, 0, Long.MaxValue, batchSize = 1)(_ => ()) shouldEqual 3
+    keyIndex.foreachPartKeyRecordMatchingFilter(Nil, 0, Long.MaxValue)(_ => ()) shouldEqual 11
+  }
+
+  it("should look up part IDs for a batch of part keys") {
+    val partKeys = gdeltPartKeys(11)
+    addPartKeys(keyIndex, partKeys.take(10))
+
+    // Results line up with the input, with -1 for part keys not in the index
+    keyIndex.partIdsFromPartKeys(Seq(partKeys(5), partKeys(10), partKeys(0), partKeys(7))) shouldEqual
+      Array(5, -1, 0, 7)
+    keyIndex.partIdsFromPartKeys(Seq(Array.empty[Byte], partKeys(3))) shouldEqual Array(-1, 3)
+    keyIndex.partIdsFromPartKeys(Nil) shouldBe empty
+  }
+
+  it("should return label names with series counts") {
+    addPartKeys(keyIndex, gdeltPartKeys(10))
+
+    val all = keyIndex.labelNamesWithCounts(Nil, 0, Long.MaxValue, 10)
+    all.names should contain theSameElementsAs Seq("_type_" -> 10L, "Actor2Code" -> 10L, "Actor2Name" -> 10L)
+    all.truncated shouldEqual false
+
+    val gov = Seq(ColumnFilter("Actor2Code", Equals("GOV")))
+    keyIndex.labelNamesWithCounts(gov, 0, Long.MaxValue, 10).names.map(_._2).distinct shouldEqual Seq(3L)
+
+    val limited = keyIndex.labelNamesWithCounts(Nil, 0, Long.MaxValue, 1)
+    limited.names.length shouldEqual 1
+    limited.truncated shouldEqual true
+  }
+
+  it("should read the index as it was when a searcher was pinned") {
+    val partKeys = gdeltPartKeys(11)
+    addPartKeys(keyIndex, partKeys.take(10))
+
+    val pin = keyIndex.pinSearcher()
+    addPartKeys(keyIndex, partKeys.drop(10), 10)
+
+    val live = PartKeyTantivyIndex.LiveSearcher
+    val order = PartKeyTantivyIndex.ResultOrderPartId
+    keyIndex.partIdsFromFilters(Nil, 0, Long.MaxValue, 100, order, pin).toIterable().toSeq shouldEqual (0 until 10)
+    keyIndex.partIdsFromFilters(Nil, 0, Long.MaxValue, 100, order, live).toIterable().toSeq shouldEqual (0 until 11)
+
+    keyIndex.partKeyRecordsFromFilters(Nil, 0, Long.MaxValue, 100, order, pin).length shouldEqual 10
+    keyIndex.labelValuesV2(Nil, 0, Long.MaxValue, "Actor2Code", 100, pin = pin).map(_.freq).sum shouldEqual 10
+    keyIndex.labelNamesWithCounts(Nil, 0, Long.MaxValue, 10, pin).names.map(_._2).max shouldEqual 10
+    keyIndex.countByLabels(Nil, Seq("_type_"), 0, Long.MaxValue, 10, pin).map(_.count) shouldEqual Seq(10)
+    keyIndex.cardinalityRollup(Nil, Seq("Actor2Code"), 0, Long.MaxValue, pin).totalCount shouldEqual 10
+    keyIndex.querySeriesLabels(Nil, Seq("Actor2Code"), 0, Long.MaxValue, 100, pin).length shouldEqual 10
+    keyIndex.samplePartKeyRecords(Nil, 0, Long.MaxValue, 100, 1, pin).length shouldEqual 10
+    keyIndex.foreachPartKeyStillIngesting((_, _) => (), pin) shouldEqual 10
+    keyIndex.foreachPartKeyRecordMatchingFilter(Nil, 0, Long.MaxValue, pin = pin)(_ => ()) shouldEqual 10
+    keyIndex.partKeysFromPartIds(Iterator(0, 10), pin).contains(10) shouldEqual false
+    keyIndex.partKeysFromPartIds(Iterator(0, 10)).contains(10) shouldEqual true
+    keyIndex.churnHistogram(Nil, 0, 2000, 1000, pin).started.sum shouldEqual 10
+    keyIndex.indexNames(10, pin) should contain theSameElementsAs keyIndex.indexNames(10)
+    val gov = Seq(ColumnFilter("Actor2Code", Equals("GOV")))
+    keyIndex.singlePartKeyFromFilters(gov, 0, Long.MaxValue, pin) shouldBe defined
+
+    // Released pins can no longer be read from
+    keyIndex.releaseSearcher(pin) shouldEqual true
+    keyIndex.releaseSearcher(pin) shouldEqual false
+    intercept[RuntimeException] {
+      keyIndex.partIdsFromFilters(Nil, 0, Long.MaxValue, 100, order, pin)
+    }
+  }
+
+  it("should fan out label values and part key records over several indexes") {
+    val first = new PartKeyTantivyIndex(dataset6.ref, dataset6.schema.partition, 1, 1.hour.toMillis)
+    val second = new PartKeyTantivyIndex(dataset6.ref, dataset6.schema.partition, 2, 1.hour.toMillis)
+
+    try {
+      val partKeys = gdeltPartKeys(10)
+      addPartKeys(first, partKeys.take(5))
+      addPartKeys(second, partKeys.drop(5), 5)
+      val indexes = Seq(first, second)
+
+      PartKeyTantivyIndex.labelValuesFanOut(indexes, Nil, 0, Long.MaxValue, "Actor2Code", 2) shouldEqual
+        Seq(TermInfo("GOV".utf8, 3), TermInfo("AGR".utf8, 2))
+      PartKeyTantivyIndex.labelValuesFanOut(indexes, Nil, 0, Long.MaxValue, "Actor2Code", 3,
+        PartKeyTantivyIndex.LabelValuesSortLexicographic) shouldEqual
+        Seq(TermInfo("AFR".utf8, 1), TermInfo("AGR".utf8, 2), TermInfo("CHN".utf8, 1))
+      PartKeyTantivyIndex.labelValuesFanOut(Nil, Nil, 0, Long.MaxValue, "Actor2Code", 2) shouldBe empty
+
+      // Records are taken in index order up to the limit
+      val records = PartKeyTantivyIndex.partKeyRecordsFanOut(indexes, Nil, 0, Long.MaxValue, 7)
+      records.map(_._1) shouldEqual Seq.fill(5)(first) ++ Seq.fill(2)(second)
+      records.take(5).map(_._2.partKey.toSeq) should contain theSameElementsAs partKeys.take(5).map(_.toSeq)
+    } finally {
+      second.closeIndex()
+      first.closeIndex()
+    }
+  }
+
+  it("should free the fan out pool once, after the last index closes and fan out calls finish") {
+    // Close some or all of the indexes while a fan out call is running, and the rest after it returns
+    for (closedDuringCall <- 0 to 3) {
+      var created = 0L
+      val freed = new ArrayBuffer[Long]()
+      val pool = new FanOutPoolRefs(() => { created += 1; created }, freed += _)
+
+      (0 until 3).foreach(_ => pool.indexOpened())
+      pool.withPool { handle =>
+        handle shouldEqual 1L
+        (0 until closedDuringCall).foreach(_ => pool.indexClosed())
+        freed shouldBe empty
+      }
+
+      (closedDuringCall until 3).foreach { _ =>
+        freed shouldBe empty
+        pool.indexClosed()
+      }
+      freed shouldEqual Seq(1L)
+      pool.allocated shouldEqual false
+
+      // Extra closes do not free it again
+      pool.indexClosed()
+      freed shouldEqual Seq(1L)
+    }
+
+    // A pool that was never used is never freed
+    val freed = new ArrayBuffer[Long]()
+    val unused = new FanOutPoolRefs(() => 1L, freed += _)
+    unused.indexOpened()
+    unused.indexClosed()
+    freed shouldBe empty
+  }
+
   it("should encode equals queries correctly") {
     val builder = new TantivyQueryBuilder()
 